@@ -0,0 +1,124 @@
+//! Drive a window with a synthetic click instead of the OS, and check that
+//! the state and the rendered frame changed, like an integration test would.
+//! Exits with a failure if they didn't within a few seconds.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use corgo::{
+    use_mounted,
+    window::{RendererEvent, Window},
+    WindowConfig,
+};
+use dioxus::{
+    core::{ElementId, EventPriority, UserEvent},
+    events::MouseData,
+    html::{
+        geometry::{ClientPoint, Coordinates, ElementPoint, PagePoint, ScreenPoint},
+        input_data::{keyboard_types::Modifiers, MouseButton, MouseButtonSet},
+    },
+    prelude::*,
+};
+use glutin::{
+    event::{Event, StartCause},
+    event_loop::{ControlFlow, EventLoop},
+    platform::run_return::EventLoopExtRunReturn,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+// the id of the div plus one, once it is mounted
+static TARGET: AtomicUsize = AtomicUsize::new(0);
+static CLICKED: AtomicBool = AtomicBool::new(false);
+
+fn app(cx: Scope) -> Element {
+    let clicked = use_state(&cx, || false);
+    let color = if *clicked.get() { "green" } else { "red" };
+    use_mounted(&cx, |div| TARGET.store(div.id().0 + 1, Ordering::Relaxed));
+
+    cx.render(rsx! {
+        div {
+            width: "100%",
+            height: "100%",
+            background_color: "{color}",
+            onclick: move |_| {
+                CLICKED.store(true, Ordering::Relaxed);
+                clicked.set(true);
+            },
+        }
+    })
+}
+
+fn click(element: ElementId) -> UserEvent {
+    let point = (0.0, 0.0);
+    UserEvent {
+        scope_id: None,
+        priority: EventPriority::Medium,
+        element: Some(element),
+        name: "click",
+        data: Arc::new(MouseData::new(
+            Coordinates::new(
+                ScreenPoint::from(point),
+                ClientPoint::from(point),
+                ElementPoint::from(point),
+                PagePoint::from(point),
+            ),
+            Some(MouseButton::Primary),
+            MouseButtonSet::empty(),
+            Modifiers::empty(),
+        )),
+        bubbles: true,
+    }
+}
+
+fn main() {
+    let mut event_loop = EventLoop::with_user_event();
+    let window = Window::new(app, (), WindowConfig::default(), &event_loop);
+    let deadline = Instant::now() + TIMEOUT;
+    let mut dispatched = false;
+    let mut green = false;
+
+    event_loop.run_return(|event, _, control_flow| {
+        *control_flow = ControlFlow::WaitUntil(deadline);
+
+        match event {
+            // the click didn't repaint the div in time
+            Event::NewEvents(StartCause::ResumeTimeReached { .. }) => {
+                *control_flow = ControlFlow::Exit;
+            }
+            Event::UserEvent(RendererEvent::FrameRendered(_)) => {
+                if !dispatched {
+                    // the div reports its id once it is laid out, before
+                    // the frame is painted
+                    if let Some(id) = TARGET.load(Ordering::Relaxed).checked_sub(1) {
+                        window.dispatch_event(click(ElementId(id)));
+                        dispatched = true;
+                    }
+                } else {
+                    let frame = window.capture_frame();
+                    let pixel = frame.get_pixel(frame.width() / 2, frame.height() / 2);
+                    if pixel[1] > pixel[0] {
+                        green = true;
+                        *control_flow = ControlFlow::Exit;
+                    }
+                }
+            }
+            _ => (),
+        }
+
+        window.send_event(event);
+    });
+
+    assert!(dispatched, "the div was never mounted");
+    assert!(
+        CLICKED.load(Ordering::Relaxed),
+        "the click didn't reach the div"
+    );
+    assert!(green, "the div isn't green {:?} after the launch", TIMEOUT);
+    println!("the click turned the div green");
+}
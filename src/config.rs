@@ -0,0 +1,242 @@
+use std::time::Duration;
+
+use dioxus::html::input_data::keyboard_types::Modifiers;
+use glutin::{
+    dpi::{LogicalPosition, LogicalSize},
+    event::VirtualKeyCode,
+    window::WindowBuilder,
+};
+use webrender::api::ColorF;
+
+use crate::{
+    state::BoxSizing,
+    style::{Keyframe, Stylesheet, USER_AGENT_CSS},
+};
+
+/// A keyboard shortcut, like Ctrl+Q. The modifiers must match exactly.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Accelerator {
+    pub modifiers: Modifiers,
+    pub key: VirtualKeyCode,
+}
+
+impl Accelerator {
+    pub fn new(modifiers: Modifiers, key: VirtualKeyCode) -> Self {
+        Self { modifiers, key }
+    }
+}
+
+/// The timings and sizes of a rendered frame, given to the
+/// [`WindowConfig::with_frame_stats`] callback.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FrameStats {
+    /// The time spent computing the layout since the last frame.
+    pub layout_time: Duration,
+    /// The time spent building the display list of the frame.
+    pub display_list_time: Duration,
+    /// The number of nodes in the dom.
+    pub node_count: usize,
+    /// The number of nodes repainted in the frame.
+    pub dirty_node_count: usize,
+}
+
+/// Configuration of a window.
+pub struct WindowConfig {
+    pub(crate) stylesheet: Stylesheet,
+    pub(crate) title: String,
+    pub(crate) inner_size: Option<LogicalSize<f64>>,
+    pub(crate) position: Option<LogicalPosition<f64>>,
+    pub(crate) clear_color: ColorF,
+    pub(crate) transparent: bool,
+    pub(crate) decorations: bool,
+    pub(crate) always_on_top: bool,
+    pub(crate) box_sizing: BoxSizing,
+    pub(crate) user_agent_styles: bool,
+    pub(crate) frame_interval: Option<Duration>,
+    pub(crate) wheel_line_height: f32,
+    pub(crate) close_on_escape: bool,
+    pub(crate) accelerators: Vec<(Accelerator, Box<dyn FnMut() + Send>)>,
+    pub(crate) on_frame_stats: Option<Box<dyn FnMut(FrameStats) + Send>>,
+    pub(crate) window_builder: Option<Box<dyn FnOnce(WindowBuilder) -> WindowBuilder + Send>>,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            stylesheet: Stylesheet::default(),
+            title: "corgo".to_string(),
+            inner_size: None,
+            position: None,
+            clear_color: ColorF::new(0.3, 0.0, 0.0, 0.5),
+            transparent: true,
+            decorations: true,
+            always_on_top: false,
+            box_sizing: BoxSizing::BorderBox,
+            user_agent_styles: false,
+            frame_interval: None,
+            wheel_line_height: 40.0,
+            close_on_escape: false,
+            accelerators: Vec::new(),
+            on_frame_stats: None,
+            window_builder: None,
+        }
+    }
+}
+
+impl WindowConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add css rules applied to the elements matching their selectors. Only
+    /// tag, class and id selectors are supported. Inline styles take precedence.
+    pub fn with_stylesheet(mut self, css: &str) -> Self {
+        self.stylesheet.add(css);
+        self
+    }
+
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    /// Add a named set of keyframes for the `animation` property, like an
+    /// `@keyframes` rule of the stylesheet.
+    pub fn with_keyframes(mut self, name: impl Into<String>, keyframes: Vec<Keyframe>) -> Self {
+        self.stylesheet.add_keyframes(name, keyframes);
+        self
+    }
+
+    /// Set the initial size of the window in logical pixels.
+    pub fn with_inner_size(mut self, width: f64, height: f64) -> Self {
+        self.inner_size = Some(LogicalSize::new(width, height));
+        self
+    }
+
+    /// Set the initial position of the top left corner of the window,
+    /// decorations included, in logical pixels from the top left corner of
+    /// the desktop. The platform places the window by default.
+    pub fn with_position(mut self, x: f64, y: f64) -> Self {
+        self.position = Some(LogicalPosition::new(x, y));
+        self
+    }
+
+    /// Set the color the window is cleared with before each frame, with
+    /// straight alpha. The alpha is only used if the window is transparent.
+    pub fn with_clear_color(mut self, color: ColorF) -> Self {
+        self.clear_color = color;
+        self
+    }
+
+    pub fn with_transparent(mut self, transparent: bool) -> Self {
+        self.transparent = transparent;
+        self
+    }
+
+    pub fn with_decorations(mut self, decorations: bool) -> Self {
+        self.decorations = decorations;
+        self
+    }
+
+    /// Keep the window above the other windows, like for tool palettes.
+    pub fn with_always_on_top(mut self, always_on_top: bool) -> Self {
+        self.always_on_top = always_on_top;
+        self
+    }
+
+    /// Set the box sizing of the elements that don't have a `box-sizing`
+    /// style. Defaults to border-box, which is how elements were always sized,
+    /// so content-box is opt-in.
+    pub fn with_box_sizing(mut self, box_sizing: BoxSizing) -> Self {
+        self.box_sizing = box_sizing;
+        self
+    }
+
+    /// Cap the number of frames rendered per second. The repaints requested
+    /// within a frame interval are coalesced into a single frame. Not capped
+    /// by default.
+    pub fn with_max_frame_rate(mut self, fps: u32) -> Self {
+        self.frame_interval = Some(Duration::from_secs(1) / fps.max(1));
+        self
+    }
+
+    /// Set the distance in layout units scrolled by a line of a mouse wheel,
+    /// for the wheels that report lines instead of pixels. Defaults to 40.
+    pub fn with_wheel_line_height(mut self, line_height: f32) -> Self {
+        self.wheel_line_height = line_height;
+        self
+    }
+
+    /// Close the window when Escape is pressed, like a dialog. The keydown is
+    /// dispatched first, and the focused element or one of its ancestors can
+    /// keep the window open with `dioxus-prevent-default="onkeydown"`.
+    /// Disabled by default.
+    pub fn with_close_on_escape(mut self, close_on_escape: bool) -> Self {
+        self.close_on_escape = close_on_escape;
+        self
+    }
+
+    /// Enable the default styles of the html elements, like the size of the
+    /// headings. Disabled by default so apps that style everything themselves
+    /// keep their look.
+    pub fn with_user_agent_styles(mut self, enabled: bool) -> Self {
+        self.user_agent_styles = enabled;
+        self
+    }
+
+    /// The clear color to give webrender. Webrender blends with premultiplied
+    /// alpha and the compositor presents the window with it, so a straight
+    /// alpha color would make the empty regions of the window too bright.
+    pub(crate) fn renderer_clear_color(&self) -> ColorF {
+        if self.transparent {
+            self.clear_color.premultiplied()
+        } else {
+            ColorF {
+                a: 1.0,
+                ..self.clear_color
+            }
+        }
+    }
+
+    /// Call `callback` when the accelerator is pressed, whatever element is
+    /// focused. The key press is not dispatched to the VirtualDom and a
+    /// [`RendererEvent::Accelerator`](crate::window::RendererEvent::Accelerator)
+    /// is sent to the event loop. The callback runs on the window's thread.
+    pub fn with_accelerator(
+        mut self,
+        accelerator: Accelerator,
+        callback: impl FnMut() + Send + 'static,
+    ) -> Self {
+        self.accelerators.push((accelerator, Box::new(callback)));
+        self
+    }
+
+    /// Call `callback` after each rendered frame with its timings, for
+    /// example to show a performance overlay. The callback runs on the
+    /// window's thread.
+    pub fn with_frame_stats(mut self, callback: impl FnMut(FrameStats) + Send + 'static) -> Self {
+        self.on_frame_stats = Some(Box::new(callback));
+        self
+    }
+
+    /// Customize the glutin WindowBuilder, for the options the config doesn't
+    /// have like the position or platform specific flags. The function gets
+    /// the builder with the options of the config applied.
+    pub fn with_window_builder(
+        mut self,
+        f: impl FnOnce(WindowBuilder) -> WindowBuilder + Send + 'static,
+    ) -> Self {
+        self.window_builder = Some(Box::new(f));
+        self
+    }
+
+    /// Take the stylesheet out of the config, with the user agent styles if
+    /// they are enabled.
+    pub(crate) fn take_stylesheet(&mut self) -> Stylesheet {
+        let mut stylesheet = std::mem::take(&mut self.stylesheet);
+        if self.user_agent_styles {
+            stylesheet.add_user_agent(USER_AGENT_CSS);
+        }
+        stylesheet
+    }
+}
@@ -0,0 +1,578 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+    sync::Arc,
+    time::Instant,
+};
+
+use arboard::Clipboard;
+use dioxus::core::{ElementId, ScopeId, ScopeState, UserEvent, VNode};
+use fxhash::FxHashMap;
+use taffy::{
+    node::Node,
+    prelude::{Number, Size},
+};
+use webrender::api::units::{LayoutPoint, LayoutRect, LayoutSize, LayoutVector2D};
+
+use crate::state::Measurer;
+
+/// State of a window shared with its components. It is provided as a context
+/// of the root component, use [`use_window`] to access it.
+#[derive(Clone)]
+pub struct WindowContext {
+    size: Rc<Cell<LayoutSize>>,
+    resize_listeners: Rc<RefCell<FxHashMap<ScopeId, Arc<dyn Fn() + Send + Sync>>>>,
+    focused: Rc<Cell<bool>>,
+    focus_listeners: Rc<RefCell<FxHashMap<ScopeId, Arc<dyn Fn() + Send + Sync>>>>,
+    layouts: Rc<RefCell<FxHashMap<ElementId, (LayoutRect, Node)>>>,
+    measurer: Measurer,
+    /// The components observing the layout of their root element, with the
+    /// rect they were last rendered with.
+    layout_listeners: Rc<RefCell<FxHashMap<ScopeId, LayoutListener>>>,
+    /// The components waiting for their root element to be laid out.
+    mounted_listeners: Rc<RefCell<FxHashMap<ScopeId, Box<dyn FnOnce(ElementHandle)>>>>,
+    /// The scrolls requested by the components, applied by the window task.
+    scroll_requests: Rc<RefCell<Vec<ScrollRequest>>>,
+    /// The pointer capture changes requested by the components, applied by
+    /// the window task.
+    pointer_capture_requests: Rc<RefCell<Vec<PointerCaptureRequest>>>,
+    /// The always on top state requested by the components, applied by the
+    /// window.
+    always_on_top_request: Rc<Cell<Option<bool>>>,
+    /// The callbacks scheduled by the components, run by the window task
+    /// once their instant is reached.
+    wakes: Rc<RefCell<Vec<(Instant, Box<dyn FnOnce()>)>>>,
+    /// The callbacks waiting for the next frame.
+    animation_frames: Rc<RefCell<Vec<Box<dyn FnOnce(Instant)>>>>,
+    /// The points whose focusable element was asked for, answered by the
+    /// window task.
+    focusable_queries: Rc<RefCell<Vec<FocusableQuery>>>,
+    /// The events waiting to be dispatched by the window task, with the
+    /// touches of the touch events.
+    events: Rc<RefCell<Vec<(UserEvent, Option<TouchList>)>>>,
+    /// The target of the event whose handlers are running.
+    event_target: Rc<RefCell<Option<EventTarget>>>,
+    /// The touches of the touch event whose handlers are running.
+    event_touches: Rc<RefCell<Option<TouchList>>>,
+    /// Connected on the first use.
+    clipboard: Rc<RefCell<Option<Clipboard>>>,
+}
+
+pub(crate) type FocusableQuery = (LayoutPoint, Box<dyn FnOnce(Option<ElementId>)>);
+
+/// The element an event was dispatched to, like `event.target` in a browser.
+/// A handler delegated to an ancestor can identify the element by its
+/// attributes.
+#[derive(Clone, PartialEq, Debug)]
+pub struct EventTarget {
+    pub element: ElementId,
+    /// The `id` and `data-*` attributes of the element.
+    pub attributes: Vec<(String, String)>,
+}
+
+impl EventTarget {
+    /// The value of an `id` or `data-*` attribute of the element, like
+    /// `data-index`.
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// A finger on the screen, like a `Touch` in a browser.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Touch {
+    /// Identifies the finger for as long as it touches the screen. It is the
+    /// `pointer_id` of its pointer events.
+    pub identifier: i32,
+    /// The element the touch started on, which gets all its events.
+    pub target: Option<ElementId>,
+    /// The position of the finger in the window, in layout units.
+    pub position: LayoutPoint,
+    /// The pressure of the finger, from 0 to 1.
+    pub force: f32,
+}
+
+/// The touches of a touch event, like `touches` and `changedTouches` in a
+/// browser. The touch data of the events only has the modifier keys.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct TouchList {
+    /// The fingers on the screen, in the order they touched it. A finger
+    /// lifted by a `touchend` isn't in it anymore.
+    pub touches: Vec<Touch>,
+    /// The fingers whose change caused the event.
+    pub changed_touches: Vec<Touch>,
+}
+
+pub(crate) enum ScrollRequest {
+    To(ElementId, LayoutVector2D),
+    IntoView(ElementId),
+}
+
+pub(crate) enum PointerCaptureRequest {
+    Set(ElementId),
+    Release(ElementId),
+}
+
+struct LayoutListener {
+    rect: Option<LayoutRect>,
+    update: Arc<dyn Fn() + Send + Sync>,
+}
+
+impl WindowContext {
+    pub(crate) fn new(size: LayoutSize, measurer: Measurer) -> Self {
+        Self {
+            size: Rc::new(Cell::new(size)),
+            resize_listeners: Rc::default(),
+            focused: Rc::new(Cell::new(true)),
+            focus_listeners: Rc::default(),
+            layouts: Rc::default(),
+            measurer,
+            layout_listeners: Rc::default(),
+            mounted_listeners: Rc::default(),
+            scroll_requests: Rc::default(),
+            pointer_capture_requests: Rc::default(),
+            always_on_top_request: Rc::default(),
+            wakes: Rc::default(),
+            animation_frames: Rc::default(),
+            focusable_queries: Rc::default(),
+            events: Rc::default(),
+            event_target: Rc::default(),
+            event_touches: Rc::default(),
+            clipboard: Rc::default(),
+        }
+    }
+
+    /// The size of the window in layout units.
+    pub fn size(&self) -> LayoutSize {
+        self.size.get()
+    }
+
+    /// Update the size and rerender the components observing it.
+    pub(crate) fn set_size(&self, size: LayoutSize) {
+        if self.size.replace(size) != size {
+            for update in self.resize_listeners.borrow().values() {
+                update();
+            }
+        }
+    }
+
+    /// Returns true if the window has the keyboard focus.
+    pub fn is_focused(&self) -> bool {
+        self.focused.get()
+    }
+
+    /// Update the focus of the window and rerender the components observing
+    /// it.
+    pub(crate) fn set_focused(&self, focused: bool) {
+        if self.focused.replace(focused) != focused {
+            for update in self.focus_listeners.borrow().values() {
+                update();
+            }
+        }
+    }
+
+    /// The rect of a mounted element in window coordinates, in layout units,
+    /// as of the last layout.
+    pub fn element_rect(&self, id: ElementId) -> Option<LayoutRect> {
+        self.layouts.borrow().get(&id).map(|(rect, _)| *rect)
+    }
+
+    /// The size a mounted element would have if it was laid out in the
+    /// available space, in layout units, like to know if a text fits before
+    /// truncating it. A `None` width or height leaves the axis unbounded. The
+    /// layout of the window is not changed.
+    pub fn measure(
+        &self,
+        id: ElementId,
+        width: Option<f32>,
+        height: Option<f32>,
+    ) -> Option<LayoutSize> {
+        let (_, node) = *self.layouts.borrow().get(&id)?;
+        let available = Size {
+            width: width.map_or(Number::Undefined, Number::Defined),
+            height: height.map_or(Number::Undefined, Number::Defined),
+        };
+        let size = self.measurer.measure(node, available);
+        Some(LayoutSize::new(size.width, size.height))
+    }
+
+    /// Scroll a scroll container to an offset in layout units. The offset is
+    /// clamped so the content stays in view.
+    pub fn scroll_to(&self, id: ElementId, x: f32, y: f32) {
+        self.scroll_requests
+            .borrow_mut()
+            .push(ScrollRequest::To(id, LayoutVector2D::new(x, y)));
+    }
+
+    /// Scroll the scroll containers around an element so it is visible, like
+    /// `scrollIntoView`.
+    pub fn scroll_into_view(&self, id: ElementId) {
+        self.scroll_requests
+            .borrow_mut()
+            .push(ScrollRequest::IntoView(id));
+    }
+
+    pub(crate) fn take_scroll_requests(&self) -> Vec<ScrollRequest> {
+        std::mem::take(&mut *self.scroll_requests.borrow_mut())
+    }
+
+    /// Send the mouse events to an element until the buttons are released,
+    /// wherever the cursor is, like `setPointerCapture`. Pressing a button
+    /// already captures the mouse for the pressed element, so this moves the
+    /// capture to another one, like the thumb of a slider pressed on its
+    /// track. It does nothing while no button is held.
+    pub fn set_pointer_capture(&self, id: ElementId) {
+        self.pointer_capture_requests
+            .borrow_mut()
+            .push(PointerCaptureRequest::Set(id));
+    }
+
+    /// Send the mouse events to the elements under the cursor again, if the
+    /// element has captured the mouse, like `releasePointerCapture`.
+    pub fn release_pointer_capture(&self, id: ElementId) {
+        self.pointer_capture_requests
+            .borrow_mut()
+            .push(PointerCaptureRequest::Release(id));
+    }
+
+    pub(crate) fn take_pointer_capture_requests(&self) -> Vec<PointerCaptureRequest> {
+        std::mem::take(&mut *self.pointer_capture_requests.borrow_mut())
+    }
+
+    /// The target of the event being handled. It is only set while the
+    /// handlers of an event run.
+    pub fn event_target(&self) -> Option<EventTarget> {
+        self.event_target.borrow().clone()
+    }
+
+    pub(crate) fn set_event_target(&self, target: Option<EventTarget>) {
+        *self.event_target.borrow_mut() = target;
+    }
+
+    /// The touches of the touch event being handled. They are only set while
+    /// the handlers of a touch event run.
+    pub fn event_touches(&self) -> Option<TouchList> {
+        self.event_touches.borrow().clone()
+    }
+
+    pub(crate) fn set_event_touches(&self, touches: Option<TouchList>) {
+        *self.event_touches.borrow_mut() = touches;
+    }
+
+    pub(crate) fn queue_event(&self, event: UserEvent, touches: Option<TouchList>) {
+        self.events.borrow_mut().push((event, touches));
+    }
+
+    pub(crate) fn take_events(&self) -> Vec<(UserEvent, Option<TouchList>)> {
+        std::mem::take(&mut *self.events.borrow_mut())
+    }
+
+    /// Keep the window above the other windows or not, like to pin a tool
+    /// palette.
+    pub fn set_always_on_top(&self, always_on_top: bool) {
+        self.always_on_top_request.set(Some(always_on_top));
+    }
+
+    pub(crate) fn take_always_on_top_request(&self) -> Option<bool> {
+        self.always_on_top_request.take()
+    }
+
+    /// Run a callback once an instant is reached, like to advance an
+    /// animation. The window sleeps until the nearest scheduled instant
+    /// instead of polling, so a component can pass its `schedule_update` to
+    /// rerender at a given time.
+    pub fn schedule_wake(&self, at: Instant, callback: impl FnOnce() + 'static) {
+        self.wakes.borrow_mut().push((at, Box::new(callback)));
+    }
+
+    /// The nearest instant a callback is scheduled at.
+    pub(crate) fn next_wake(&self) -> Option<Instant> {
+        self.wakes.borrow().iter().map(|(at, _)| *at).min()
+    }
+
+    /// Run the callbacks scheduled at or before `now`.
+    pub(crate) fn run_due_wakes(&self, now: Instant) {
+        // the callbacks may schedule other ones
+        let due = {
+            let mut wakes = self.wakes.borrow_mut();
+            let (due, pending): (Vec<_>, Vec<_>) = std::mem::take(&mut *wakes)
+                .into_iter()
+                .partition(|(at, _)| *at <= now);
+            *wakes = pending;
+            due
+        };
+        for (_, callback) in due {
+            callback();
+        }
+    }
+
+    /// Run a callback once with the time of the next frame, like
+    /// `requestAnimationFrame`, to advance an animation in step with the
+    /// frames. A callback asking for the following frame runs once per frame,
+    /// and the window keeps a steady rate while nothing is painted.
+    pub fn request_animation_frame(&self, callback: impl FnOnce(Instant) + 'static) {
+        self.animation_frames.borrow_mut().push(Box::new(callback));
+    }
+
+    pub(crate) fn has_animation_frames(&self) -> bool {
+        !self.animation_frames.borrow().is_empty()
+    }
+
+    /// Run the callbacks waiting for the frame started at `timestamp`.
+    pub(crate) fn run_animation_frames(&self, timestamp: Instant) {
+        // the callbacks asking for the next frame are kept for it
+        let callbacks = std::mem::take(&mut *self.animation_frames.borrow_mut());
+        for callback in callbacks {
+            callback(timestamp);
+        }
+    }
+
+    /// Find the element that pressing a point in layout units would focus:
+    /// the element under the point or its closest focusable ancestor, or
+    /// `None` over a disabled element or one that can't be focused. The
+    /// window task answers with the current layout before the components
+    /// render again, so the callback can set state, like to show a pointer
+    /// cursor over the elements that can be interacted with.
+    pub fn focusable_at(
+        &self,
+        point: LayoutPoint,
+        callback: impl FnOnce(Option<ElementId>) + 'static,
+    ) {
+        self.focusable_queries
+            .borrow_mut()
+            .push((point, Box::new(callback)));
+    }
+
+    pub(crate) fn has_focusable_queries(&self) -> bool {
+        !self.focusable_queries.borrow().is_empty()
+    }
+
+    pub(crate) fn take_focusable_queries(&self) -> Vec<FocusableQuery> {
+        std::mem::take(&mut *self.focusable_queries.borrow_mut())
+    }
+
+    /// The text of the system clipboard, with its line breaks normalized to
+    /// `\n`, like to handle a paste event. Returns `None` if the clipboard
+    /// has no text or can't be accessed.
+    ///
+    /// The copy, cut and paste events have no built-in behavior yet, as there
+    /// are no text selections or editable elements: the handlers do the work.
+    pub fn clipboard_text(&self) -> Option<String> {
+        let text = self.with_clipboard(|clipboard| clipboard.get_text())?;
+        Some(text.replace("\r\n", "\n").replace('\r', "\n"))
+    }
+
+    /// Replace the content of the system clipboard with a text, like to
+    /// handle a copy or cut event.
+    pub fn set_clipboard_text(&self, text: impl Into<String>) {
+        let text = text.into();
+        self.with_clipboard(|clipboard| clipboard.set_text(text));
+    }
+
+    // The errors are only logged, the clipboard may be unavailable like on
+    // wayland when the window doesn't have the focus
+    fn with_clipboard<T>(
+        &self,
+        f: impl FnOnce(&mut Clipboard) -> Result<T, arboard::Error>,
+    ) -> Option<T> {
+        let mut clipboard = self.clipboard.borrow_mut();
+        if clipboard.is_none() {
+            *clipboard = Clipboard::new()
+                .map_err(|e| warn!("Failed to access the clipboard: {}", e))
+                .ok();
+        }
+        f(clipboard.as_mut()?)
+            .map_err(|e| warn!("Failed to access the clipboard: {}", e))
+            .ok()
+    }
+
+    /// Replace the rects of the elements after a layout and rerender the
+    /// components whose root element moved or was resized. `root_element`
+    /// finds the root element of a component.
+    pub(crate) fn set_layouts(
+        &self,
+        layouts: FxHashMap<ElementId, (LayoutRect, Node)>,
+        root_element: impl Fn(ScopeId) -> Option<ElementId>,
+    ) {
+        *self.layouts.borrow_mut() = layouts;
+        for (scope, listener) in self.layout_listeners.borrow().iter() {
+            let rect = root_element(*scope).and_then(|id| self.element_rect(id));
+            if rect != listener.rect {
+                (listener.update)();
+            }
+        }
+
+        // the callbacks may use the context, so they run once it is released
+        let mounted: Vec<(ElementId, Box<dyn FnOnce(ElementHandle)>)> = {
+            let mut listeners = self.mounted_listeners.borrow_mut();
+            let ready: Vec<(ScopeId, ElementId)> = listeners
+                .keys()
+                .filter_map(|scope| Some((*scope, root_element(*scope)?)))
+                .filter(|(_, id)| self.layouts.borrow().contains_key(id))
+                .collect();
+            ready
+                .into_iter()
+                .filter_map(|(scope, id)| Some((id, listeners.remove(&scope)?)))
+                .collect()
+        };
+        for (id, callback) in mounted {
+            callback(ElementHandle {
+                window: self.clone(),
+                id,
+            });
+        }
+    }
+}
+
+/// The first element a node is mounted as. Components are not looked into.
+pub(crate) fn root_element(node: &VNode) -> Option<ElementId> {
+    match node {
+        VNode::Element(el) => el.id.get(),
+        VNode::Text(text) => text.id.get(),
+        VNode::Placeholder(placeholder) => placeholder.id.get(),
+        VNode::Fragment(fragment) => fragment.children.first().and_then(root_element),
+        VNode::Component(_) => None,
+    }
+}
+
+/// A mounted element, given to the callback of [`use_mounted`] like the
+/// target of an `onmounted` event, to query its layout.
+#[derive(Clone)]
+pub struct ElementHandle {
+    window: WindowContext,
+    id: ElementId,
+}
+
+impl ElementHandle {
+    pub fn id(&self) -> ElementId {
+        self.id
+    }
+
+    /// The rect of the element in window coordinates, in layout units, as of
+    /// the last layout, like `getBoundingClientRect`. Query it again to see
+    /// resizes, or use [`use_layout`] to be rerendered on them. `None` once
+    /// the element is removed.
+    pub fn get_client_rect(&self) -> Option<LayoutRect> {
+        self.window.element_rect(self.id)
+    }
+}
+
+// Unsubscribes a component from the window's resizes, focus, layout changes
+// and mount when it is dropped
+struct ListenerGuard {
+    window: WindowContext,
+    scope: ScopeId,
+}
+
+impl Drop for ListenerGuard {
+    fn drop(&mut self) {
+        self.window
+            .resize_listeners
+            .borrow_mut()
+            .remove(&self.scope);
+        self.window.focus_listeners.borrow_mut().remove(&self.scope);
+        self.window
+            .layout_listeners
+            .borrow_mut()
+            .remove(&self.scope);
+        self.window
+            .mounted_listeners
+            .borrow_mut()
+            .remove(&self.scope);
+    }
+}
+
+/// Get the context of the window the component is rendered in.
+pub fn use_window(cx: &ScopeState) -> &WindowContext {
+    cx.use_hook(|_| {
+        cx.consume_context::<WindowContext>()
+            .expect("the window context is provided to the root component")
+    })
+}
+
+/// Get the size of the window in layout units. The component is rerendered
+/// whenever the window is resized.
+pub fn use_window_size(cx: &ScopeState) -> LayoutSize {
+    let window = use_window(cx);
+    cx.use_hook(|_| {
+        window
+            .resize_listeners
+            .borrow_mut()
+            .insert(cx.scope_id(), cx.schedule_update());
+        ListenerGuard {
+            window: window.clone(),
+            scope: cx.scope_id(),
+        }
+    });
+    window.size()
+}
+
+/// Returns true if the window has the keyboard focus. The component is
+/// rerendered whenever the window gains or loses the focus, so an inactive
+/// window can be dimmed.
+pub fn use_window_focus(cx: &ScopeState) -> bool {
+    let window = use_window(cx);
+    cx.use_hook(|_| {
+        window
+            .focus_listeners
+            .borrow_mut()
+            .insert(cx.scope_id(), cx.schedule_update());
+        ListenerGuard {
+            window: window.clone(),
+            scope: cx.scope_id(),
+        }
+    });
+    window.is_focused()
+}
+
+/// Get the rect of the root element of the component in window coordinates,
+/// in layout units, like `getBoundingClientRect`. It is `None` until the
+/// element is mounted and laid out, and the component is rerendered whenever
+/// the rect changes, so it can be used to position tooltips or to size
+/// virtual lists.
+///
+/// A component whose root is another component has no root element.
+pub fn use_layout(cx: &ScopeState) -> Option<LayoutRect> {
+    let window = use_window(cx);
+    // the root node is the one of the last render, which is mounted
+    let rect = root_element(cx.root_node()).and_then(|id| window.element_rect(id));
+    let (_, update) = cx.use_hook(|_| {
+        let listener = ListenerGuard {
+            window: window.clone(),
+            scope: cx.scope_id(),
+        };
+        (listener, cx.schedule_update())
+    });
+    let listener = LayoutListener {
+        rect,
+        update: update.clone(),
+    };
+    window
+        .layout_listeners
+        .borrow_mut()
+        .insert(cx.scope_id(), listener);
+    rect
+}
+
+/// Run a callback once the root element of the component is mounted and laid
+/// out, like an `onmounted` event, with a handle to query the element's rect.
+/// The callback runs after the layout, before the next frame is painted, so
+/// it can set state to position a tooltip without a visible jump.
+///
+/// A component whose root is another component has no root element.
+pub fn use_mounted(cx: &ScopeState, callback: impl FnOnce(ElementHandle) + 'static) {
+    let window = use_window(cx);
+    cx.use_hook(|_| {
+        window
+            .mounted_listeners
+            .borrow_mut()
+            .insert(cx.scope_id(), Box::new(callback));
+        ListenerGuard {
+            window: window.clone(),
+            scope: cx.scope_id(),
+        }
+    });
+}
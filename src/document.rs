@@ -0,0 +1,131 @@
+use std::{cell::RefCell, rc::Rc};
+
+use dioxus::prelude::{Component, VirtualDom};
+use dioxus_native_core::{node_ref::NodeMask, real_dom::RealDom};
+use taffy::{
+    prelude::{Number, Size},
+    Taffy,
+};
+use webrender::api::{units::LayoutSize, IdNamespace};
+
+use crate::{
+    config::WindowConfig,
+    context::WindowContext,
+    state::{compute_layout, remove_layout_nodes, BoxSizing, NodeState, TextMeasures},
+    style::Stylesheet,
+    text::FontRegistry,
+    window::{set_layouts, state_context},
+};
+
+/// The RealDom of a root component, styled and laid out like in a window,
+/// for embedders that send the display lists of
+/// [`build_display_list`](crate::render::build_display_list) to their own
+/// webrender document.
+///
+/// The fonts of the display lists are registered in [`Document::fonts`],
+/// whose pending resources have to be flushed to the transaction before the
+/// display list is set.
+pub struct Document {
+    vdom: VirtualDom,
+    rdom: RealDom<NodeState>,
+    context: WindowContext,
+    fonts: Rc<FontRegistry>,
+    stretch: Rc<RefCell<Taffy>>,
+    stylesheet: Rc<Stylesheet>,
+    box_sizing: BoxSizing,
+    text_measures: TextMeasures,
+}
+
+impl Document {
+    /// Render a root component and lay it out in a viewport of `size` layout
+    /// units. The fonts get keys in the namespace of the webrender api they
+    /// are sent with. Only the style options of the config are used.
+    pub fn new<P: 'static>(
+        root: Component<P>,
+        props: P,
+        mut cfg: WindowConfig,
+        namespace: IdNamespace,
+        size: LayoutSize,
+    ) -> Self {
+        let stretch = Rc::new(RefCell::new(Taffy::new()));
+        let fonts = Rc::new(FontRegistry::new(namespace));
+        let text_measures = TextMeasures::new(fonts.clone());
+
+        let mut vdom = VirtualDom::new_with_props(root, props);
+        let context = WindowContext::new(size, text_measures.measurer(&stretch));
+        vdom.base_scope().provide_context(context.clone());
+
+        let mut document = Self {
+            vdom,
+            rdom: RealDom::new(),
+            context,
+            fonts,
+            stretch,
+            stylesheet: Rc::new(cfg.take_stylesheet()),
+            box_sizing: cfg.box_sizing,
+            text_measures,
+        };
+        let mutations = document.vdom.rebuild();
+        let to_update = document.rdom.apply_mutations(vec![mutations]);
+        document.update_state(to_update);
+        document
+    }
+
+    pub fn rdom(&self) -> &RealDom<NodeState> {
+        &self.rdom
+    }
+
+    pub fn fonts(&self) -> &FontRegistry {
+        &self.fonts
+    }
+
+    /// The VirtualDom, to send it events. Call [`Document::update`] to apply
+    /// their changes.
+    pub fn vdom_mut(&mut self) -> &mut VirtualDom {
+        &mut self.vdom
+    }
+
+    /// Apply the pending work of the VirtualDom, then style and lay out the
+    /// nodes that changed.
+    pub fn update(&mut self) {
+        self.vdom.process_all_messages();
+        let mutations = self.vdom.work_with_deadline(|| false);
+        for m in &mutations {
+            remove_layout_nodes(
+                m,
+                &self.rdom,
+                &mut self.stretch.borrow_mut(),
+                &mut self.text_measures,
+            );
+        }
+        let to_update = self.rdom.apply_mutations(mutations);
+        self.update_state(to_update);
+    }
+
+    /// Lay the dom out again in a viewport of `size` layout units.
+    pub fn set_size(&mut self, size: LayoutSize) {
+        self.context.set_size(size);
+        self.update();
+    }
+
+    fn update_state(&mut self, to_update: Vec<(usize, NodeMask)>) {
+        let ctx = state_context(&self.stretch, &self.stylesheet, self.box_sizing);
+        let changed = self.rdom.update_state(&self.vdom, to_update, ctx);
+        self.text_measures.update(
+            &self.rdom,
+            &mut self.stretch.borrow_mut(),
+            changed.iter().copied(),
+        );
+
+        let size = self.context.size();
+        compute_layout(
+            &mut self.rdom,
+            &self.stretch,
+            Size {
+                width: Number::Defined(size.width),
+                height: Number::Defined(size.height),
+            },
+        );
+        set_layouts(&self.context, &self.rdom, &self.vdom);
+    }
+}
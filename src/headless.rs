@@ -0,0 +1,147 @@
+use crossbeam_channel::Sender;
+use dioxus::prelude::Component;
+use gleam::gl;
+use glutin::{dpi::PhysicalSize, NotCurrent};
+use image::RgbaImage;
+use webrender::{
+    api::{
+        units::{DeviceIntRect, DeviceIntSize, LayoutSize},
+        *,
+    },
+    Transaction,
+};
+
+use crate::{
+    config::WindowConfig,
+    document::Document,
+    window::{load_gl, read_pixels, unpremultiply},
+};
+
+/// Render the first frame of a root component into an image without opening
+/// a window. Useful to snapshot test the renderer. Like
+/// [`Window::capture_frame`](crate::window::Window::capture_frame), the image
+/// has straight alpha.
+///
+/// On linux the gl context is created with OSMesa, so no display is needed.
+pub fn render_headless(root: Component<()>, size: PhysicalSize<u32>) -> RgbaImage {
+    render_headless_cfg(root, WindowConfig::default(), size)
+}
+
+pub fn render_headless_cfg(
+    root: Component<()>,
+    cfg: WindowConfig,
+    size: PhysicalSize<u32>,
+) -> RgbaImage {
+    let (width, height) = (size.width as i32, size.height as i32);
+
+    let context = create_context(size);
+    let context = unsafe { context.make_current().map_err(|(_, e)| e).unwrap() };
+    let gl = load_gl(&context);
+
+    // webrender draws into the framebuffer that is bound when it renders
+    let fbo = gl.gen_framebuffers(1)[0];
+    let rbo = gl.gen_renderbuffers(1)[0];
+    gl.bind_renderbuffer(gl::RENDERBUFFER, rbo);
+    gl.renderbuffer_storage(gl::RENDERBUFFER, gl::RGBA8, width, height);
+    gl.bind_framebuffer(gl::FRAMEBUFFER, fbo);
+    gl.framebuffer_renderbuffer(
+        gl::FRAMEBUFFER,
+        gl::COLOR_ATTACHMENT0,
+        gl::RENDERBUFFER,
+        rbo,
+    );
+
+    // Create Webrender
+    let (frame_tx, frame_rx) = crossbeam_channel::unbounded();
+    let opts = webrender::WebRenderOptions {
+        clear_color: cfg.renderer_clear_color(),
+        ..Default::default()
+    };
+    let (mut renderer, sender) = webrender::create_webrender_instance(
+        gl.clone(),
+        Box::new(HeadlessNotifier(frame_tx)),
+        opts,
+        None,
+    )
+    .unwrap();
+    let mut api = sender.create_api();
+    let device_size = DeviceIntSize::new(width, height);
+    let document_id = api.add_document(device_size);
+    let epoch = Epoch(0);
+    let pipeline_id = PipelineId(0, 0);
+    let layout_size = LayoutSize::new(size.width as f32, size.height as f32);
+
+    let mut txn = Transaction::new();
+    txn.set_root_pipeline(pipeline_id);
+    api.send_transaction(document_id, txn);
+
+    // Build the Real DOM like a window task does
+    let document = Document::new(root, (), cfg, api.get_namespace_id(), layout_size);
+
+    crate::render::render(
+        document_id,
+        epoch,
+        &mut api,
+        &mut DisplayListBuilder::new(pipeline_id),
+        document.rdom(),
+        document.fonts(),
+        layout_size,
+        1.0,
+    );
+
+    // wait for the render backend to build the frame
+    frame_rx.recv().unwrap();
+    renderer.update();
+    renderer.render(device_size, 0).unwrap();
+
+    let mut pixels = read_pixels(&*gl, DeviceIntRect::from_size(device_size), height);
+    unpremultiply(&mut pixels);
+    renderer.deinit();
+    gl.delete_framebuffers(&[fbo]);
+    gl.delete_renderbuffers(&[rbo]);
+
+    RgbaImage::from_raw(size.width, size.height, pixels).unwrap()
+}
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+))]
+fn create_context(size: PhysicalSize<u32>) -> glutin::Context<NotCurrent> {
+    use glutin::platform::unix::HeadlessContextExt;
+
+    glutin::ContextBuilder::new().build_osmesa(size).unwrap()
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+)))]
+fn create_context(size: PhysicalSize<u32>) -> glutin::Context<NotCurrent> {
+    // other platforms need an event loop to create a context, but it is
+    // never run
+    let event_loop = glutin::event_loop::EventLoop::new();
+    glutin::ContextBuilder::new()
+        .build_headless(&event_loop, size)
+        .unwrap()
+}
+
+struct HeadlessNotifier(Sender<()>);
+
+impl RenderNotifier for HeadlessNotifier {
+    fn clone(&self) -> Box<dyn RenderNotifier> {
+        Box::new(HeadlessNotifier(self.0.clone()))
+    }
+
+    fn wake_up(&self, _composite_needed: bool) {}
+
+    fn new_frame_ready(&self, _: DocumentId, _scrolled: bool, _composite_needed: bool) {
+        let _ = self.0.send(());
+    }
+}
@@ -0,0 +1,52 @@
+use std::io::{BufRead, BufReader};
+
+use crossbeam_channel::Sender;
+use dioxus::prelude::VirtualDom;
+use dioxus_rsx_interpreter::{SetManyRsxMessage, RSX_CONTEXT};
+use interprocess::local_socket::LocalSocketListener;
+
+use crate::window::TaskMessage;
+
+/// Listen for the rsx edits sent by the dioxus cli and forward them to a
+/// window task, which applies them between events.
+pub(crate) fn init(vdom: &VirtualDom, event_tx: Sender<TaskMessage>) {
+    // applying the edits marks the scopes of the vdom dirty
+    RSX_CONTEXT.provide_scheduler_channel(vdom.get_scheduler_channel());
+
+    std::thread::spawn(move || {
+        let path = std::env::temp_dir().join("@dioxusin");
+        let listener = match LocalSocketListener::bind(path) {
+            Ok(listener) => listener,
+            Err(e) => {
+                error!("Hot reload is disabled: {}", e);
+                return;
+            }
+        };
+
+        for conn in listener.incoming() {
+            let conn = match conn {
+                Ok(conn) => conn,
+                Err(e) => {
+                    error!("{}", e);
+                    continue;
+                }
+            };
+            // one json message per line
+            for line in BufReader::new(conn).lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(_) => break,
+                };
+                match serde_json::from_str::<SetManyRsxMessage>(&line) {
+                    Ok(msgs) => {
+                        // the window was dropped
+                        if event_tx.send(TaskMessage::HotReload(msgs)).is_err() {
+                            return;
+                        }
+                    }
+                    Err(e) => error!("Invalid hot reload message: {}", e),
+                }
+            }
+        }
+    });
+}
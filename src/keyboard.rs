@@ -0,0 +1,452 @@
+use dioxus::html::input_data::keyboard_types::{Code, Key, Location, Modifiers};
+use glutin::event::VirtualKeyCode;
+
+/// Translate a key reported by winit to the key value, physical code and
+/// location of the keyboard events.
+///
+/// winit only reports which key was pressed, not the text it types in the
+/// current layout, so the characters are the ones of a US layout. Keys whose
+/// character depends on the layout have an unidentified key value.
+pub(crate) fn translate_key(key: VirtualKeyCode, modifiers: Modifiers) -> (Key, Code, Location) {
+    if let Some((code, character, shifted)) = printable(key) {
+        let character = if modifiers.contains(Modifiers::SHIFT) {
+            shifted
+        } else {
+            character
+        };
+        let location = if is_numpad(code) {
+            Location::Numpad
+        } else {
+            Location::Standard
+        };
+        return (Key::Character(character.to_string()), code, location);
+    }
+
+    use VirtualKeyCode as K;
+    let (value, code, location) = match key {
+        // navigation
+        K::Left => (Key::ArrowLeft, Code::ArrowLeft, Location::Standard),
+        K::Up => (Key::ArrowUp, Code::ArrowUp, Location::Standard),
+        K::Right => (Key::ArrowRight, Code::ArrowRight, Location::Standard),
+        K::Down => (Key::ArrowDown, Code::ArrowDown, Location::Standard),
+        K::Home => (Key::Home, Code::Home, Location::Standard),
+        K::End => (Key::End, Code::End, Location::Standard),
+        K::PageUp => (Key::PageUp, Code::PageUp, Location::Standard),
+        K::PageDown => (Key::PageDown, Code::PageDown, Location::Standard),
+        K::Tab => (Key::Tab, Code::Tab, Location::Standard),
+
+        // editing
+        K::Back => (Key::Backspace, Code::Backspace, Location::Standard),
+        K::Delete => (Key::Delete, Code::Delete, Location::Standard),
+        K::Insert => (Key::Insert, Code::Insert, Location::Standard),
+        K::Return => (Key::Enter, Code::Enter, Location::Standard),
+        K::NumpadEnter => (Key::Enter, Code::NumpadEnter, Location::Numpad),
+        K::Copy => (Key::Copy, Code::Copy, Location::Standard),
+        K::Cut => (Key::Cut, Code::Cut, Location::Standard),
+        K::Paste => (Key::Paste, Code::Paste, Location::Standard),
+
+        // modifiers and locks
+        K::LShift => (Key::Shift, Code::ShiftLeft, Location::Left),
+        K::RShift => (Key::Shift, Code::ShiftRight, Location::Right),
+        K::LControl => (Key::Control, Code::ControlLeft, Location::Left),
+        K::RControl => (Key::Control, Code::ControlRight, Location::Right),
+        K::LAlt => (Key::Alt, Code::AltLeft, Location::Left),
+        K::RAlt => (Key::Alt, Code::AltRight, Location::Right),
+        K::LWin => (Key::Meta, Code::MetaLeft, Location::Left),
+        K::RWin => (Key::Meta, Code::MetaRight, Location::Right),
+        K::Capital => (Key::CapsLock, Code::CapsLock, Location::Standard),
+        K::Numlock => (Key::NumLock, Code::NumLock, Location::Numpad),
+        K::Scroll => (Key::ScrollLock, Code::ScrollLock, Location::Standard),
+
+        // ui and system
+        K::Escape => (Key::Escape, Code::Escape, Location::Standard),
+        K::Apps => (Key::ContextMenu, Code::ContextMenu, Location::Standard),
+        K::Pause => (Key::Pause, Code::Pause, Location::Standard),
+        K::Snapshot | K::Sysrq => (Key::PrintScreen, Code::PrintScreen, Location::Standard),
+        K::Power => (Key::Power, Code::Power, Location::Standard),
+        K::Sleep => (Key::Standby, Code::Sleep, Location::Standard),
+        K::Wake => (Key::WakeUp, Code::WakeUp, Location::Standard),
+        K::Compose => (Key::Compose, Code::Unidentified, Location::Standard),
+
+        // function keys
+        K::F1 => (Key::F1, Code::F1, Location::Standard),
+        K::F2 => (Key::F2, Code::F2, Location::Standard),
+        K::F3 => (Key::F3, Code::F3, Location::Standard),
+        K::F4 => (Key::F4, Code::F4, Location::Standard),
+        K::F5 => (Key::F5, Code::F5, Location::Standard),
+        K::F6 => (Key::F6, Code::F6, Location::Standard),
+        K::F7 => (Key::F7, Code::F7, Location::Standard),
+        K::F8 => (Key::F8, Code::F8, Location::Standard),
+        K::F9 => (Key::F9, Code::F9, Location::Standard),
+        K::F10 => (Key::F10, Code::F10, Location::Standard),
+        K::F11 => (Key::F11, Code::F11, Location::Standard),
+        K::F12 => (Key::F12, Code::F12, Location::Standard),
+        K::F13 => (Key::F13, Code::F13, Location::Standard),
+        K::F14 => (Key::F14, Code::F14, Location::Standard),
+        K::F15 => (Key::F15, Code::F15, Location::Standard),
+        K::F16 => (Key::F16, Code::F16, Location::Standard),
+        K::F17 => (Key::F17, Code::F17, Location::Standard),
+        K::F18 => (Key::F18, Code::F18, Location::Standard),
+        K::F19 => (Key::F19, Code::F19, Location::Standard),
+        K::F20 => (Key::F20, Code::F20, Location::Standard),
+        K::F21 => (Key::F21, Code::F21, Location::Standard),
+        K::F22 => (Key::F22, Code::F22, Location::Standard),
+        K::F23 => (Key::F23, Code::F23, Location::Standard),
+        K::F24 => (Key::F24, Code::F24, Location::Standard),
+
+        // media and browser keys
+        K::PlayPause => (
+            Key::MediaPlayPause,
+            Code::MediaPlayPause,
+            Location::Standard,
+        ),
+        K::MediaStop => (Key::MediaStop, Code::MediaStop, Location::Standard),
+        K::NextTrack => (
+            Key::MediaTrackNext,
+            Code::MediaTrackNext,
+            Location::Standard,
+        ),
+        K::PrevTrack => (
+            Key::MediaTrackPrevious,
+            Code::MediaTrackPrevious,
+            Location::Standard,
+        ),
+        K::VolumeDown => (
+            Key::AudioVolumeDown,
+            Code::AudioVolumeDown,
+            Location::Standard,
+        ),
+        K::VolumeUp => (Key::AudioVolumeUp, Code::AudioVolumeUp, Location::Standard),
+        K::Mute => (
+            Key::AudioVolumeMute,
+            Code::AudioVolumeMute,
+            Location::Standard,
+        ),
+        K::MediaSelect => (
+            Key::LaunchMediaPlayer,
+            Code::MediaSelect,
+            Location::Standard,
+        ),
+        K::Mail => (Key::LaunchMail, Code::LaunchMail, Location::Standard),
+        K::Calculator => (
+            Key::LaunchApplication2,
+            Code::LaunchApp2,
+            Location::Standard,
+        ),
+        K::MyComputer => (
+            Key::LaunchApplication1,
+            Code::LaunchApp1,
+            Location::Standard,
+        ),
+        K::WebBack | K::NavigateBackward => {
+            (Key::BrowserBack, Code::BrowserBack, Location::Standard)
+        }
+        K::WebForward | K::NavigateForward => (
+            Key::BrowserForward,
+            Code::BrowserForward,
+            Location::Standard,
+        ),
+        K::WebFavorites => (
+            Key::BrowserFavorites,
+            Code::BrowserFavorites,
+            Location::Standard,
+        ),
+        K::WebHome => (Key::BrowserHome, Code::BrowserHome, Location::Standard),
+        K::WebRefresh => (
+            Key::BrowserRefresh,
+            Code::BrowserRefresh,
+            Location::Standard,
+        ),
+        K::WebSearch => (Key::BrowserSearch, Code::BrowserSearch, Location::Standard),
+        K::WebStop => (Key::BrowserStop, Code::BrowserStop, Location::Standard),
+
+        // input method keys
+        K::Convert => (Key::Convert, Code::Convert, Location::Standard),
+        K::NoConvert => (Key::NonConvert, Code::NonConvert, Location::Standard),
+        K::Kana => (Key::KanaMode, Code::KanaMode, Location::Standard),
+        K::Kanji => (Key::KanjiMode, Code::Unidentified, Location::Standard),
+
+        // the keys that type a character that depends on the layout, and
+        // dead keys
+        K::Caret => (Key::Unidentified, Code::Unidentified, Location::Standard),
+        K::OEM102 => (Key::Unidentified, Code::IntlBackslash, Location::Standard),
+        K::AbntC1 => (Key::Unidentified, Code::IntlRo, Location::Standard),
+        K::Yen => (Key::Unidentified, Code::IntlYen, Location::Standard),
+        _ => (Key::Unidentified, Code::Unidentified, Location::Standard),
+    };
+    (value, code, location)
+}
+
+// The physical code of a key that types a character, with the characters it
+// types without and with shift.
+fn printable(key: VirtualKeyCode) -> Option<(Code, char, char)> {
+    use VirtualKeyCode as K;
+    Some(match key {
+        K::A => (Code::KeyA, 'a', 'A'),
+        K::B => (Code::KeyB, 'b', 'B'),
+        K::C => (Code::KeyC, 'c', 'C'),
+        K::D => (Code::KeyD, 'd', 'D'),
+        K::E => (Code::KeyE, 'e', 'E'),
+        K::F => (Code::KeyF, 'f', 'F'),
+        K::G => (Code::KeyG, 'g', 'G'),
+        K::H => (Code::KeyH, 'h', 'H'),
+        K::I => (Code::KeyI, 'i', 'I'),
+        K::J => (Code::KeyJ, 'j', 'J'),
+        K::K => (Code::KeyK, 'k', 'K'),
+        K::L => (Code::KeyL, 'l', 'L'),
+        K::M => (Code::KeyM, 'm', 'M'),
+        K::N => (Code::KeyN, 'n', 'N'),
+        K::O => (Code::KeyO, 'o', 'O'),
+        K::P => (Code::KeyP, 'p', 'P'),
+        K::Q => (Code::KeyQ, 'q', 'Q'),
+        K::R => (Code::KeyR, 'r', 'R'),
+        K::S => (Code::KeyS, 's', 'S'),
+        K::T => (Code::KeyT, 't', 'T'),
+        K::U => (Code::KeyU, 'u', 'U'),
+        K::V => (Code::KeyV, 'v', 'V'),
+        K::W => (Code::KeyW, 'w', 'W'),
+        K::X => (Code::KeyX, 'x', 'X'),
+        K::Y => (Code::KeyY, 'y', 'Y'),
+        K::Z => (Code::KeyZ, 'z', 'Z'),
+
+        K::Key1 => (Code::Digit1, '1', '!'),
+        K::Key2 => (Code::Digit2, '2', '@'),
+        K::Key3 => (Code::Digit3, '3', '#'),
+        K::Key4 => (Code::Digit4, '4', '$'),
+        K::Key5 => (Code::Digit5, '5', '%'),
+        K::Key6 => (Code::Digit6, '6', '^'),
+        K::Key7 => (Code::Digit7, '7', '&'),
+        K::Key8 => (Code::Digit8, '8', '*'),
+        K::Key9 => (Code::Digit9, '9', '('),
+        K::Key0 => (Code::Digit0, '0', ')'),
+
+        K::Space => (Code::Space, ' ', ' '),
+        K::Grave => (Code::Backquote, '`', '~'),
+        K::Minus => (Code::Minus, '-', '_'),
+        K::Equals => (Code::Equal, '=', '+'),
+        K::LBracket => (Code::BracketLeft, '[', '{'),
+        K::RBracket => (Code::BracketRight, ']', '}'),
+        K::Backslash => (Code::Backslash, '\\', '|'),
+        K::Semicolon => (Code::Semicolon, ';', ':'),
+        K::Apostrophe => (Code::Quote, '\'', '"'),
+        K::Comma => (Code::Comma, ',', '<'),
+        K::Period => (Code::Period, '.', '>'),
+        K::Slash => (Code::Slash, '/', '?'),
+
+        // reported by layouts that have a key for these characters
+        K::Plus => (Code::Unidentified, '+', '+'),
+        K::Asterisk => (Code::Unidentified, '*', '*'),
+        K::At => (Code::Unidentified, '@', '@'),
+        K::Colon => (Code::Unidentified, ':', ':'),
+        K::Underline => (Code::Unidentified, '_', '_'),
+
+        K::Numpad0 => (Code::Numpad0, '0', '0'),
+        K::Numpad1 => (Code::Numpad1, '1', '1'),
+        K::Numpad2 => (Code::Numpad2, '2', '2'),
+        K::Numpad3 => (Code::Numpad3, '3', '3'),
+        K::Numpad4 => (Code::Numpad4, '4', '4'),
+        K::Numpad5 => (Code::Numpad5, '5', '5'),
+        K::Numpad6 => (Code::Numpad6, '6', '6'),
+        K::Numpad7 => (Code::Numpad7, '7', '7'),
+        K::Numpad8 => (Code::Numpad8, '8', '8'),
+        K::Numpad9 => (Code::Numpad9, '9', '9'),
+        K::NumpadAdd => (Code::NumpadAdd, '+', '+'),
+        K::NumpadSubtract => (Code::NumpadSubtract, '-', '-'),
+        K::NumpadMultiply => (Code::NumpadMultiply, '*', '*'),
+        K::NumpadDivide => (Code::NumpadDivide, '/', '/'),
+        K::NumpadDecimal => (Code::NumpadDecimal, '.', '.'),
+        K::NumpadComma => (Code::NumpadComma, ',', ','),
+        K::NumpadEquals => (Code::NumpadEqual, '=', '='),
+        _ => return None,
+    })
+}
+
+fn is_numpad(code: Code) -> bool {
+    matches!(
+        code,
+        Code::Numpad0
+            | Code::Numpad1
+            | Code::Numpad2
+            | Code::Numpad3
+            | Code::Numpad4
+            | Code::Numpad5
+            | Code::Numpad6
+            | Code::Numpad7
+            | Code::Numpad8
+            | Code::Numpad9
+            | Code::NumpadAdd
+            | Code::NumpadSubtract
+            | Code::NumpadMultiply
+            | Code::NumpadDivide
+            | Code::NumpadDecimal
+            | Code::NumpadComma
+            | Code::NumpadEqual
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translate() {
+        use VirtualKeyCode as K;
+        let none = Modifiers::empty();
+        let shift = Modifiers::SHIFT;
+        let character = |c: &str| Key::Character(c.to_string());
+
+        let table = [
+            // letters
+            (K::A, none, character("a"), Code::KeyA, Location::Standard),
+            (K::A, shift, character("A"), Code::KeyA, Location::Standard),
+            (K::Z, shift, character("Z"), Code::KeyZ, Location::Standard),
+            (
+                K::Q,
+                Modifiers::CONTROL,
+                character("q"),
+                Code::KeyQ,
+                Location::Standard,
+            ),
+            // digits and symbols of the US layout
+            (
+                K::Key1,
+                none,
+                character("1"),
+                Code::Digit1,
+                Location::Standard,
+            ),
+            (
+                K::Key1,
+                shift,
+                character("!"),
+                Code::Digit1,
+                Location::Standard,
+            ),
+            (
+                K::Key0,
+                shift,
+                character(")"),
+                Code::Digit0,
+                Location::Standard,
+            ),
+            (
+                K::Slash,
+                shift,
+                character("?"),
+                Code::Slash,
+                Location::Standard,
+            ),
+            (
+                K::Space,
+                shift,
+                character(" "),
+                Code::Space,
+                Location::Standard,
+            ),
+            // the numpad ignores shift
+            (
+                K::Numpad1,
+                none,
+                character("1"),
+                Code::Numpad1,
+                Location::Numpad,
+            ),
+            (
+                K::Numpad1,
+                shift,
+                character("1"),
+                Code::Numpad1,
+                Location::Numpad,
+            ),
+            (
+                K::NumpadAdd,
+                none,
+                character("+"),
+                Code::NumpadAdd,
+                Location::Numpad,
+            ),
+            (
+                K::NumpadDecimal,
+                none,
+                character("."),
+                Code::NumpadDecimal,
+                Location::Numpad,
+            ),
+            (
+                K::NumpadEnter,
+                none,
+                Key::Enter,
+                Code::NumpadEnter,
+                Location::Numpad,
+            ),
+            // named keys
+            (K::Return, none, Key::Enter, Code::Enter, Location::Standard),
+            (
+                K::Back,
+                none,
+                Key::Backspace,
+                Code::Backspace,
+                Location::Standard,
+            ),
+            (
+                K::Left,
+                shift,
+                Key::ArrowLeft,
+                Code::ArrowLeft,
+                Location::Standard,
+            ),
+            (K::F12, none, Key::F12, Code::F12, Location::Standard),
+            (
+                K::LShift,
+                shift,
+                Key::Shift,
+                Code::ShiftLeft,
+                Location::Left,
+            ),
+            (
+                K::RControl,
+                none,
+                Key::Control,
+                Code::ControlRight,
+                Location::Right,
+            ),
+            // keys whose character depends on the layout
+            (
+                K::Plus,
+                none,
+                character("+"),
+                Code::Unidentified,
+                Location::Standard,
+            ),
+            (
+                K::OEM102,
+                none,
+                Key::Unidentified,
+                Code::IntlBackslash,
+                Location::Standard,
+            ),
+            (
+                K::Caret,
+                shift,
+                Key::Unidentified,
+                Code::Unidentified,
+                Location::Standard,
+            ),
+            (
+                K::Ax,
+                none,
+                Key::Unidentified,
+                Code::Unidentified,
+                Location::Standard,
+            ),
+        ];
+
+        for (key, modifiers, value, code, location) in table {
+            assert_eq!(
+                translate_key(key, modifiers),
+                (value, code, location),
+                "{key:?} with {modifiers:?}"
+            );
+        }
+    }
+}
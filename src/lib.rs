@@ -5,27 +5,205 @@ use dioxus::prelude::Component;
 use glutin::{
     event_loop::{ControlFlow, EventLoop},
     platform::run_return::EventLoopExtRunReturn,
+    window::WindowBuilder,
 };
+use webrender::api::ColorF;
 use window::Window;
 
-mod render;
+mod config;
+mod context;
+mod document;
+mod headless;
+#[cfg(feature = "hot-reload")]
+mod hot_reload;
+mod keyboard;
+mod mouse;
+pub mod render;
 mod state;
+mod style;
+pub mod text;
 mod utils;
 pub mod window;
 
+pub use config::{Accelerator, FrameStats, WindowConfig};
+pub use context::{
+    use_layout, use_mounted, use_window, use_window_focus, use_window_size, ElementHandle,
+    EventTarget, Touch, TouchList, WindowContext,
+};
+pub use document::Document;
+pub use headless::{render_headless, render_headless_cfg};
+pub use state::{BoxSizing, Easing, NodeState};
+pub use style::Keyframe;
+
 pub fn launch(root: Component<()>) {
+    LaunchBuilder::new(root).launch()
+}
+
+pub fn launch_cfg(root: Component<()>, cfg: WindowConfig) {
+    launch_with_props_cfg(root, (), cfg)
+}
+
+/// Launch a root component that takes props.
+pub fn launch_with_props<P: 'static + Send>(root: Component<P>, props: P) {
+    LaunchBuilder::with_props(root, props).launch()
+}
+
+pub fn launch_with_props_cfg<P: 'static + Send>(root: Component<P>, props: P, cfg: WindowConfig) {
     // env_logger::init();
 
     let mut event_loop = EventLoop::with_user_event();
-    let window = Window::new(root, &event_loop);
+    let mut window = Some(Window::new(root, props, cfg, &event_loop));
 
     event_loop.run_return(move |event, _, control_flow| {
-        *control_flow = ControlFlow::Wait;
+        let open = match &window {
+            Some(open) => open,
+            None => {
+                *control_flow = ControlFlow::Exit;
+                return;
+            }
+        };
+
+        open.send_event(event);
 
-        match event {
-            _ => (),
+        // the task ended after a close request, so the window goes with it
+        if open.is_closed() {
+            window = None;
+            *control_flow = ControlFlow::Exit;
+            return;
         }
 
-        window.send_event(event);
+        // sleep until the next event, or the nearest callback scheduled by
+        // the windows
+        *control_flow = match open.next_wake() {
+            Some(at) => ControlFlow::WaitUntil(at),
+            None => ControlFlow::Wait,
+        };
     });
 }
+
+/// Configure the window of a root component before launching it.
+pub struct LaunchBuilder<P = ()> {
+    root: Component<P>,
+    props: P,
+    cfg: WindowConfig,
+}
+
+impl LaunchBuilder {
+    pub fn new(root: Component<()>) -> Self {
+        Self::with_props(root, ())
+    }
+}
+
+impl<P: 'static + Send> LaunchBuilder<P> {
+    /// Create a builder for a root component that takes props.
+    pub fn with_props(root: Component<P>, props: P) -> Self {
+        Self {
+            root,
+            props,
+            cfg: WindowConfig::default(),
+        }
+    }
+
+    /// Replace the whole window configuration.
+    pub fn with_config(mut self, cfg: WindowConfig) -> Self {
+        self.cfg = cfg;
+        self
+    }
+
+    pub fn with_stylesheet(mut self, css: &str) -> Self {
+        self.cfg = self.cfg.with_stylesheet(css);
+        self
+    }
+
+    pub fn with_keyframes(mut self, name: impl Into<String>, keyframes: Vec<Keyframe>) -> Self {
+        self.cfg = self.cfg.with_keyframes(name, keyframes);
+        self
+    }
+
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.cfg = self.cfg.with_title(title);
+        self
+    }
+
+    pub fn with_inner_size(mut self, width: f64, height: f64) -> Self {
+        self.cfg = self.cfg.with_inner_size(width, height);
+        self
+    }
+
+    pub fn with_position(mut self, x: f64, y: f64) -> Self {
+        self.cfg = self.cfg.with_position(x, y);
+        self
+    }
+
+    pub fn with_clear_color(mut self, color: ColorF) -> Self {
+        self.cfg = self.cfg.with_clear_color(color);
+        self
+    }
+
+    pub fn with_transparent(mut self, transparent: bool) -> Self {
+        self.cfg = self.cfg.with_transparent(transparent);
+        self
+    }
+
+    pub fn with_decorations(mut self, decorations: bool) -> Self {
+        self.cfg = self.cfg.with_decorations(decorations);
+        self
+    }
+
+    pub fn with_always_on_top(mut self, always_on_top: bool) -> Self {
+        self.cfg = self.cfg.with_always_on_top(always_on_top);
+        self
+    }
+
+    pub fn with_box_sizing(mut self, box_sizing: BoxSizing) -> Self {
+        self.cfg = self.cfg.with_box_sizing(box_sizing);
+        self
+    }
+
+    pub fn with_max_frame_rate(mut self, fps: u32) -> Self {
+        self.cfg = self.cfg.with_max_frame_rate(fps);
+        self
+    }
+
+    pub fn with_wheel_line_height(mut self, line_height: f32) -> Self {
+        self.cfg = self.cfg.with_wheel_line_height(line_height);
+        self
+    }
+
+    pub fn with_close_on_escape(mut self, close_on_escape: bool) -> Self {
+        self.cfg = self.cfg.with_close_on_escape(close_on_escape);
+        self
+    }
+
+    pub fn with_user_agent_styles(mut self, enabled: bool) -> Self {
+        self.cfg = self.cfg.with_user_agent_styles(enabled);
+        self
+    }
+
+    pub fn with_accelerator(
+        mut self,
+        accelerator: Accelerator,
+        callback: impl FnMut() + Send + 'static,
+    ) -> Self {
+        self.cfg = self.cfg.with_accelerator(accelerator, callback);
+        self
+    }
+
+    pub fn with_frame_stats(mut self, callback: impl FnMut(FrameStats) + Send + 'static) -> Self {
+        self.cfg = self.cfg.with_frame_stats(callback);
+        self
+    }
+
+    pub fn with_window_builder(
+        mut self,
+        f: impl FnOnce(WindowBuilder) -> WindowBuilder + Send + 'static,
+    ) -> Self {
+        self.cfg = self.cfg.with_window_builder(f);
+        self
+    }
+
+    /// Open the window and run the event loop until it is closed.
+    pub fn launch(self) {
+        launch_with_props_cfg(self.root, self.props, self.cfg)
+    }
+}
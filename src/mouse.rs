@@ -0,0 +1,119 @@
+use dioxus::{
+    events::{MouseData, PointerData},
+    html::{
+        geometry::{ClientPoint, Coordinates, ElementPoint, PagePoint, ScreenPoint},
+        input_data::{
+            encode_mouse_button_set, keyboard_types::Modifiers, MouseButton, MouseButtonSet,
+        },
+    },
+};
+use glutin::event::{MouseButton as WindowButton, MouseScrollDelta};
+use webrender::api::units::{LayoutRect, LayoutVector2D};
+
+/// Translate a mouse button reported by winit to the button of the mouse
+/// events.
+pub(crate) fn translate_button(button: WindowButton) -> MouseButton {
+    match button {
+        WindowButton::Left => MouseButton::Primary,
+        WindowButton::Right => MouseButton::Secondary,
+        WindowButton::Middle => MouseButton::Auxiliary,
+        // the numbering of the other buttons depends on the platform
+        WindowButton::Other(_) => MouseButton::Unknown,
+    }
+}
+
+/// The data of a mouse event at a point of the window, in layout units. The
+/// element coordinates are relative to the rect of the target, and the screen
+/// coordinates to the origin of the window on the screen.
+pub(crate) fn mouse_data(
+    (x, y): (f32, f32),
+    window_origin: (f32, f32),
+    target_rect: Option<LayoutRect>,
+    trigger_button: Option<MouseButton>,
+    held_buttons: MouseButtonSet,
+    modifiers: Modifiers,
+) -> MouseData {
+    let (x, y) = (x as f64, y as f64);
+    let (element_x, element_y) = target_rect.map_or((x, y), |rect| {
+        (x - rect.min.x as f64, y - rect.min.y as f64)
+    });
+    // the document doesn't scroll, only the scroll containers in it
+    let coordinates = Coordinates::new(
+        ScreenPoint::new(x + window_origin.0 as f64, y + window_origin.1 as f64),
+        ClientPoint::new(x, y),
+        ElementPoint::new(element_x, element_y),
+        PagePoint::new(x, y),
+    );
+    MouseData::new(coordinates, trigger_button, held_buttons, modifiers)
+}
+
+/// The pointer id of the mouse. The touches get the following ones.
+pub(crate) const MOUSE_POINTER_ID: i32 = 1;
+
+/// The data of a pointer event, from the data of the mouse event at the same
+/// point. `pointer_type` is `mouse`, `touch` or `pen`, and the pressure goes
+/// from 0 to 1.
+pub(crate) fn pointer_data(
+    mouse: &MouseData,
+    pointer_id: i32,
+    pointer_type: &str,
+    pressure: f32,
+    is_primary: bool,
+) -> PointerData {
+    let client = mouse.client_coordinates();
+    let page = mouse.page_coordinates();
+    let screen = mouse.screen_coordinates();
+    let modifiers = mouse.modifiers();
+    PointerData {
+        alt_key: modifiers.contains(Modifiers::ALT),
+        // no button changed for the moves
+        button: mouse
+            .trigger_button()
+            .map_or(-1, |button| button.into_web_code()),
+        buttons: encode_mouse_button_set(mouse.held_buttons()),
+        client_x: client.x as i32,
+        client_y: client.y as i32,
+        ctrl_key: modifiers.contains(Modifiers::CONTROL),
+        meta_key: modifiers.contains(Modifiers::META),
+        page_x: page.x as i32,
+        page_y: page.y as i32,
+        screen_x: screen.x as i32,
+        screen_y: screen.y as i32,
+        shift_key: modifiers.contains(Modifiers::SHIFT),
+        pointer_id,
+        width: 1,
+        height: 1,
+        pressure,
+        tangential_pressure: 0.0,
+        tilt_x: 0,
+        tilt_y: 0,
+        twist: 0,
+        pointer_type: pointer_type.to_string(),
+        is_primary,
+    }
+}
+
+/// The distance scrolled by a mouse wheel in layout units, positive towards
+/// the bottom right like in browsers. The lines of the wheels that report
+/// them are `line_height` long, and with Shift a vertical wheel scrolls
+/// horizontally.
+pub(crate) fn wheel_delta(
+    delta: MouseScrollDelta,
+    scale_factor: f64,
+    line_height: f32,
+    shift: bool,
+) -> LayoutVector2D {
+    // winit reports the distance the content moves
+    let delta = match delta {
+        MouseScrollDelta::LineDelta(x, y) => LayoutVector2D::new(-x, -y) * line_height,
+        MouseScrollDelta::PixelDelta(position) => {
+            let position = position.to_logical::<f32>(scale_factor);
+            LayoutVector2D::new(-position.x, -position.y)
+        }
+    };
+    if shift && delta.x == 0.0 {
+        LayoutVector2D::new(delta.y, 0.0)
+    } else {
+        delta
+    }
+}
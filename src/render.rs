@@ -1,24 +1,45 @@
+use dioxus::core::ElementId;
+use dioxus_native_core::real_dom::{NodeType, RealDom};
+use taffy::style::{Dimension, Display};
 use webrender::{
-    api::{units::LayoutSize, *},
+    api::{
+        units::{LayoutPoint, LayoutRect, LayoutSideOffsets, LayoutSize, LayoutVector2D},
+        *,
+    },
     RenderApi, Transaction,
 };
 
-use crate::utils::RectBuilder;
+use crate::{
+    state::{
+        max_scroll_offset, BorderSide, Gradient, GradientKind, NodeState, TextStyle, Transform,
+    },
+    text::{layout_text, FontId, FontRegistry},
+    utils::RectBuilder,
+};
 
-pub fn render(
-    pipeline_id: PipelineId,
-    document_id: DocumentId,
-    epoch: Epoch,
-    api: &mut RenderApi,
+/// Build a display list of the RealDom for a viewport of `layout_size`, in
+/// device pixels. The layout is in logical pixels and is scaled by
+/// `scale_factor`, so `1px` has the same physical size as in a browser.
+///
+/// The builder is meant to be kept between frames, so its buffers are reused
+/// instead of being allocated again for every frame.
+///
+/// A laid out RealDom and its fonts come from a [`Document`](crate::Document).
+/// The display list references font keys that webrender doesn't know yet, so
+/// `fonts.flush(&mut txn)` has to be called on the transaction before
+/// `set_display_list`, like [`render`] does.
+pub fn build_display_list(
+    builder: &mut DisplayListBuilder,
+    rdom: &RealDom<NodeState>,
+    fonts: &FontRegistry,
     layout_size: LayoutSize,
-) {
-    let mut txn = Transaction::new();
-    let mut builder = DisplayListBuilder::new(pipeline_id);
+    scale_factor: f32,
+) -> (PipelineId, BuiltDisplayList) {
+    let pipeline_id = builder.pipeline_id;
     builder.begin();
 
     {
-        //TODO render node
-        let content_bounds = units::LayoutRect::from_size(layout_size);
+        let content_bounds = LayoutRect::from_size(layout_size);
         let root_space_and_clip = SpaceAndClipInfo::root_scroll(pipeline_id);
         let spatial_id = root_space_and_clip.spatial_id;
 
@@ -28,10 +49,616 @@ pub fn render(
             PrimitiveFlags::IS_BACKFACE_VISIBLE,
         );
 
+        let cx = RenderContext {
+            rdom,
+            fonts,
+            scale_factor,
+        };
+        render_node(
+            builder,
+            &root_space_and_clip,
+            &cx,
+            ElementId(rdom.root_id()),
+            LayoutPoint::zero(),
+            false,
+            true,
+        );
+
+        builder.pop_stacking_context();
+    }
+
+    // the buffers leave with the list, and the builder allocates new ones
+    // sized like them for the next frame
+    builder.end()
+}
+
+/// The opacity disabled elements are rendered with.
+const DISABLED_OPACITY: f32 = 0.5;
+
+// What the nodes of a display list are rendered with
+struct RenderContext<'a> {
+    rdom: &'a RealDom<NodeState>,
+    fonts: &'a FontRegistry,
+    scale_factor: f32,
+}
+
+// Push the display items of a node and its children. `parent_origin` is the
+// position of the parent in the viewport since taffy positions are relative.
+fn render_node(
+    builder: &mut DisplayListBuilder,
+    space_and_clip: &SpaceAndClipInfo,
+    cx: &RenderContext,
+    id: ElementId,
+    parent_origin: LayoutPoint,
+    parent_disabled: bool,
+    parent_visible: bool,
+) {
+    let node = &cx.rdom[id];
+    let layout = match node.state.layout.layout {
+        // taffy gives nodes with `display: none` and their children no size
+        Some(_) if node.state.layout.style.display == Display::None => return,
+        Some(layout) => layout,
+        None => return,
+    };
+    let origin = parent_origin + LayoutVector2D::new(layout.location.x, layout.location.y);
+    // snap the edges to whole device pixels
+    let device = |x: f32| (x * cx.scale_factor).round() as i32;
+    let rect = (device(origin.x), device(origin.y)).to(
+        device(origin.x + layout.size.width),
+        device(origin.y + layout.size.height),
+    );
+
+    // the node and its children are painted in the space of its transform
+    let style = node.state.paint_style();
+    let transformed;
+    let space_and_clip = if style.transform.is_identity() {
+        space_and_clip
+    } else {
+        transformed = push_transform(builder, space_and_clip, id, rect, &style.transform, cx);
+        &transformed
+    };
+
+    // dim the whole disabled subtree once instead of every disabled node in it
+    let disabled = parent_disabled || node.state.disabled.0;
+    let dim = disabled && !parent_disabled;
+    if dim {
+        push_opacity(builder, space_and_clip, DISABLED_OPACITY);
+    }
+
+    let opacity = style.opacity.unwrap_or(1.0);
+    let translucent = opacity < 1.0;
+    if translucent {
+        push_opacity(builder, space_and_clip, opacity);
+    }
+
+    // hidden nodes keep their box, and their visible children are painted
+    let visible = node.state.visibility.0.unwrap_or(parent_visible);
+    let radius = style
+        .border_radius
+        .map(|radius| device_radius(&radius, &rect, cx.scale_factor))
+        .filter(|radius| !radius.is_zero());
+    let has_background = style.background_color.is_some() || style.background_image.is_some();
+    if visible && has_background {
+        let rounded;
+        let background_space_and_clip = match &radius {
+            Some(radius) => {
+                rounded = push_clip(builder, space_and_clip, rect, radius);
+                &rounded
+            }
+            None => space_and_clip,
+        };
+        let props = CommonItemProperties::new(rect, *background_space_and_clip);
+        if let Some(color) = style.background_color {
+            builder.push_rect(&props, rect, color);
+        }
+        if let Some(gradient) = &style.background_image {
+            push_gradient(builder, &props, rect, gradient, cx.scale_factor);
+        }
+    }
+
+    if visible && style.border.iter().any(|side| side.is_visible()) {
+        let props = CommonItemProperties::new(rect, *space_and_clip);
+        let radius = radius.unwrap_or_else(BorderRadius::zero);
+        push_border(builder, &props, cx, id, rect, &style.border, radius);
+    }
+
+    match &node.node_type {
+        NodeType::Element { children, .. } => {
+            // the children of scroll containers are clipped and moved by the
+            // scroll offset, in a scroll frame so a scroll only moves it
+            let clipped;
+            let children_space_and_clip = if node.state.overflow.clips() {
+                let radius = radius.unwrap_or_else(BorderRadius::zero);
+                clipped = push_clip(builder, space_and_clip, rect, &radius);
+                &clipped
+            } else {
+                space_and_clip
+            };
+            let scrolled;
+            let children_space_and_clip = if node.state.overflow.is_scroll_container() {
+                scrolled = push_scroll_frame(builder, children_space_and_clip, cx, id, rect);
+                &scrolled
+            } else {
+                children_space_and_clip
+            };
+            let children_origin = origin - node.state.scroll_offset;
+            for child in children {
+                render_node(
+                    builder,
+                    children_space_and_clip,
+                    cx,
+                    *child,
+                    children_origin,
+                    disabled,
+                    visible,
+                );
+            }
+        }
+        NodeType::Text { text } if visible => {
+            push_text(
+                builder,
+                space_and_clip,
+                cx.fonts,
+                text,
+                &node.state.text,
+                rect,
+                cx.scale_factor,
+            );
+        }
+        _ => (),
+    }
+
+    if translucent {
+        builder.pop_stacking_context();
+    }
+    if dim {
+        builder.pop_stacking_context();
+    }
+    if !style.transform.is_identity() {
         builder.pop_stacking_context();
+        builder.pop_reference_frame();
     }
+}
 
-    txn.set_display_list(epoch, None, layout_size, builder.end());
+// Push a reference frame that transforms the items of a node about its
+// transform origin, and a stacking context for them in it.
+fn push_transform(
+    builder: &mut DisplayListBuilder,
+    space_and_clip: &SpaceAndClipInfo,
+    id: ElementId,
+    rect: LayoutRect,
+    transform: &Transform,
+    cx: &RenderContext,
+) -> SpaceAndClipInfo {
+    let spatial_id = builder.push_reference_frame(
+        LayoutPoint::zero(),
+        space_and_clip.spatial_id,
+        TransformStyle::Flat,
+        PropertyBinding::Value(transform.to_matrix(rect, cx.scale_factor)),
+        ReferenceFrameKind::Transform {
+            is_2d_scale_translation: false,
+            should_snap: false,
+            paired_with_perspective: false,
+        },
+        SpatialTreeItemKey::new(id.0 as u64, 0),
+    );
+    builder.push_simple_stacking_context(
+        LayoutPoint::zero(),
+        spatial_id,
+        PrimitiveFlags::IS_BACKFACE_VISIBLE,
+    );
+    SpaceAndClipInfo {
+        spatial_id,
+        clip_chain_id: space_and_clip.clip_chain_id,
+    }
+}
+
+// Define the scroll frame of a scroll container. Its children are pushed at
+// their scrolled positions, which webrender is told with the external scroll
+// offset, so later scrolls can move the frame with `scroll` instead of
+// building a new display list.
+fn push_scroll_frame(
+    builder: &mut DisplayListBuilder,
+    space_and_clip: &SpaceAndClipInfo,
+    cx: &RenderContext,
+    id: ElementId,
+    rect: LayoutRect,
+) -> SpaceAndClipInfo {
+    let max = max_scroll_offset(cx.rdom, id) * cx.scale_factor;
+    let content = LayoutRect::from_origin_and_size(rect.min, rect.size() + max.to_size());
+    let spatial_id = builder.define_scroll_frame(
+        space_and_clip.spatial_id,
+        scroll_id(builder.pipeline_id, id),
+        content,
+        rect,
+        cx.rdom[id].state.scroll_offset * cx.scale_factor,
+        0,
+        HasScrollLinkedEffect::No,
+        SpatialTreeItemKey::new(id.0 as u64, 1),
+    );
+    SpaceAndClipInfo {
+        spatial_id,
+        clip_chain_id: space_and_clip.clip_chain_id,
+    }
+}
+
+// The id of the scroll frame of a scroll container. The root scroll frame
+// of the pipeline is 0.
+fn scroll_id(pipeline_id: PipelineId, id: ElementId) -> ExternalScrollId {
+    ExternalScrollId(id.0 as u64 + 1, pipeline_id)
+}
+
+// Define a clip chain that clips the items of `space_and_clip` to a rect,
+// with rounded corners if the radius isn't zero.
+fn push_clip(
+    builder: &mut DisplayListBuilder,
+    space_and_clip: &SpaceAndClipInfo,
+    rect: LayoutRect,
+    radius: &BorderRadius,
+) -> SpaceAndClipInfo {
+    let clip_id = if radius.is_zero() {
+        builder.define_clip_rect(space_and_clip.spatial_id, rect)
+    } else {
+        let region = ComplexClipRegion::new(rect, *radius, ClipMode::Clip);
+        builder.define_clip_rounded_rect(space_and_clip.spatial_id, region)
+    };
+    let parent = Some(space_and_clip.clip_chain_id).filter(|id| *id != ClipChainId::INVALID);
+    SpaceAndClipInfo {
+        spatial_id: space_and_clip.spatial_id,
+        clip_chain_id: builder.define_clip_chain(parent, [clip_id]),
+    }
+}
+
+// Push a gradient that fills a rect. The gradient points are relative to the
+// rect, and the stops in layout units are scaled like it. Webrender repeats
+// the stops between the first and the last one.
+fn push_gradient(
+    builder: &mut DisplayListBuilder,
+    props: &CommonItemProperties,
+    rect: LayoutRect,
+    gradient: &Gradient,
+    scale_factor: f32,
+) {
+    let size = rect.size();
+    let extend_mode = if gradient.repeating {
+        ExtendMode::Repeat
+    } else {
+        ExtendMode::Clamp
+    };
+    let center = LayoutPoint::new(size.width / 2.0, size.height / 2.0);
+    match gradient.kind {
+        GradientKind::Linear(direction) => {
+            // the line is long enough for its ends to reach the corners
+            let (x, y) = direction.vector(size.width, size.height);
+            let length = (size.width * x).abs() + (size.height * y).abs();
+            let half = LayoutVector2D::new(x, y) * (length / 2.0);
+            let stops = gradient.resolve_stops(length / scale_factor);
+            let gradient =
+                builder.create_gradient(center - half, center + half, stops, extend_mode);
+            builder.push_gradient(props, rect, gradient, size, LayoutSize::zero());
+        }
+        GradientKind::Radial { circle } => {
+            // the shape reaches the farthest corner
+            let radius = if circle {
+                let radius = center.x.hypot(center.y);
+                LayoutSize::new(radius, radius)
+            } else {
+                LayoutSize::new(center.x, center.y) * std::f32::consts::SQRT_2
+            };
+            let stops = gradient.resolve_stops(radius.width / scale_factor);
+            let gradient = builder.create_radial_gradient(center, radius, stops, extend_mode);
+            builder.push_radial_gradient(props, rect, gradient, size, LayoutSize::zero());
+        }
+    }
+}
+
+// Push the border of a node, with the widths of its layout and the colors and
+// styles of its paint style.
+fn push_border(
+    builder: &mut DisplayListBuilder,
+    props: &CommonItemProperties,
+    cx: &RenderContext,
+    id: ElementId,
+    rect: LayoutRect,
+    sides: &[BorderSide; 4],
+    radius: BorderRadius,
+) {
+    let node = &cx.rdom[id];
+    let border = node.state.layout.style.border;
+    let width = |dimension: Dimension, side: &BorderSide| match dimension {
+        Dimension::Points(width) if side.is_visible() => width * cx.scale_factor,
+        _ => 0.0,
+    };
+    let widths = LayoutSideOffsets::new(
+        width(border.top, &sides[0]),
+        width(border.end, &sides[1]),
+        width(border.bottom, &sides[2]),
+        width(border.start, &sides[3]),
+    );
+    if widths.is_zero() {
+        return;
+    }
+    // sides without a color are painted with the color of the text
+    let side = |side: &BorderSide| webrender::api::BorderSide {
+        color: side.color.unwrap_or(node.state.text.color),
+        style: side.style,
+    };
+    let details = BorderDetails::Normal(NormalBorder {
+        top: side(&sides[0]),
+        right: side(&sides[1]),
+        bottom: side(&sides[2]),
+        left: side(&sides[3]),
+        radius,
+        do_aa: true,
+    });
+    builder.push_border(props, rect, widths, details);
+}
+
+// Scale the radii of a box to device pixels. Like in css, all the radii are
+// shrunk when the radii of two corners don't fit along a side.
+fn device_radius(radius: &BorderRadius, rect: &LayoutRect, scale_factor: f32) -> BorderRadius {
+    let mut radius = BorderRadius {
+        top_left: radius.top_left * scale_factor,
+        top_right: radius.top_right * scale_factor,
+        bottom_right: radius.bottom_right * scale_factor,
+        bottom_left: radius.bottom_left * scale_factor,
+    };
+    let (width, height) = (rect.width(), rect.height());
+    // sides without radii divide by zero, which doesn't lower the factor
+    let factor = [
+        width / (radius.top_left.width + radius.top_right.width),
+        width / (radius.bottom_left.width + radius.bottom_right.width),
+        height / (radius.top_left.height + radius.bottom_left.height),
+        height / (radius.top_right.height + radius.bottom_right.height),
+    ]
+    .into_iter()
+    .fold(1.0, f32::min);
+    if factor < 1.0 {
+        for corner in [
+            &mut radius.top_left,
+            &mut radius.top_right,
+            &mut radius.bottom_right,
+            &mut radius.bottom_left,
+        ] {
+            *corner = *corner * factor;
+        }
+    }
+    radius
+}
+
+// Push a stacking context that renders its items with an opacity.
+fn push_opacity(builder: &mut DisplayListBuilder, space_and_clip: &SpaceAndClipInfo, opacity: f32) {
+    builder.push_simple_stacking_context_with_filters(
+        LayoutPoint::zero(),
+        space_and_clip.spatial_id,
+        PrimitiveFlags::IS_BACKFACE_VISIBLE,
+        &[FilterOp::Opacity(PropertyBinding::Value(opacity), opacity)],
+        &[],
+        &[],
+    );
+}
+
+// Push the glyphs of a text node, one text item per line. The text is laid
+// out in logical pixels and scaled to the device pixels of `rect`.
+fn push_text(
+    builder: &mut DisplayListBuilder,
+    space_and_clip: &SpaceAndClipInfo,
+    fonts: &FontRegistry,
+    text: &str,
+    style: &TextStyle,
+    rect: LayoutRect,
+    scale_factor: f32,
+) {
+    let width = rect.width() / scale_factor;
+    let layout = layout_text(fonts, text, style, Some(width));
+    let font = match layout.font {
+        Some(font) => font,
+        None => return,
+    };
+    let metrics = fonts.metrics(font, style.font_size);
+
+    // a text box stretched by a flex container centers its lines vertically
+    let top = rect.min.y + ((rect.height() - layout.height() * scale_factor) / 2.0).max(0.0);
+    let last = layout.lines.len().saturating_sub(1);
+    for (i, line) in layout.lines.iter().enumerate() {
+        let baseline = top + (i as f32 * layout.line_height + layout.ascent) * scale_factor;
+        let align = layout.line_align(style.text_align, i == last);
+        let (start, end) = line.aligned_span(align, width, i == last);
+        // a word longer than the text box overflows it like in css, so a
+        // line is only clipped by the clips of the ancestors. Its bounds
+        // leave room for the glyphs that reach out of the line box.
+        let reach = layout.line_height.max(style.font_size) * scale_factor;
+        let bounds = LayoutRect::new(
+            LayoutPoint::new(rect.min.x + start.min(0.0) * scale_factor, baseline - reach),
+            LayoutPoint::new(rect.min.x + end.max(width) * scale_factor, baseline + reach),
+        );
+        let props = CommonItemProperties::new(bounds, *space_and_clip);
+        // a text item has a single font, so the line is split into runs of
+        // the glyphs of each font
+        let mut runs: Vec<(FontId, Vec<GlyphInstance>)> = Vec::new();
+        for (g, x) in line.glyphs.iter().zip(line.align(align, width, i == last)) {
+            let glyph = GlyphInstance {
+                index: g.index,
+                point: LayoutPoint::new(
+                    rect.min.x + x * scale_factor,
+                    baseline + g.y * scale_factor,
+                ),
+            };
+            match runs.last_mut() {
+                Some((font, glyphs)) if *font == g.font => glyphs.push(glyph),
+                _ => runs.push((g.font, vec![glyph])),
+            }
+        }
+        for (font, glyphs) in runs {
+            // the glyphs are rasterized at the device size to stay sharp
+            let instance = fonts.instance(font, style.font_size * scale_factor);
+            builder.push_text(&props, bounds, &glyphs, instance, style.color, None);
+        }
+
+        if style.underline {
+            let y = baseline + metrics.underline_position * scale_factor;
+            let thickness = (metrics.underline_thickness * scale_factor).max(1.0);
+            let underline = LayoutRect::new(
+                LayoutPoint::new(rect.min.x + start * scale_factor, y),
+                LayoutPoint::new(rect.min.x + end * scale_factor, y + thickness),
+            );
+            builder.push_rect(&props, underline, style.color);
+        }
+    }
+}
+
+/// Build the display list of the RealDom and send it to webrender.
+#[allow(clippy::too_many_arguments)]
+pub fn render(
+    document_id: DocumentId,
+    epoch: Epoch,
+    api: &mut RenderApi,
+    builder: &mut DisplayListBuilder,
+    rdom: &RealDom<NodeState>,
+    fonts: &FontRegistry,
+    layout_size: LayoutSize,
+    scale_factor: f32,
+) {
+    let display_list = build_display_list(builder, rdom, fonts, layout_size, scale_factor);
+
+    let mut txn = Transaction::new();
+    // the fonts used by the display list have to be added before it
+    fonts.flush(&mut txn);
+    txn.set_display_list(epoch, None, layout_size, display_list);
     txn.generate_frame(0, RenderReasons::empty());
     api.send_transaction(document_id, txn);
 }
+
+/// Move the scroll frames of scroll containers to their current offsets and
+/// render a frame, without building a new display list.
+pub(crate) fn scroll(
+    document_id: DocumentId,
+    api: &mut RenderApi,
+    pipeline_id: PipelineId,
+    rdom: &RealDom<NodeState>,
+    containers: &[ElementId],
+    scale_factor: f32,
+) {
+    let mut txn = Transaction::new();
+    for &id in containers {
+        // webrender moves the content by the opposite of the scroll offset
+        let offset = -rdom[id].state.scroll_offset * scale_factor;
+        txn.set_scroll_offsets(
+            scroll_id(pipeline_id, id),
+            vec![SampledScrollOffset {
+                offset,
+                generation: 0,
+            }],
+        );
+    }
+    txn.generate_frame(0, RenderReasons::APZ);
+    api.send_transaction(document_id, txn);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::alloc::{GlobalAlloc, System};
+    use std::cell::Cell;
+    use std::time::Instant;
+
+    use dioxus::prelude::*;
+
+    use super::*;
+    use crate::state::TestDom;
+
+    thread_local! {
+        static COUNTING: Cell<bool> = const { Cell::new(false) };
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    // Counts the allocations of the threads that turned counting on, so the
+    // tests running in parallel don't add to them.
+    struct CountingAllocator;
+
+    impl CountingAllocator {
+        fn count() {
+            let counting = COUNTING.try_with(Cell::get).unwrap_or(false);
+            if counting {
+                let _ = ALLOCATIONS.try_with(|a| a.set(a.get() + 1));
+            }
+        }
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: std::alloc::Layout) -> *mut u8 {
+            Self::count();
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: std::alloc::Layout) {
+            System.dealloc(ptr, layout)
+        }
+
+        unsafe fn realloc(
+            &self,
+            ptr: *mut u8,
+            layout: std::alloc::Layout,
+            new_size: usize,
+        ) -> *mut u8 {
+            Self::count();
+            System.realloc(ptr, layout, new_size)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    // 500 cells with a background, a border and a text
+    fn cells(cx: Scope) -> Element {
+        cx.render(rsx! {
+            div {
+                flex_wrap: "wrap",
+                (0..500).map(|i| rsx! {
+                    div {
+                        key: "{i}",
+                        width: "60px",
+                        background_color: "rgb(200, 220, 240)",
+                        border: "1px solid black",
+                        "{i}"
+                    }
+                })
+            }
+        })
+    }
+
+    // The builder keeps the size of the last list, so once it has built a
+    // frame, every frame of the same tree makes the same allocations and none
+    // of its buffers grow. It doesn't reach zero allocations per frame:
+    // `end()` gives the buffers away with the list, so the builder allocates
+    // new ones for every frame. Run with
+    // `cargo test --release display_list_allocations -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn display_list_allocations() {
+        const FRAMES: usize = 100;
+        let dom = TestDom::new(cells);
+        let mut builder = DisplayListBuilder::new(PipelineId(0, 0));
+        let size = LayoutSize::new(800.0, 600.0);
+
+        let mut allocations = Vec::with_capacity(FRAMES);
+        let started = Instant::now();
+        for _ in 0..FRAMES {
+            COUNTING.with(|c| c.set(true));
+            let (_, list) = build_display_list(&mut builder, &dom.rdom, &dom.fonts, size, 1.0);
+            COUNTING.with(|c| c.set(false));
+            drop(list);
+            allocations.push(ALLOCATIONS.with(|a| a.replace(0)));
+        }
+        println!(
+            "{:?} per frame, allocations of the first frames: {:?}",
+            started.elapsed() / FRAMES as u32,
+            &allocations[..3]
+        );
+
+        assert!(allocations[1] <= allocations[0]);
+        assert!(
+            allocations[1..].windows(2).all(|w| w[0] == w[1]),
+            "{allocations:?}"
+        );
+    }
+}
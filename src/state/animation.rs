@@ -0,0 +1,286 @@
+use std::time::{Duration, Instant};
+
+use super::{
+    transition::{parse_time, AnimatedValue, TimingFunction, TransitionProperty},
+    PaintStyle,
+};
+use crate::style::Stylesheet;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AnimationDirection {
+    Normal,
+    Reverse,
+    Alternate,
+    AlternateReverse,
+}
+
+/// An animation from the css `animation` property.
+#[derive(Clone, PartialEq, Debug)]
+pub struct KeyframeAnimation {
+    pub name: String,
+    pub duration: Duration,
+    pub delay: Duration,
+    pub timing: TimingFunction,
+    /// The number of iterations, infinite for `infinite`.
+    pub iterations: f32,
+    pub direction: AnimationDirection,
+    pub paused: bool,
+    /// The keyframes with the name, resolved from the stylesheet.
+    keyframes: Vec<(f32, Vec<(TransitionProperty, AnimatedValue)>)>,
+}
+
+impl KeyframeAnimation {
+    /// Look up the keyframes of the animation in the stylesheet. Animations
+    /// without keyframes don't change anything.
+    pub(super) fn resolve(&mut self, stylesheet: &Stylesheet) {
+        self.keyframes = stylesheet
+            .keyframes(&self.name)
+            .unwrap_or_default()
+            .iter()
+            .map(|keyframe| {
+                let values = keyframe
+                    .declarations
+                    .iter()
+                    .filter_map(|(name, value)| TransitionProperty::parse(name, value))
+                    .collect();
+                (keyframe.offset, values)
+            })
+            .collect();
+    }
+
+    fn is_finished(&self, elapsed: Duration) -> bool {
+        let time = elapsed.saturating_sub(self.delay).as_secs_f32();
+        self.duration.is_zero() || time >= self.duration.as_secs_f32() * self.iterations
+    }
+
+    // The values of the animated properties after `elapsed`, or None if the
+    // animation is in its delay or finished. Properties missing from the
+    // first or last keyframe animate from or to their value in `underlying`.
+    fn values_at(
+        &self,
+        elapsed: Duration,
+        underlying: &PaintStyle,
+    ) -> Option<Vec<(TransitionProperty, AnimatedValue)>> {
+        let time = elapsed.checked_sub(self.delay)?;
+        if self.is_finished(elapsed) {
+            return None;
+        }
+        let iteration = time.as_secs_f32() / self.duration.as_secs_f32();
+        let odd = iteration as u32 % 2 == 1;
+        let reversed = match self.direction {
+            AnimationDirection::Normal => false,
+            AnimationDirection::Reverse => true,
+            AnimationDirection::Alternate => odd,
+            AnimationDirection::AlternateReverse => !odd,
+        };
+        let progress = if reversed {
+            1.0 - iteration.fract()
+        } else {
+            iteration.fract()
+        };
+
+        let mut properties: Vec<TransitionProperty> = Vec::new();
+        for (_, values) in &self.keyframes {
+            for (property, _) in values {
+                if !properties.contains(property) {
+                    properties.push(*property);
+                }
+            }
+        }
+        Some(
+            properties
+                .into_iter()
+                .map(|p| (p, self.value_of(p, progress, underlying)))
+                .collect(),
+        )
+    }
+
+    fn value_of(
+        &self,
+        property: TransitionProperty,
+        progress: f32,
+        underlying: &PaintStyle,
+    ) -> AnimatedValue {
+        let mut frames: Vec<(f32, AnimatedValue)> = self
+            .keyframes
+            .iter()
+            .filter_map(|(offset, values)| {
+                let (_, value) = values.iter().find(|(p, _)| *p == property)?;
+                Some((*offset, value.clone()))
+            })
+            .collect();
+        let base = property.get(underlying);
+        if frames.first().map_or(true, |(offset, _)| *offset > 0.0) {
+            frames.insert(0, (0.0, base.clone()));
+        }
+        if frames.last().map_or(true, |(offset, _)| *offset < 1.0) {
+            frames.push((1.0, base));
+        }
+
+        // the timing function applies between each pair of keyframes
+        let i = frames
+            .iter()
+            .rposition(|(offset, _)| *offset <= progress)
+            .unwrap_or(0)
+            .min(frames.len() - 2);
+        let ((start, from), (end, to)) = (&frames[i], &frames[i + 1]);
+        let (start, end) = (*start, *end);
+        let local = if end > start {
+            ((progress - start) / (end - start)).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+        from.interpolate(to, self.timing.apply(local))
+    }
+}
+
+/// Parse the value of the css `animation` property, like
+/// `spin 2s linear infinite alternate`.
+pub fn parse_animations(value: &str) -> Option<Vec<KeyframeAnimation>> {
+    let mut animations = Vec::new();
+    for animation in value.split(',') {
+        let mut name = None;
+        let mut times = Vec::new();
+        let mut timing = TimingFunction::EASE;
+        let mut iterations = 1.0;
+        let mut direction = AnimationDirection::Normal;
+        let mut paused = false;
+        for token in animation.split_whitespace() {
+            if let Some(time) = parse_time(token) {
+                times.push(time);
+            } else if let Some(t) = TimingFunction::parse(token) {
+                timing = t;
+            } else if let Ok(n) = token.parse::<f32>() {
+                iterations = if n >= 0.0 { n } else { return None };
+            } else {
+                match token {
+                    "infinite" => iterations = f32::INFINITY,
+                    "normal" => direction = AnimationDirection::Normal,
+                    "reverse" => direction = AnimationDirection::Reverse,
+                    "alternate" => direction = AnimationDirection::Alternate,
+                    "alternate-reverse" => direction = AnimationDirection::AlternateReverse,
+                    "running" => paused = false,
+                    "paused" => paused = true,
+                    name_token => name = Some(name_token),
+                }
+            }
+        }
+        let (duration, delay) = match times[..] {
+            [] => (Duration::ZERO, Duration::ZERO),
+            [duration] => (duration, Duration::ZERO),
+            [duration, delay] => (duration, delay),
+            _ => return None,
+        };
+        match name {
+            None | Some("none") => (),
+            Some(name) => animations.push(KeyframeAnimation {
+                name: name.to_string(),
+                duration,
+                delay,
+                timing,
+                iterations,
+                direction,
+                paused,
+                keyframes: Vec::new(),
+            }),
+        }
+    }
+    Some(animations)
+}
+
+/// Set the play state of animations from the `animation-play-state` property.
+/// Like other animation properties, the values repeat if there are more
+/// animations than values.
+pub(super) fn apply_play_state(animations: &mut [KeyframeAnimation], value: &str) -> bool {
+    let states: Option<Vec<bool>> = value
+        .split(',')
+        .map(|s| match s.trim() {
+            "running" => Some(false),
+            "paused" => Some(true),
+            _ => None,
+        })
+        .collect();
+    match states {
+        Some(states) if !states.is_empty() => {
+            for (animation, paused) in animations.iter_mut().zip(states.iter().cycle()) {
+                animation.paused = *paused;
+            }
+            true
+        }
+        _ => false,
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+struct AnimationClock {
+    name: String,
+    /// The time the animation has been playing, without the pauses.
+    elapsed: Duration,
+    last_tick: Option<Instant>,
+}
+
+/// The clocks and current values of the keyframe animations of a node.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct AnimationState {
+    clocks: Vec<AnimationClock>,
+    values: Vec<(TransitionProperty, AnimatedValue)>,
+}
+
+impl AnimationState {
+    /// Returns true if the node has animations that were ticked.
+    pub fn is_active(&self) -> bool {
+        !self.clocks.is_empty()
+    }
+
+    /// Advance the clocks of the animations and compute their values. An
+    /// animation starts on its first tick and restarts when its name goes
+    /// away and comes back. Returns true if an animation needs more frames,
+    /// paused and finished animations don't.
+    pub(crate) fn tick(
+        &mut self,
+        animations: &[KeyframeAnimation],
+        underlying: &PaintStyle,
+        now: Instant,
+    ) -> bool {
+        self.clocks
+            .retain(|c| animations.iter().any(|a| a.name == c.name));
+        self.values.clear();
+
+        let mut running = false;
+        for animation in animations {
+            let clock = match self.clocks.iter().position(|c| c.name == animation.name) {
+                Some(i) => &mut self.clocks[i],
+                None => {
+                    self.clocks.push(AnimationClock {
+                        name: animation.name.clone(),
+                        elapsed: Duration::ZERO,
+                        last_tick: None,
+                    });
+                    self.clocks.last_mut().unwrap()
+                }
+            };
+            if let (Some(last), false) = (clock.last_tick, animation.paused) {
+                clock.elapsed += now.saturating_duration_since(last);
+            }
+            clock.last_tick = Some(now);
+
+            // later animations override the earlier ones
+            if let Some(values) = animation.values_at(clock.elapsed, underlying) {
+                self.values.extend(values);
+            }
+            running |= !animation.paused && !animation.is_finished(clock.elapsed);
+        }
+        running
+    }
+
+    /// Override the properties of `style` with the values of the last tick.
+    pub fn apply(&self, style: &mut PaintStyle) {
+        for (property, value) in &self.values {
+            property.set(style, value.clone());
+        }
+    }
+
+    pub fn has_values(&self) -> bool {
+        !self.values.is_empty()
+    }
+}
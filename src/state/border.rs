@@ -0,0 +1,215 @@
+use webrender::api::{units::LayoutSize, BorderRadius, BorderStyle, ColorF};
+
+use crate::{style::parse_color, utils::split_outside_parens};
+
+/// The color and style of a side of a border. Its width is part of the
+/// layout.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct BorderSide {
+    /// The color of the side, or None for the color of the text.
+    pub color: Option<ColorF>,
+    pub style: BorderStyle,
+}
+
+impl BorderSide {
+    /// Returns true if the side is painted when it has a width.
+    pub fn is_visible(&self) -> bool {
+        !matches!(self.style, BorderStyle::None | BorderStyle::Hidden)
+    }
+}
+
+impl Default for BorderSide {
+    fn default() -> Self {
+        Self {
+            color: None,
+            style: BorderStyle::None,
+        }
+    }
+}
+
+/// Apply a declaration of the border colors and styles to the sides, in the
+/// top, right, bottom and left order. The widths are applied by the layout.
+/// Returns false if the value is invalid.
+pub(super) fn apply_border(sides: &mut [BorderSide; 4], name: &str, value: &str) -> bool {
+    match name {
+        "border" => parse_border_side(value)
+            .map(|side| *sides = [side; 4])
+            .is_some(),
+        "border-color" => match parse_each(value, parse_color).as_deref().and_then(expand) {
+            Some(colors) => {
+                for (side, color) in sides.iter_mut().zip(colors) {
+                    side.color = Some(color);
+                }
+                true
+            }
+            None => false,
+        },
+        "border-style" => match parse_each(value, parse_border_style)
+            .as_deref()
+            .and_then(expand)
+        {
+            Some(styles) => {
+                for (side, style) in sides.iter_mut().zip(styles) {
+                    side.style = style;
+                }
+                true
+            }
+            None => false,
+        },
+        "border-width" => true,
+        _ => {
+            let mut parts = name.split('-').skip(1);
+            let side = match parts.next() {
+                Some("top") => &mut sides[0],
+                Some("right") => &mut sides[1],
+                Some("bottom") => &mut sides[2],
+                Some("left") => &mut sides[3],
+                _ => return true,
+            };
+            match parts.next() {
+                None => parse_border_side(value).map(|new| *side = new).is_some(),
+                Some("color") => parse_color(value).map(|c| side.color = Some(c)).is_some(),
+                Some("style") => parse_border_style(value.trim())
+                    .map(|style| side.style = style)
+                    .is_some(),
+                _ => true,
+            }
+        }
+    }
+}
+
+/// Parse the `border` shorthand or a side shorthand like `border-top`: an
+/// optional width, style and color in any order. The omitted values are
+/// reset.
+pub(super) fn parse_border_side(value: &str) -> Option<BorderSide> {
+    let mut side = BorderSide::default();
+    for token in tokens(value) {
+        if let Some(style) = parse_border_style(token) {
+            side.style = style;
+        } else if parse_border_width(token).is_none() {
+            side.color = Some(parse_color(token)?);
+        }
+    }
+    Some(side)
+}
+
+/// The width set by a border shorthand, or None if the shorthand has no
+/// visible style. Like in css, a visible border without a width is medium.
+pub(super) fn border_shorthand_width(value: &str) -> Option<f32> {
+    let tokens = tokens(value);
+    let style = tokens.iter().find_map(|t| parse_border_style(t))?;
+    if matches!(style, BorderStyle::None | BorderStyle::Hidden) {
+        return None;
+    }
+    let width = tokens.iter().find_map(|t| parse_border_width(t));
+    Some(width.unwrap_or(MEDIUM_BORDER_WIDTH))
+}
+
+const MEDIUM_BORDER_WIDTH: f32 = 3.0;
+
+fn parse_border_width(token: &str) -> Option<f32> {
+    let width = match token {
+        "thin" => 1.0,
+        "medium" => MEDIUM_BORDER_WIDTH,
+        "thick" => 5.0,
+        "0" => 0.0,
+        _ => token.strip_suffix("px")?.parse().ok()?,
+    };
+    (width.is_finite() && width >= 0.0).then(|| width)
+}
+
+fn parse_border_style(token: &str) -> Option<BorderStyle> {
+    Some(match token {
+        "none" => BorderStyle::None,
+        "hidden" => BorderStyle::Hidden,
+        "solid" => BorderStyle::Solid,
+        "double" => BorderStyle::Double,
+        "dotted" => BorderStyle::Dotted,
+        "dashed" => BorderStyle::Dashed,
+        "groove" => BorderStyle::Groove,
+        "ridge" => BorderStyle::Ridge,
+        "inset" => BorderStyle::Inset,
+        "outset" => BorderStyle::Outset,
+        _ => return None,
+    })
+}
+
+// Parse each of the space separated values.
+fn parse_each<T>(value: &str, parse: impl Fn(&str) -> Option<T>) -> Option<Vec<T>> {
+    tokens(value).into_iter().map(parse).collect()
+}
+
+// Split a value on the spaces that are not between parentheses, so colors
+// like `rgb(0, 0, 0)` stay whole.
+fn tokens(value: &str) -> Vec<&str> {
+    split_outside_parens(value, char::is_whitespace)
+}
+
+/// Parse the `border-radius` shorthand: one to four horizontal radii, then
+/// optionally `/` and one to four vertical radii. Like the margin shorthand,
+/// the values start at the top left corner and go clockwise.
+pub(super) fn parse_border_radius(value: &str) -> Option<BorderRadius> {
+    let (horizontal, vertical) = match value.split_once('/') {
+        Some((horizontal, vertical)) => (expand_corners(horizontal)?, expand_corners(vertical)?),
+        None => {
+            let radii = expand_corners(value)?;
+            (radii, radii)
+        }
+    };
+    let corner = |i: usize| LayoutSize::new(horizontal[i], vertical[i]);
+    Some(BorderRadius {
+        top_left: corner(0),
+        top_right: corner(1),
+        bottom_right: corner(2),
+        bottom_left: corner(3),
+    })
+}
+
+/// Parse a corner longhand like `border-top-left-radius`: a horizontal
+/// radius and an optional vertical one.
+pub(super) fn parse_corner_radius(value: &str) -> Option<LayoutSize> {
+    let values: Option<Vec<f32>> = value.split_whitespace().map(parse_radius).collect();
+    match *values?.as_slice() {
+        [both] => Some(LayoutSize::new(both, both)),
+        [horizontal, vertical] => Some(LayoutSize::new(horizontal, vertical)),
+        _ => None,
+    }
+}
+
+/// The corner of a radius set by a corner longhand.
+pub(super) fn corner_mut<'a>(radius: &'a mut BorderRadius, name: &str) -> &'a mut LayoutSize {
+    match name {
+        "border-top-left-radius" => &mut radius.top_left,
+        "border-top-right-radius" => &mut radius.top_right,
+        "border-bottom-right-radius" => &mut radius.bottom_right,
+        _ => &mut radius.bottom_left,
+    }
+}
+
+// Expand one to four values to the top left, top right, bottom right and
+// bottom left corners.
+fn expand_corners(value: &str) -> Option<[f32; 4]> {
+    let values: Option<Vec<f32>> = value.split_whitespace().map(parse_radius).collect();
+    expand(&values?)
+}
+
+// Expand one to four values to four, like the margin shorthand expands them
+// to the top, right, bottom and left sides.
+fn expand<T: Copy>(values: &[T]) -> Option<[T; 4]> {
+    Some(match *values {
+        [all] => [all; 4],
+        [vertical, horizontal] => [vertical, horizontal, vertical, horizontal],
+        [top, horizontal, bottom] => [top, horizontal, bottom, horizontal],
+        [top, right, bottom, left] => [top, right, bottom, left],
+        _ => return None,
+    })
+}
+
+fn parse_radius(value: &str) -> Option<f32> {
+    let radius = match value.strip_suffix("px") {
+        Some(px) => px.parse().ok()?,
+        None if value == "0" => 0.0,
+        None => return None,
+    };
+    (radius.is_finite() && radius >= 0.0).then(|| radius)
+}
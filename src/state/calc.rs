@@ -0,0 +1,201 @@
+/// A parsed css `calc()` expression.
+///
+/// Taffy dimensions can't mix units, so the expression is kept around and
+/// resolved against the parent size once the parent has been laid out.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Calc {
+    Number(f32),
+    Points(f32),
+    Percent(f32),
+    Em(f32),
+    Add(Box<Calc>, Box<Calc>),
+    Sub(Box<Calc>, Box<Calc>),
+    Mul(Box<Calc>, Box<Calc>),
+    Div(Box<Calc>, Box<Calc>),
+}
+
+impl Calc {
+    /// Parse a `calc(...)` value. Returns None if the value is not a valid calc expression.
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut parser = Parser {
+            src: value.trim(),
+            pos: 0,
+        };
+        let calc = parser.function()?;
+        if parser.pos == parser.src.len() {
+            Some(calc)
+        } else {
+            None
+        }
+    }
+
+    /// Resolve the expression to points. Percentages are relative to `parent`
+    /// and em values to `font_size`.
+    pub fn resolve(&self, parent: f32, font_size: f32) -> f32 {
+        match self {
+            Calc::Number(n) | Calc::Points(n) => *n,
+            Calc::Percent(p) => parent * p / 100.0,
+            Calc::Em(e) => e * font_size,
+            Calc::Add(a, b) => a.resolve(parent, font_size) + b.resolve(parent, font_size),
+            Calc::Sub(a, b) => a.resolve(parent, font_size) - b.resolve(parent, font_size),
+            Calc::Mul(a, b) => a.resolve(parent, font_size) * b.resolve(parent, font_size),
+            Calc::Div(a, b) => {
+                let divisor = b.resolve(parent, font_size);
+                if divisor == 0.0 {
+                    0.0
+                } else {
+                    a.resolve(parent, font_size) / divisor
+                }
+            }
+        }
+    }
+
+    /// Returns true if the expression is a unitless number.
+    fn is_number(&self) -> bool {
+        match self {
+            Calc::Number(_) => true,
+            Calc::Points(_) | Calc::Percent(_) | Calc::Em(_) => false,
+            // both sides of a sum always have the same type
+            Calc::Add(a, _) | Calc::Sub(a, _) => a.is_number(),
+            Calc::Mul(a, b) => a.is_number() && b.is_number(),
+            Calc::Div(a, _) => a.is_number(),
+        }
+    }
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.src[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn eat(&mut self, s: &str) -> bool {
+        if self.rest().starts_with(s) {
+            self.pos += s.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns true if any whitespace was skipped.
+    fn skip_whitespace(&mut self) -> bool {
+        let rest = self.rest();
+        let skipped = rest.len() - rest.trim_start().len();
+        self.pos += skipped;
+        skipped > 0
+    }
+
+    // function := "calc(" sum ")"
+    fn function(&mut self) -> Option<Calc> {
+        if !self.eat("calc(") {
+            return None;
+        }
+        self.group()
+    }
+
+    // the inside of a parenthesized expression, including the closing paren
+    fn group(&mut self) -> Option<Calc> {
+        self.skip_whitespace();
+        let calc = self.sum()?;
+        self.skip_whitespace();
+        self.eat(")").then(|| calc)
+    }
+
+    // sum := product ( ws ("+" | "-") ws product )*
+    fn sum(&mut self) -> Option<Calc> {
+        let mut lhs = self.product()?;
+        loop {
+            let start = self.pos;
+            // + and - must be surrounded by whitespace, so `100%-40px` is invalid
+            if !self.skip_whitespace() {
+                break;
+            }
+            let op = match self.peek() {
+                Some(op @ ('+' | '-')) => op,
+                _ => {
+                    self.pos = start;
+                    break;
+                }
+            };
+            self.pos += 1;
+            if !self.skip_whitespace() {
+                return None;
+            }
+            let rhs = self.product()?;
+            if lhs.is_number() != rhs.is_number() {
+                return None;
+            }
+            lhs = match op {
+                '+' => Calc::Add(Box::new(lhs), Box::new(rhs)),
+                _ => Calc::Sub(Box::new(lhs), Box::new(rhs)),
+            };
+        }
+        Some(lhs)
+    }
+
+    // product := value ( ws? ("*" | "/") ws? value )*
+    fn product(&mut self) -> Option<Calc> {
+        let mut lhs = self.value()?;
+        loop {
+            let start = self.pos;
+            self.skip_whitespace();
+            let op = match self.peek() {
+                Some(op @ ('*' | '/')) => op,
+                _ => {
+                    self.pos = start;
+                    break;
+                }
+            };
+            self.pos += 1;
+            self.skip_whitespace();
+            let rhs = self.value()?;
+            lhs = match op {
+                '*' if lhs.is_number() || rhs.is_number() => {
+                    Calc::Mul(Box::new(lhs), Box::new(rhs))
+                }
+                '/' if rhs.is_number() => Calc::Div(Box::new(lhs), Box::new(rhs)),
+                _ => return None,
+            };
+        }
+        Some(lhs)
+    }
+
+    // value := "(" sum ")" | function | number unit?
+    fn value(&mut self) -> Option<Calc> {
+        if self.eat("(") {
+            return self.group();
+        }
+        if self.rest().starts_with("calc(") {
+            return self.function();
+        }
+
+        let number_len = self
+            .rest()
+            .char_indices()
+            .take_while(|&(i, c)| {
+                c.is_ascii_digit() || c == '.' || (i == 0 && matches!(c, '+' | '-'))
+            })
+            .count();
+        let number: f32 = self.rest()[..number_len].parse().ok()?;
+        self.pos += number_len;
+
+        Some(if self.eat("px") {
+            Calc::Points(number)
+        } else if self.eat("%") {
+            Calc::Percent(number)
+        } else if self.eat("em") {
+            Calc::Em(number)
+        } else {
+            Calc::Number(number)
+        })
+    }
+}
@@ -0,0 +1,88 @@
+use std::rc::Rc;
+
+use dioxus_native_core::{
+    node_ref::{AttributeMask, NodeMask, NodeView},
+    state::NodeDepState,
+};
+use dioxus_native_core_macro::sorted_str_slice;
+use glutin::window::CursorIcon;
+
+use crate::style::Stylesheet;
+
+/// The mouse cursor shown over the node, set by the `cursor` style. None
+/// uses the cursor of the parent like `cursor: auto`.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Cursor(pub Option<CursorIcon>);
+
+impl NodeDepState<()> for Cursor {
+    type Ctx = Rc<Stylesheet>;
+    const NODE_MASK: NodeMask =
+        NodeMask::new_with_attrs(AttributeMask::Static(CURSOR_ATTRIBUTES)).with_tag();
+
+    fn reduce(&mut self, node: NodeView<'_>, _sibling: (), stylesheet: &Self::Ctx) -> bool {
+        let mut new = Cursor::default();
+        // stylesheet rules come first so inline styles override them
+        let inline = node
+            .attributes()
+            .filter_map(|a| Some((a.name, a.value.as_text()?)));
+        for (name, value) in stylesheet.matching(&node).into_iter().chain(inline) {
+            if name == "cursor" {
+                match parse_cursor(value) {
+                    Some(cursor) => new = cursor,
+                    None => warn!("Invalid cursor: {}", value),
+                }
+            }
+        }
+
+        if *self != new {
+            *self = new;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+fn parse_cursor(value: &str) -> Option<Cursor> {
+    let icon = match value {
+        "auto" => return Some(Cursor(None)),
+        "default" => CursorIcon::Default,
+        "pointer" => CursorIcon::Hand,
+        "text" => CursorIcon::Text,
+        "vertical-text" => CursorIcon::VerticalText,
+        "crosshair" => CursorIcon::Crosshair,
+        "move" => CursorIcon::Move,
+        "wait" => CursorIcon::Wait,
+        "help" => CursorIcon::Help,
+        "progress" => CursorIcon::Progress,
+        "not-allowed" => CursorIcon::NotAllowed,
+        "context-menu" => CursorIcon::ContextMenu,
+        "cell" => CursorIcon::Cell,
+        "alias" => CursorIcon::Alias,
+        "copy" => CursorIcon::Copy,
+        "no-drop" => CursorIcon::NoDrop,
+        "grab" => CursorIcon::Grab,
+        "grabbing" => CursorIcon::Grabbing,
+        "all-scroll" => CursorIcon::AllScroll,
+        "zoom-in" => CursorIcon::ZoomIn,
+        "zoom-out" => CursorIcon::ZoomOut,
+        "e-resize" => CursorIcon::EResize,
+        "n-resize" => CursorIcon::NResize,
+        "ne-resize" => CursorIcon::NeResize,
+        "nw-resize" => CursorIcon::NwResize,
+        "s-resize" => CursorIcon::SResize,
+        "se-resize" => CursorIcon::SeResize,
+        "sw-resize" => CursorIcon::SwResize,
+        "w-resize" => CursorIcon::WResize,
+        "ew-resize" => CursorIcon::EwResize,
+        "ns-resize" => CursorIcon::NsResize,
+        "nesw-resize" => CursorIcon::NeswResize,
+        "nwse-resize" => CursorIcon::NwseResize,
+        "col-resize" => CursorIcon::ColResize,
+        "row-resize" => CursorIcon::RowResize,
+        _ => return None,
+    };
+    Some(Cursor(Some(icon)))
+}
+
+const CURSOR_ATTRIBUTES: &[&str] = &sorted_str_slice!(["class", "cursor", "id"]);
@@ -0,0 +1,32 @@
+use dioxus_native_core::{
+    node_ref::{AttributeMask, NodeMask, NodeView},
+    state::NodeDepState,
+};
+
+/// The `id` and `data-*` attributes of an element, shared with the handlers
+/// of the events it is the target of. See
+/// [`WindowContext::event_target`](crate::WindowContext::event_target).
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct DataAttributes(pub Vec<(String, String)>);
+
+impl NodeDepState<()> for DataAttributes {
+    type Ctx = ();
+    // the data attributes can have any name
+    const NODE_MASK: NodeMask = NodeMask::new_with_attrs(AttributeMask::All);
+
+    fn reduce(&mut self, node: NodeView<'_>, _sibling: (), _ctx: &Self::Ctx) -> bool {
+        let new = DataAttributes(
+            node.attributes()
+                .filter(|a| a.name == "id" || a.name.starts_with("data-"))
+                .filter_map(|a| Some((a.name.to_string(), a.value.as_text()?.to_string())))
+                .collect(),
+        );
+
+        if *self != new {
+            *self = new;
+            true
+        } else {
+            false
+        }
+    }
+}
@@ -1,56 +1,61 @@
 use dioxus::core::ElementId;
-use dioxus_native_core::{
-    real_dom::RealDom,
-    utils::{ElementProduced, PersistantElementIter},
-};
+use dioxus_native_core::real_dom::RealDom;
 use dioxus_native_core_macro::sorted_str_slice;
 
 use std::{cmp::Ordering, num::NonZeroU16};
 
 use dioxus_native_core::{
     node_ref::{AttributeMask, NodeMask, NodeView},
-    real_dom::NodeType,
     state::NodeDepState,
 };
 
-use super::{NodeState, PreventDefault};
+use super::{is_attribute_set, is_disabled, is_displayed, NodeState, PreventableEvent};
 
+/// How a node takes part in the keyboard focus, set by its `tabindex`
+/// attribute like in html.
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum FocusLevel {
     Unfocusable,
+    /// `tabindex="-1"`: focused by clicks and programmatically, but skipped
+    /// by Tab.
+    Programmatic,
+    /// `tabindex="0"`, or a node with keyboard listeners: tabbed to in
+    /// document order, after the ordered nodes.
     Focusable,
+    /// A positive `tabindex`: tabbed to first, in ascending order.
     Ordered(std::num::NonZeroU16),
 }
 
 impl FocusLevel {
     pub fn focusable(&self) -> bool {
+        !matches!(self, FocusLevel::Unfocusable)
+    }
+
+    /// Returns true if Tab can move the focus to the node.
+    pub fn tabbable(&self) -> bool {
+        matches!(self, FocusLevel::Focusable | FocusLevel::Ordered(_))
+    }
+
+    // the position of the level in the tab order
+    fn rank(&self) -> (u8, u16) {
         match self {
-            FocusLevel::Unfocusable => false,
-            FocusLevel::Focusable => true,
-            FocusLevel::Ordered(_) => true,
+            FocusLevel::Unfocusable => (0, 0),
+            FocusLevel::Programmatic => (1, 0),
+            FocusLevel::Ordered(index) => (2, index.get()),
+            FocusLevel::Focusable => (3, 0),
         }
     }
 }
 
 impl PartialOrd for FocusLevel {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        match (self, other) {
-            (FocusLevel::Unfocusable, FocusLevel::Unfocusable) => Some(std::cmp::Ordering::Equal),
-            (FocusLevel::Unfocusable, FocusLevel::Focusable) => Some(std::cmp::Ordering::Less),
-            (FocusLevel::Unfocusable, FocusLevel::Ordered(_)) => Some(std::cmp::Ordering::Less),
-            (FocusLevel::Focusable, FocusLevel::Unfocusable) => Some(std::cmp::Ordering::Greater),
-            (FocusLevel::Focusable, FocusLevel::Focusable) => Some(std::cmp::Ordering::Equal),
-            (FocusLevel::Focusable, FocusLevel::Ordered(_)) => Some(std::cmp::Ordering::Greater),
-            (FocusLevel::Ordered(_), FocusLevel::Unfocusable) => Some(std::cmp::Ordering::Greater),
-            (FocusLevel::Ordered(_), FocusLevel::Focusable) => Some(std::cmp::Ordering::Less),
-            (FocusLevel::Ordered(a), FocusLevel::Ordered(b)) => a.partial_cmp(b),
-        }
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for FocusLevel {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.partial_cmp(other).unwrap()
+        self.rank().cmp(&other.rank())
     }
 }
 
@@ -63,6 +68,12 @@ impl Default for FocusLevel {
 #[derive(Clone, PartialEq, Debug, Default)]
 pub struct Focus {
     pub level: FocusLevel,
+    /// Set by the `data-focus-trap` attribute. Tab keeps the focus within the
+    /// node once it is inside, like in a modal dialog.
+    pub trap: bool,
+    /// Set by the `autofocus` attribute. The node is focused when it is
+    /// mounted, if it can be focused.
+    pub autofocus: bool,
 }
 
 impl NodeDepState<()> for Focus {
@@ -79,11 +90,11 @@ impl NodeDepState<()> for Focus {
                     .or_else(|| a.value.as_text().and_then(|v| v.parse::<i32>().ok()))
                 {
                     match index.cmp(&0) {
-                        Ordering::Less => FocusLevel::Unfocusable,
+                        Ordering::Less => FocusLevel::Programmatic,
                         Ordering::Equal => FocusLevel::Focusable,
-                        Ordering::Greater => {
-                            FocusLevel::Ordered(NonZeroU16::new(index as u16).unwrap())
-                        }
+                        Ordering::Greater => FocusLevel::Ordered(
+                            NonZeroU16::new(index.min(u16::MAX as i32) as u16).unwrap(),
+                        ),
                     }
                 } else {
                     FocusLevel::Unfocusable
@@ -97,6 +108,12 @@ impl NodeDepState<()> for Focus {
             } else {
                 FocusLevel::Unfocusable
             },
+            trap: node
+                .attributes()
+                .any(|a| a.name == "data-focus-trap" && is_attribute_set(&a.value)),
+            autofocus: node
+                .attributes()
+                .any(|a| a.name == "autofocus" && is_attribute_set(&a.value)),
         };
         if *self != new {
             *self = new;
@@ -108,13 +125,16 @@ impl NodeDepState<()> for Focus {
 }
 
 const FOCUS_EVENTS: &[&str] = &sorted_str_slice!(["keydown", "keypress", "keyup"]);
-const FOCUS_ATTRIBUTES: &[&str] = &sorted_str_slice!(["tabindex"]);
+const FOCUS_ATTRIBUTES: &[&str] = &sorted_str_slice!(["autofocus", "data-focus-trap", "tabindex"]);
 
 #[derive(Default)]
 pub struct FocusState {
-    pub(crate) focus_iter: PersistantElementIter,
     pub(crate) last_focused_id: Option<ElementId>,
-    pub(crate) focus_level: FocusLevel,
+    // the tabbable nodes with their position in the document, in tab order.
+    // Built again after the dom changes.
+    tab_order: Option<Vec<(ElementId, usize)>>,
+    // the nodes created since the last autofocus
+    mounted: Vec<ElementId>,
     pub(crate) dirty: bool,
 }
 
@@ -122,158 +142,132 @@ impl FocusState {
     /// Returns true if the focus has changed.
     pub fn progress(&mut self, rdom: &mut RealDom<NodeState>, forward: bool) -> bool {
         if let Some(last) = self.last_focused_id {
-            if rdom[last].state.prevent_default == PreventDefault::KeyDown {
+            let prevent_default = &rdom[last].state.prevent_default;
+            if prevent_default.prevents(PreventableEvent::KeyDown) {
                 return false;
             }
         }
-        // the id that started focused to track when a loop has happened
-        let mut loop_marker_id = self.last_focused_id;
-        let focus_level = &mut self.focus_level;
-        let mut next_focus = None;
 
-        loop {
-            let new = if forward {
-                self.focus_iter.next(rdom)
-            } else {
-                self.focus_iter.prev(rdom)
-            };
-            let new_id = new.id();
-            if let ElementProduced::Looped(_) = new {
-                let mut closest_level = None;
+        // a focus trap around the focused node keeps the focus inside
+        let trap = self.last_focused_id.and_then(|id| focus_trap(rdom, id));
+        let order = self.tab_order.get_or_insert_with(|| tab_order(rdom));
+        let candidates: Vec<(ElementId, usize)> = order
+            .iter()
+            .copied()
+            .filter(|(id, _)| !is_disabled(rdom, *id) && is_displayed(rdom, *id))
+            .filter(|(id, _)| trap.map_or(true, |trap| is_inclusive_ancestor(rdom, trap, *id)))
+            .collect();
+        if candidates.is_empty() {
+            return false;
+        }
 
+        let position = self
+            .last_focused_id
+            .and_then(|last| candidates.iter().position(|(id, _)| *id == last));
+        let next = match (position, self.last_focused_id) {
+            (Some(i), _) if forward => candidates[(i + 1) % candidates.len()],
+            (Some(i), _) => candidates[(i + candidates.len() - 1) % candidates.len()],
+            // from a node that Tab skips, move to the closest candidate in
+            // document order
+            (None, Some(last)) => {
+                let index = document_index(rdom, last);
                 if forward {
-                    // find the closest focusable element after the current level
-                    rdom.traverse_depth_first(|n| {
-                        let node_level = n.state.focus.level;
-                        if node_level != *focus_level
-                            && node_level.focusable()
-                            && node_level > *focus_level
-                        {
-                            if let Some(level) = &mut closest_level {
-                                if node_level < *level {
-                                    *level = node_level;
-                                }
-                            } else {
-                                closest_level = Some(node_level);
-                            }
-                        }
-                    });
+                    let after = candidates.iter().find(|(_, i)| *i > index);
+                    *after.unwrap_or(&candidates[0])
                 } else {
-                    // find the closest focusable element before the current level
-                    rdom.traverse_depth_first(|n| {
-                        let node_level = n.state.focus.level;
-                        if node_level != *focus_level
-                            && node_level.focusable()
-                            && node_level < *focus_level
-                        {
-                            if let Some(level) = &mut closest_level {
-                                if node_level > *level {
-                                    *level = node_level;
-                                }
-                            } else {
-                                closest_level = Some(node_level);
-                            }
-                        }
-                    });
-                }
-
-                // extend the loop_marker_id to allow for another pass
-                loop_marker_id = None;
-
-                if let Some(level) = closest_level {
-                    *focus_level = level;
-                } else if forward {
-                    *focus_level = FocusLevel::Unfocusable;
-                } else {
-                    *focus_level = FocusLevel::Focusable;
+                    let before = candidates.iter().rev().find(|(_, i)| *i < index);
+                    *before.unwrap_or(&candidates[candidates.len() - 1])
                 }
             }
+            (None, None) if forward => candidates[0],
+            (None, None) => candidates[candidates.len() - 1],
+        };
 
-            // once we have looked at all the elements exit the loop
-            if let Some(last) = loop_marker_id {
-                if new_id == last {
-                    break;
-                }
-            } else {
-                loop_marker_id = Some(new_id);
-            }
-
-            let current_level = rdom[new_id].state.focus.level;
-            let after_previous_focused = if forward {
-                current_level >= *focus_level
-            } else {
-                current_level <= *focus_level
-            };
-            if after_previous_focused && current_level.focusable() && current_level == *focus_level
-            {
-                next_focus = Some(new_id);
-                break;
-            }
-        }
-
-        if let Some(id) = next_focus {
-            rdom[id].state.focused = true;
-            if let Some(old) = self.last_focused_id.replace(id) {
-                rdom[old].state.focused = false;
-            }
-            // reset the position to the currently focused element
-            while self.focus_iter.next(rdom).id() != id {}
-            self.dirty = true;
-            return true;
+        if Some(next.0) == self.last_focused_id {
+            return false;
         }
-
-        false
+        self.set_focus(rdom, next.0);
+        true
     }
 
-    pub(crate) fn prune(&mut self, mutations: &dioxus::core::Mutations, rdom: &RealDom<NodeState>) {
-        fn remove_children(
-            to_prune: &mut [&mut Option<ElementId>],
-            rdom: &RealDom<NodeState>,
-            removed: ElementId,
-        ) {
-            for opt in to_prune.iter_mut() {
-                if let Some(id) = opt {
-                    if *id == removed {
-                        **opt = None;
-                    }
-                }
-            }
-            if let NodeType::Element { children, .. } = &rdom[removed].node_type {
-                for child in children {
-                    remove_children(to_prune, rdom, *child);
+    /// Forget the focused node if the mutations remove it. Returns the parent
+    /// of the removed subtree that held the focus.
+    pub(crate) fn prune(
+        &mut self,
+        mutations: &dioxus::core::Mutations,
+        rdom: &RealDom<NodeState>,
+    ) -> Option<ElementId> {
+        if !mutations.edits.is_empty() {
+            self.tab_order = None;
+        }
+        let mut lost_in = None;
+        for m in &mutations.edits {
+            let removed = match m {
+                dioxus::core::DomEdit::ReplaceWith { root, .. } => ElementId(*root as usize),
+                dioxus::core::DomEdit::Remove { root } => ElementId(*root as usize),
+                _ => continue,
+            };
+            if let Some(focused) = self.last_focused_id {
+                if is_inclusive_ancestor(rdom, removed, focused) {
+                    self.last_focused_id = None;
+                    lost_in = rdom[removed].parent;
                 }
             }
         }
-        if self.focus_iter.prune(mutations, rdom) {
-            self.dirty = true;
-        }
+        lost_in
+    }
+
+    /// Remember the nodes created by the mutations, for [`Self::autofocus`].
+    pub(crate) fn record_mounted(&mut self, mutations: &dioxus::core::Mutations) {
         for m in &mutations.edits {
             match m {
-                dioxus::core::DomEdit::ReplaceWith { root, .. } => remove_children(
-                    &mut [&mut self.last_focused_id],
-                    rdom,
-                    ElementId(*root as usize),
-                ),
-                dioxus::core::DomEdit::Remove { root } => remove_children(
-                    &mut [&mut self.last_focused_id],
-                    rdom,
-                    ElementId(*root as usize),
-                ),
+                dioxus::core::DomEdit::CreateElement { root, .. }
+                | dioxus::core::DomEdit::CreateElementNs { root, .. } => {
+                    self.mounted.push(ElementId(*root as usize))
+                }
                 _ => (),
             }
         }
     }
 
-    #[allow(unused)]
+    /// Focus the first node in document order with the `autofocus` attribute
+    /// among the nodes mounted since the last call, once their state is
+    /// updated. Like the other ways to focus, the node has to be focusable,
+    /// enabled and displayed, and not prevent its focus. Returns the focused
+    /// node.
+    pub(crate) fn autofocus(&mut self, rdom: &mut RealDom<NodeState>) -> Option<ElementId> {
+        let mounted = std::mem::take(&mut self.mounted);
+        let candidates: Vec<ElementId> = mounted
+            .into_iter()
+            .filter(|id| rdom.get(id.0).is_some())
+            .filter(|id| {
+                let state = &rdom[*id].state;
+                state.focus.autofocus
+                    && state.focus.level.focusable()
+                    && !state.prevent_default.prevents(PreventableEvent::Focus)
+            })
+            .filter(|id| !is_disabled(rdom, *id) && is_displayed(rdom, *id))
+            .collect();
+        if candidates.is_empty() {
+            return None;
+        }
+
+        let mut first = None;
+        rdom.traverse_depth_first(|n| {
+            if first.is_none() && candidates.contains(&n.id) {
+                first = Some(n.id);
+            }
+        });
+        let first = first.filter(|first| self.last_focused_id != Some(*first))?;
+        self.set_focus(rdom, first);
+        Some(first)
+    }
+
     pub(crate) fn set_focus(&mut self, rdom: &mut RealDom<NodeState>, id: ElementId) {
         if let Some(old) = self.last_focused_id.replace(id) {
             rdom[old].state.focused = false;
         }
-        let state = &mut rdom[id].state;
-        state.focused = true;
-        self.focus_level = state.focus.level;
-        // reset the position to the currently focused element
-        while self.focus_iter.next(rdom).id() != id {}
+        rdom[id].state.focused = true;
         self.dirty = true;
     }
 
@@ -283,3 +277,59 @@ impl FocusState {
         old
     }
 }
+
+// The tabbable nodes in tab order: the positive tabindexes in ascending order
+// and then the others, in document order for equal levels.
+fn tab_order(rdom: &RealDom<NodeState>) -> Vec<(ElementId, usize)> {
+    let mut order = Vec::new();
+    let mut index = 0;
+    rdom.traverse_depth_first(|n| {
+        if n.state.focus.level.tabbable() {
+            order.push((n.id, n.state.focus.level, index));
+        }
+        index += 1;
+    });
+    // the sort is stable, so the document order is kept within a level
+    order.sort_by_key(|(_, level, _)| *level);
+    order
+        .into_iter()
+        .map(|(id, _, index)| (id, index))
+        .collect()
+}
+
+// The position of a node in the document order.
+fn document_index(rdom: &RealDom<NodeState>, id: ElementId) -> usize {
+    let mut index = 0;
+    let mut found = None;
+    rdom.traverse_depth_first(|n| {
+        if n.id == id {
+            found = Some(index);
+        }
+        index += 1;
+    });
+    found.unwrap_or(index)
+}
+
+// The closest focus trap around a node, including the node itself.
+fn focus_trap(rdom: &RealDom<NodeState>, id: ElementId) -> Option<ElementId> {
+    let mut current = Some(id);
+    while let Some(id) = current {
+        let node = &rdom[id];
+        if node.state.focus.trap {
+            return Some(id);
+        }
+        current = node.parent;
+    }
+    None
+}
+
+fn is_inclusive_ancestor(rdom: &RealDom<NodeState>, ancestor: ElementId, id: ElementId) -> bool {
+    let mut current = Some(id);
+    while let Some(id) = current {
+        if id == ancestor {
+            return true;
+        }
+        current = rdom[id].parent;
+    }
+    false
+}
@@ -0,0 +1,333 @@
+use webrender::api::{ColorF, GradientStop};
+
+use crate::{style::parse_color, utils::split_outside_parens};
+
+/// A gradient painted over the background color, from the
+/// `background-image` property.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Gradient {
+    pub kind: GradientKind,
+    pub stops: Vec<ColorStop>,
+    /// Whether the stops repeat past the last one, for the `repeating-`
+    /// gradients.
+    pub repeating: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GradientKind {
+    /// `linear-gradient()`, along a line in a direction.
+    Linear(GradientDirection),
+    /// `radial-gradient()`, from the center of the box to its farthest
+    /// corner, as a circle or as an ellipse.
+    Radial { circle: bool },
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GradientDirection {
+    /// An angle in degrees, clockwise from `to top`.
+    Angle(f32),
+    /// `to` a corner, like `to top right`. The angle depends on the size of
+    /// the box.
+    Corner { right: bool, bottom: bool },
+}
+
+impl GradientDirection {
+    /// The unit vector of the gradient line in a box, with y going down.
+    pub fn vector(&self, width: f32, height: f32) -> (f32, f32) {
+        match *self {
+            GradientDirection::Angle(degrees) => {
+                let radians = degrees.to_radians();
+                (radians.sin(), -radians.cos())
+            }
+            // perpendicular to the diagonal between the two other corners
+            GradientDirection::Corner { right, bottom } => {
+                let diagonal = width.hypot(height);
+                if diagonal == 0.0 {
+                    return (0.0, 1.0);
+                }
+                let x = if right { height } else { -height };
+                let y = if bottom { width } else { -width };
+                (x / diagonal, y / diagonal)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ColorStop {
+    pub color: ColorF,
+    pub position: Option<StopPosition>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum StopPosition {
+    /// A fraction of the gradient line.
+    Fraction(f32),
+    /// A distance along the gradient line in layout units.
+    Points(f32),
+}
+
+impl Gradient {
+    /// The offsets of the stops along a gradient line of `length` layout
+    /// units, following the rules of css: the first and last stops default
+    /// to the ends of the line, a stop is never before the previous one, and
+    /// the stops without a position are spread evenly between their
+    /// neighbours.
+    pub fn resolve_stops(&self, length: f32) -> Vec<GradientStop> {
+        let mut offsets: Vec<Option<f32>> = self
+            .stops
+            .iter()
+            .map(|stop| match stop.position? {
+                StopPosition::Fraction(fraction) => Some(fraction),
+                StopPosition::Points(_) if length <= 0.0 => Some(0.0),
+                StopPosition::Points(points) => Some(points / length),
+            })
+            .collect();
+        let last = offsets.len() - 1;
+        offsets[0].get_or_insert(0.0);
+        offsets[last].get_or_insert(1.0);
+
+        let mut max = f32::MIN;
+        for offset in offsets.iter_mut().flatten() {
+            *offset = offset.max(max);
+            max = *offset;
+        }
+
+        let mut start = 0;
+        while start < last {
+            // the next stop with a position after a run without ones
+            let end = (start + 1..=last).find(|i| offsets[*i].is_some()).unwrap();
+            let (from, to) = (offsets[start].unwrap(), offsets[end].unwrap());
+            let steps = (end - start) as f32;
+            for (i, offset) in offsets.iter_mut().enumerate().take(end).skip(start + 1) {
+                *offset = Some(from + (to - from) * (i - start) as f32 / steps);
+            }
+            start = end;
+        }
+
+        self.stops
+            .iter()
+            .zip(offsets)
+            .map(|(stop, offset)| GradientStop {
+                offset: offset.unwrap(),
+                color: stop.color,
+            })
+            .collect()
+    }
+}
+
+/// Parse `linear-gradient()` and `radial-gradient()`, or their `repeating-`
+/// variants. Each stop is a color with up to two positions, in px or %.
+pub(super) fn parse_gradient(value: &str) -> Option<Gradient> {
+    let value = value.trim();
+    let open = value.find('(')?;
+    let args = split_outside_parens(value[open + 1..].strip_suffix(')')?, |c| c == ',');
+    let first = args.first()?;
+    let function = value[..open].trim();
+    let repeating = function.starts_with("repeating-");
+    let (kind, stops) = match function.trim_start_matches("repeating-") {
+        "linear-gradient" => match parse_direction(first) {
+            Some(direction) => (GradientKind::Linear(direction), &args[1..]),
+            None => (
+                GradientKind::Linear(GradientDirection::Angle(180.0)),
+                &args[..],
+            ),
+        },
+        "radial-gradient" => match parse_shape(first) {
+            Some(circle) => (GradientKind::Radial { circle }, &args[1..]),
+            None => (GradientKind::Radial { circle: false }, &args[..]),
+        },
+        _ => return None,
+    };
+
+    let mut parsed = Vec::new();
+    for stop in stops {
+        let tokens = split_outside_parens(stop, char::is_whitespace);
+        let (color, positions) = tokens.split_first()?;
+        let color = parse_color(color)?;
+        // two positions are two stops of the same color
+        let positions: Option<Vec<StopPosition>> =
+            positions.iter().map(|p| parse_position(p)).collect();
+        match positions?.as_slice() {
+            [] => parsed.push(ColorStop {
+                color,
+                position: None,
+            }),
+            [position] => parsed.push(ColorStop {
+                color,
+                position: Some(*position),
+            }),
+            [first, second] => {
+                for position in [first, second] {
+                    parsed.push(ColorStop {
+                        color,
+                        position: Some(*position),
+                    });
+                }
+            }
+            _ => return None,
+        }
+    }
+    (parsed.len() >= 2).then(|| Gradient {
+        kind,
+        stops: parsed,
+        repeating,
+    })
+}
+
+// An angle, or `to` a side or a corner.
+fn parse_direction(value: &str) -> Option<GradientDirection> {
+    if let Some(sides) = value.strip_prefix("to ") {
+        let (mut right, mut bottom) = (None, None);
+        for side in sides.split_whitespace() {
+            match side {
+                "left" if right.is_none() => right = Some(false),
+                "right" if right.is_none() => right = Some(true),
+                "top" if bottom.is_none() => bottom = Some(false),
+                "bottom" if bottom.is_none() => bottom = Some(true),
+                _ => return None,
+            }
+        }
+        return Some(match (right, bottom) {
+            (Some(right), Some(bottom)) => GradientDirection::Corner { right, bottom },
+            (Some(right), None) => GradientDirection::Angle(if right { 90.0 } else { 270.0 }),
+            (None, Some(bottom)) => GradientDirection::Angle(if bottom { 180.0 } else { 0.0 }),
+            (None, None) => return None,
+        });
+    }
+    parse_angle(value).map(GradientDirection::Angle)
+}
+
+/// Parse an angle in deg, grad, rad or turn, to degrees.
+pub(super) fn parse_angle(value: &str) -> Option<f32> {
+    let degrees = if let Some(deg) = value.strip_suffix("deg") {
+        deg.parse().ok()?
+    } else if let Some(grad) = value.strip_suffix("grad") {
+        grad.parse::<f32>().ok()? * 0.9
+    } else if let Some(rad) = value.strip_suffix("rad") {
+        rad.parse::<f32>().ok()?.to_degrees()
+    } else if let Some(turn) = value.strip_suffix("turn") {
+        turn.parse::<f32>().ok()? * 360.0
+    } else if value == "0" {
+        0.0
+    } else {
+        return None;
+    };
+    degrees.is_finite().then(|| degrees)
+}
+
+// The shape of a radial gradient, returning true for a circle. Only the
+// farthest-corner size at the center is supported.
+fn parse_shape(value: &str) -> Option<bool> {
+    let mut circle = false;
+    for token in value.split_whitespace() {
+        match token {
+            "circle" => circle = true,
+            "ellipse" | "farthest-corner" => (),
+            _ => return None,
+        }
+    }
+    Some(circle)
+}
+
+fn parse_position(value: &str) -> Option<StopPosition> {
+    let position = if let Some(percent) = value.strip_suffix('%') {
+        StopPosition::Fraction(percent.parse::<f32>().ok()? / 100.0)
+    } else if let Some(px) = value.strip_suffix("px") {
+        StopPosition::Points(px.parse().ok()?)
+    } else if value == "0" {
+        StopPosition::Points(0.0)
+    } else {
+        return None;
+    };
+    match position {
+        StopPosition::Fraction(v) | StopPosition::Points(v) if v.is_finite() => Some(position),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn offsets(value: &str, length: f32) -> Vec<f32> {
+        let gradient = parse_gradient(value).unwrap();
+        gradient
+            .resolve_stops(length)
+            .iter()
+            .map(|stop| stop.offset)
+            .collect()
+    }
+
+    fn assert_offsets(value: &str, length: f32, expected: &[f32]) {
+        let offsets = offsets(value, length);
+        assert!(
+            offsets.len() == expected.len()
+                && offsets
+                    .iter()
+                    .zip(expected)
+                    .all(|(a, b)| (a - b).abs() < 1e-5),
+            "{value}: {offsets:?} != {expected:?}"
+        );
+    }
+
+    #[test]
+    fn implicit_stops() {
+        assert_offsets("linear-gradient(red, blue)", 100.0, &[0.0, 1.0]);
+        assert_offsets(
+            "linear-gradient(red, blue, green, white)",
+            100.0,
+            &[0.0, 1.0 / 3.0, 2.0 / 3.0, 1.0],
+        );
+    }
+
+    #[test]
+    fn mixed_stops() {
+        assert_offsets(
+            "linear-gradient(red, blue, green 60%, yellow, white)",
+            100.0,
+            &[0.0, 0.3, 0.6, 0.8, 1.0],
+        );
+        assert_offsets(
+            "linear-gradient(red 10px, blue, green 50px)",
+            100.0,
+            &[0.1, 0.3, 0.5],
+        );
+        assert_offsets(
+            "linear-gradient(red 20%, blue, green, white 80%)",
+            100.0,
+            &[0.2, 0.4, 0.6, 0.8],
+        );
+        // two positions are two stops of the same color
+        assert_offsets("linear-gradient(red 0% 20%, blue)", 100.0, &[0.0, 0.2, 1.0]);
+    }
+
+    #[test]
+    fn out_of_order_stops() {
+        assert_offsets("linear-gradient(red 50%, blue 20%)", 100.0, &[0.5, 0.5]);
+        assert_offsets(
+            "linear-gradient(red, blue 20%, green, yellow 10%, white)",
+            100.0,
+            &[0.0, 0.2, 0.2, 0.2, 1.0],
+        );
+        assert_offsets(
+            "linear-gradient(red 80%, blue, green 40%)",
+            100.0,
+            &[0.8, 0.8, 0.8],
+        );
+        assert_offsets(
+            "linear-gradient(red 40% 20%, blue)",
+            100.0,
+            &[0.4, 0.4, 1.0],
+        );
+    }
+
+    #[test]
+    fn empty_line() {
+        assert_offsets(
+            "linear-gradient(red 10px, blue, green 50%)",
+            0.0,
+            &[0.0, 0.25, 0.5],
+        );
+    }
+}
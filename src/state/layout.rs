@@ -1,30 +1,106 @@
 use std::cell::RefCell;
+use std::fmt::{self, Write};
+use std::hash::{Hash, Hasher};
 use std::rc::Rc;
 
-use dioxus::core::{Attribute, ElementId};
+use dioxus::core::{Attribute, DomEdit, ElementId, Mutations};
 use dioxus_native_core::layout_attributes::apply_layout_attributes;
 use dioxus_native_core::node_ref::{AttributeMask, NodeMask};
+use dioxus_native_core::real_dom::NodeType;
+use dioxus_native_core::real_dom::RealDom;
 use dioxus_native_core::state::ChildDepState;
-use taffy::prelude::*;
+use fxhash::{FxHashMap, FxHashSet, FxHasher};
+use taffy::{node::MeasureFunc, prelude::*};
+use webrender::api::units::{LayoutPoint, LayoutRect, LayoutSize, LayoutVector2D};
+
+use super::{border::border_shorthand_width, is_attribute_set, Calc, NodeState, TextStyle};
+use crate::{
+    style::Stylesheet,
+    text::{layout_text, FontRegistry},
+};
+
+/// The font size em values in calc expressions are relative to.
+const DEFAULT_FONT_SIZE: f32 = 16.0;
+/// The maximum number of times the layout is recomputed to settle calc values.
+const MAX_CALC_PASSES: usize = 4;
+
+/// Feeds formatted text into a hasher without allocating a string.
+struct HashWriter<'a, H: Hasher>(&'a mut H);
+
+impl<H: Hasher> Write for HashWriter<'_, H> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.write(s.as_bytes());
+        Ok(())
+    }
+}
+
+/// Dimensions of a node that are calc expressions, resolved after layout.
+#[derive(Clone, Default, Debug, PartialEq)]
+pub struct CalcSize {
+    pub width: Option<Calc>,
+    pub height: Option<Calc>,
+}
+
+/// The space between the children of a flex container, from the `gap`,
+/// `row-gap` and `column-gap` styles.
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub struct Gap {
+    pub row: Dimension,
+    pub column: Dimension,
+}
+
+/// Whether `width` and `height` include the padding and border like in css.
+/// Taffy sizes are border-box, so that is the default.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BoxSizing {
+    ContentBox,
+    BorderBox,
+}
+
+impl Default for BoxSizing {
+    fn default() -> Self {
+        BoxSizing::BorderBox
+    }
+}
 
 #[derive(Clone, Default, Debug)]
 pub struct StretchLayout {
     pub style: Style,
     pub node: Option<Node>,
     pub layout: Option<Layout>,
+    pub calc: CalcSize,
+    pub box_sizing: BoxSizing,
+    pub gap: Gap,
+    /// Hash of the attributes `style` was parsed from. Used to skip parsing
+    /// when reduce is only triggered by a change in the children.
+    attributes_hash: u64,
 }
 
 impl PartialEq<Self> for StretchLayout {
     fn eq(&self, other: &Self) -> bool {
-        self.style == other.style && self.node == other.node
+        self.style == other.style
+            && self.node == other.node
+            && self.calc == other.calc
+            && self.box_sizing == other.box_sizing
+            && self.gap == other.gap
     }
 }
 
+/// The context the layout is computed with.
+pub struct LayoutContext {
+    pub stretch: Rc<RefCell<Taffy>>,
+    pub stylesheet: Rc<Stylesheet>,
+    /// The box sizing of elements without a `box-sizing` style.
+    pub box_sizing: BoxSizing,
+}
+
 impl ChildDepState for StretchLayout {
-    type Ctx = Rc<RefCell<Taffy>>;
+    type Ctx = LayoutContext;
     type DepState = Self;
 
-    const NODE_MASK: NodeMask = NodeMask::new_with_attrs(AttributeMask::All).with_text();
+    const NODE_MASK: NodeMask = NodeMask::new_with_attrs(AttributeMask::All)
+        .with_tag()
+        .with_text();
     /// Setup the layout
     fn reduce<'a>(
         &mut self,
@@ -35,20 +111,19 @@ impl ChildDepState for StretchLayout {
     where
         Self::DepState: 'a,
     {
-        let mut stretch = ctx.borrow_mut();
+        let mut stretch = ctx.stretch.borrow_mut();
         let mut changed = false;
         if let Some(text) = node.text() {
-            let char_len = text.chars().count();
+            // text nodes are sized by the measure function set in `TextMeasures`
+            let style = Style::default();
 
-            // todo: this should change with the font
-            let style = Style {
-                size: Size {
-                    height: Dimension::Points(10.0),
-
-                    width: Dimension::Points(char_len as f32 * 10.0),
-                },
-                ..Default::default()
-            };
+            let mut hasher = FxHasher::default();
+            text.hash(&mut hasher);
+            let text_hash = hasher.finish();
+            if self.attributes_hash != text_hash {
+                self.attributes_hash = text_hash;
+                changed = true;
+            }
 
             if let Some(n) = self.node {
                 if self.style != style {
@@ -65,26 +140,99 @@ impl ChildDepState for StretchLayout {
                 changed = true;
             }
         } else {
-            // gather up all the styles from the attribute list
-            let mut style = Style::default();
-
+            let mut hasher = FxHasher::default();
+            // the whole value is hashed, so attributes like `hidden: false`
+            // that aren't text still invalidate the cached style
             for Attribute { name, value, .. } in node.attributes() {
-                if let Some(value) = value.as_text() {
-                    apply_layout_attributes(name, value, &mut style);
-                }
+                name.hash(&mut hasher);
+                write!(HashWriter(&mut hasher), "{:?}", value).unwrap();
             }
+            let attributes_hash = hasher.finish();
 
-            // the root node fills the entire area
-            if node.id() == ElementId(0) {
-                apply_layout_attributes("width", "100%", &mut style);
-                apply_layout_attributes("height", "100%", &mut style);
-            }
+            // only gather up the styles from the attribute list if they changed
+            let style = if self.node.is_some()
+                && self.attributes_hash == attributes_hash
+                && style_cache_enabled()
+            {
+                self.style
+            } else {
+                let mut style = Style::default();
+                let mut calc = CalcSize::default();
+                let mut box_sizing = ctx.box_sizing;
+                let mut gap = Gap::default();
+
+                // stylesheet rules come first so inline styles override them
+                for (name, value) in ctx.stylesheet.matching(&node) {
+                    apply_declaration(
+                        name,
+                        value,
+                        &mut style,
+                        &mut calc,
+                        &mut box_sizing,
+                        &mut gap,
+                    );
+                }
+                for Attribute { name, value, .. } in node.attributes() {
+                    if let Some(value) = value.as_text() {
+                        apply_declaration(
+                            name,
+                            value,
+                            &mut style,
+                            &mut calc,
+                            &mut box_sizing,
+                            &mut gap,
+                        );
+                    }
+                }
+
+                // the hidden attribute hides the element like `display: none`,
+                // and `hidden: false` is treated as if it was not set
+                if node
+                    .attributes()
+                    .any(|a| a.name == "hidden" && is_attribute_set(&a.value))
+                {
+                    style.display = Display::None;
+                }
+
+                // taffy sizes are border-box, so content-box sizes are grown by
+                // the padding and border
+                if box_sizing == BoxSizing::ContentBox {
+                    let extra = padding_border(&style);
+                    for size in [&mut style.size, &mut style.min_size, &mut style.max_size] {
+                        size.width = grow(size.width, extra.width);
+                        size.height = grow(size.height, extra.height);
+                    }
+                }
+
+                // the root node fills the entire area
+                if node.id() == ElementId(0) {
+                    apply_layout_attributes("width", "100%", &mut style);
+                    apply_layout_attributes("height", "100%", &mut style);
+                }
+
+                if self.calc != calc {
+                    self.calc = calc;
+                    changed = true;
+                }
+                if self.box_sizing != box_sizing {
+                    self.box_sizing = box_sizing;
+                    changed = true;
+                }
+                if self.gap != gap {
+                    self.gap = gap;
+                    changed = true;
+                }
+                self.attributes_hash = attributes_hash;
+                style
+            };
 
             // Set all direct nodes as our children
+            let children: Vec<&Self> = children.collect();
             let mut child_layout = vec![];
-            for l in children {
+            for l in &children {
                 child_layout.push(l.node.unwrap());
             }
+            apply_gap(&mut stretch, &style, self.gap, &children);
 
             if let Some(n) = self.node {
                 if stretch.children(n).unwrap() != child_layout {
@@ -108,3 +256,814 @@ impl ChildDepState for StretchLayout {
         changed
     }
 }
+
+// The benchmark turns the cache off to measure what it saves
+#[cfg(test)]
+fn style_cache_enabled() -> bool {
+    !tests::NO_STYLE_CACHE.load(std::sync::atomic::Ordering::Relaxed)
+}
+
+#[cfg(not(test))]
+fn style_cache_enabled() -> bool {
+    true
+}
+
+fn apply_declaration(
+    name: &str,
+    value: &str,
+    style: &mut Style,
+    calc: &mut CalcSize,
+    box_sizing: &mut BoxSizing,
+    gap: &mut Gap,
+) {
+    if name == "box-sizing" {
+        match value {
+            "content-box" => *box_sizing = BoxSizing::ContentBox,
+            "border-box" => *box_sizing = BoxSizing::BorderBox,
+            _ => warn!("Invalid box-sizing: {}", value),
+        }
+    } else if matches!(name, "gap" | "row-gap" | "column-gap") {
+        let values: Option<Vec<Dimension>> = value.split_whitespace().map(parse_gap).collect();
+        match (name, values.as_deref()) {
+            ("gap", Some(&[both])) => {
+                *gap = Gap {
+                    row: both,
+                    column: both,
+                }
+            }
+            ("gap", Some(&[row, column])) => *gap = Gap { row, column },
+            ("row-gap", Some(&[row])) => gap.row = row,
+            ("column-gap", Some(&[column])) => gap.column = column,
+            _ => warn!("Invalid {}: {}", name, value),
+        }
+    } else if name == "display" {
+        // every element is a flex container, except the ones taffy skips
+        style.display = match value.trim() {
+            "none" => Display::None,
+            _ => Display::Flex,
+        };
+    } else if matches!(
+        name,
+        "border" | "border-top" | "border-right" | "border-bottom" | "border-left"
+    ) {
+        // the colors and styles are painted, only the widths are laid out
+        let width = Dimension::Points(border_shorthand_width(value).unwrap_or(0.0));
+        match name {
+            "border" => {
+                style.border = Rect {
+                    start: width,
+                    end: width,
+                    top: width,
+                    bottom: width,
+                }
+            }
+            "border-top" => style.border.top = width,
+            "border-right" => style.border.end = width,
+            "border-bottom" => style.border.bottom = width,
+            _ => style.border.start = width,
+        }
+    } else if name == "aspect-ratio" {
+        match parse_aspect_ratio(value) {
+            Some(ratio) => style.aspect_ratio = ratio,
+            None => warn!("Invalid aspect-ratio: {}", value),
+        }
+    } else if value.starts_with("calc(") {
+        let parsed = Calc::parse(value);
+        if parsed.is_none() {
+            warn!("Invalid calc expression for {}: {}", name, value);
+        }
+        match name {
+            "width" => calc.width = parsed,
+            "height" => calc.height = parsed,
+            _ => (),
+        }
+    } else {
+        apply_layout_attributes(name, value, style);
+    }
+}
+
+fn parse_gap(value: &str) -> Option<Dimension> {
+    if let Some(px) = value.strip_suffix("px") {
+        px.parse().ok().map(Dimension::Points)
+    } else if let Some(percent) = value.strip_suffix('%') {
+        percent
+            .parse::<f32>()
+            .ok()
+            .map(|p| Dimension::Percent(p / 100.0))
+    } else {
+        match value {
+            "0" | "normal" => Some(Dimension::Points(0.0)),
+            _ => None,
+        }
+    }
+}
+
+// Parse `auto`, a `width / height` ratio or a single number. The ratio is
+// width over height, like the taffy aspect ratio.
+fn parse_aspect_ratio(value: &str) -> Option<Number> {
+    let value = value.trim();
+    if value == "auto" {
+        return Some(Number::Undefined);
+    }
+    let (width, height) = match value.split_once('/') {
+        Some((width, height)) => (width.trim(), height.trim()),
+        None => (value, "1"),
+    };
+    let width: f32 = width.parse().ok()?;
+    let height: f32 = height.parse().ok()?;
+    let ratio = width / height;
+    (ratio.is_finite() && ratio > 0.0).then(|| Number::Defined(ratio))
+}
+
+// Taffy has no gaps, so the main axis gap of a flex container is added to
+// the margin of each child in the flow that follows another one. The gap
+// between wrapped lines is not supported.
+fn apply_gap(stretch: &mut Taffy, style: &Style, gap: Gap, children: &[&StretchLayout]) {
+    let gap = match style.flex_direction {
+        FlexDirection::Row | FlexDirection::RowReverse => gap.column,
+        FlexDirection::Column | FlexDirection::ColumnReverse => gap.row,
+    };
+    let mut first = true;
+    for child in children {
+        let node = child.node.unwrap();
+        let mut margin = child.style.margin;
+        let in_flow = child.style.display != Display::None
+            && child.style.position_type != PositionType::Absolute;
+        if in_flow && !first {
+            // the margin between a child and the previous one
+            let side = match style.flex_direction {
+                FlexDirection::Row => &mut margin.start,
+                FlexDirection::RowReverse => &mut margin.end,
+                FlexDirection::Column => &mut margin.top,
+                FlexDirection::ColumnReverse => &mut margin.bottom,
+            };
+            *side = match (*side, gap) {
+                (Dimension::Undefined, gap) => gap,
+                (Dimension::Points(m), Dimension::Points(g)) => Dimension::Points(m + g),
+                (Dimension::Percent(m), Dimension::Percent(g)) => Dimension::Percent(m + g),
+                // auto margins and mixed units can't be added up
+                (side, _) => side,
+            };
+        }
+        first &= !in_flow;
+
+        // only the margin is set, calc sizes are resolved in the taffy style
+        let mut child_style = *stretch.style(node).unwrap();
+        if child_style.margin != margin {
+            child_style.margin = margin;
+            stretch.set_style(node, child_style).unwrap();
+        }
+    }
+}
+
+/// The fixed padding and border of each axis. Percentages can't be added to
+/// the size, so they are ignored.
+fn padding_border(style: &Style) -> Size<f32> {
+    let points = |d: Dimension| match d {
+        Dimension::Points(p) => p,
+        _ => 0.0,
+    };
+    let sum = |rect: Rect<Dimension>| Size {
+        width: points(rect.start) + points(rect.end),
+        height: points(rect.top) + points(rect.bottom),
+    };
+    let (padding, border) = (sum(style.padding), sum(style.border));
+    Size {
+        width: padding.width + border.width,
+        height: padding.height + border.height,
+    }
+}
+
+fn grow(dimension: Dimension, extra: f32) -> Dimension {
+    match dimension {
+        Dimension::Points(p) => Dimension::Points(p + extra),
+        d => d,
+    }
+}
+
+/// Keeps the measure functions of the text nodes in sync with their text and style.
+pub(crate) struct TextMeasures {
+    fonts: Rc<FontRegistry>,
+    /// The text and style each text node's measure function was created with.
+    measured: Rc<RefCell<FxHashMap<ElementId, MeasuredText>>>,
+}
+
+struct MeasuredText {
+    node: Node,
+    text: String,
+    style: TextStyle,
+}
+
+impl TextMeasures {
+    pub(crate) fn new(fonts: Rc<FontRegistry>) -> Self {
+        Self {
+            fonts,
+            measured: Rc::default(),
+        }
+    }
+
+    /// A measurer of the subtrees of the taffy tree whose text nodes are
+    /// measured by this.
+    pub(crate) fn measurer(&self, stretch: &Rc<RefCell<Taffy>>) -> Measurer {
+        Measurer {
+            stretch: stretch.clone(),
+            fonts: self.fonts.clone(),
+            measured: self.measured.clone(),
+        }
+    }
+
+    /// Refresh the measure functions of the text nodes among the nodes whose
+    /// state changed, so text and font changes are seen as layout changes.
+    /// Has to be called after each state update, before the layout.
+    pub(crate) fn update(
+        &mut self,
+        rdom: &RealDom<NodeState>,
+        stretch: &mut Taffy,
+        changed: impl IntoIterator<Item = ElementId>,
+    ) {
+        let mut measured = self.measured.borrow_mut();
+        for id in changed {
+            let n = match rdom.get(id.0) {
+                Some(n) => n,
+                None => continue,
+            };
+            let (text, node) = match (&n.node_type, n.state.layout.node) {
+                (NodeType::Text { text }, Some(node)) => (text, node),
+                _ => continue,
+            };
+
+            // only the texts that changed are copied into a new measure
+            let style = &n.state.text;
+            let unchanged = measured.get(&id).map_or(false, |m| {
+                m.node == node && m.text == *text && m.style.layout_eq(style)
+            });
+            if !unchanged {
+                let measure = measure_text(self.fonts.clone(), text.clone(), style.clone());
+                stretch.set_measure(node, Some(measure)).unwrap();
+                let (text, style) = (text.clone(), style.clone());
+                measured.insert(id, MeasuredText { node, text, style });
+            }
+        }
+    }
+}
+
+fn measure_text(fonts: Rc<FontRegistry>, text: String, style: TextStyle) -> MeasureFunc {
+    // the text wraps at the width of the node when it is known
+    MeasureFunc::Boxed(Box::new(move |size| {
+        let max_width = match size.width {
+            Number::Defined(width) => Some(width),
+            Number::Undefined => None,
+        };
+        let layout = layout_text(&fonts, &text, &style, max_width);
+        Size {
+            width: layout.width(),
+            height: layout.height(),
+        }
+    }))
+}
+
+/// Lays out subtrees of the taffy tree in a scratch tree, to know their size
+/// without changing the layout of the window.
+#[derive(Clone)]
+pub(crate) struct Measurer {
+    stretch: Rc<RefCell<Taffy>>,
+    fonts: Rc<FontRegistry>,
+    measured: Rc<RefCell<FxHashMap<ElementId, MeasuredText>>>,
+}
+
+impl Measurer {
+    /// The size the subtree of a node would have in the available space.
+    pub(crate) fn measure(&self, node: Node, available: Size<Number>) -> Size<f32> {
+        let stretch = self.stretch.borrow();
+        let measured = self.measured.borrow();
+        let texts: FxHashMap<Node, &MeasuredText> =
+            measured.values().map(|m| (m.node, m)).collect();
+
+        let mut scratch = Taffy::new();
+        let root = self.copy(&stretch, &texts, &mut scratch, node);
+        scratch.compute_layout(root, available).unwrap();
+        scratch.layout(root).unwrap().size
+    }
+
+    // Copy the subtree of a node into the scratch tree. Measure functions
+    // can't be cloned, so the ones of the text nodes are created again.
+    fn copy(
+        &self,
+        stretch: &Taffy,
+        texts: &FxHashMap<Node, &MeasuredText>,
+        scratch: &mut Taffy,
+        node: Node,
+    ) -> Node {
+        let children: Vec<Node> = stretch
+            .children(node)
+            .unwrap()
+            .into_iter()
+            .map(|child| self.copy(stretch, texts, scratch, child))
+            .collect();
+        let copy = scratch
+            .new_node(*stretch.style(node).unwrap(), &children)
+            .unwrap();
+        if let Some(m) = texts.get(&node) {
+            let measure = measure_text(self.fonts.clone(), m.text.clone(), m.style.clone());
+            scratch.set_measure(copy, Some(measure)).unwrap();
+        }
+        copy
+    }
+}
+
+/// Remove the taffy nodes of the elements that the mutations remove, so the
+/// taffy tree doesn't grow with every unmounted element. Has to be called
+/// before the mutations are applied to the real dom.
+pub(crate) fn remove_layout_nodes(
+    mutations: &Mutations,
+    rdom: &RealDom<NodeState>,
+    stretch: &mut Taffy,
+    text_measures: &mut TextMeasures,
+) {
+    fn remove(
+        rdom: &RealDom<NodeState>,
+        stretch: &mut Taffy,
+        text_measures: &mut TextMeasures,
+        id: ElementId,
+    ) {
+        let n = match rdom.get(id.0) {
+            Some(n) => n,
+            None => return,
+        };
+        if let NodeType::Element { children, .. } = &n.node_type {
+            for child in children {
+                remove(rdom, stretch, text_measures, *child);
+            }
+        }
+        if let Some(node) = n.state.layout.node {
+            stretch.remove(node);
+        }
+        // the element id can be reused by a new text node
+        text_measures.measured.borrow_mut().remove(&id);
+    }
+
+    for m in &mutations.edits {
+        let root = match m {
+            DomEdit::ReplaceWith { root, .. } | DomEdit::Remove { root } => {
+                ElementId(*root as usize)
+            }
+            _ => continue,
+        };
+        let n = match rdom.get(root.0) {
+            Some(n) => n,
+            None => continue,
+        };
+
+        // detach the node first, removing it would reorder its siblings
+        let parent = n.parent.and_then(|p| rdom[p].state.layout.node);
+        if let (Some(parent), Some(node)) = (parent, n.state.layout.node) {
+            if stretch.children(parent).unwrap().contains(&node) {
+                stretch.remove_child(parent, node).unwrap();
+            }
+        }
+        remove(rdom, stretch, text_measures, root);
+    }
+}
+
+/// The deepest visible node at the point in viewport coordinates.
+pub(crate) fn hit_test(rdom: &RealDom<NodeState>, x: f32, y: f32) -> Option<ElementId> {
+    // the point is relative to the parent of the node
+    fn hit(
+        rdom: &RealDom<NodeState>,
+        id: ElementId,
+        x: f32,
+        y: f32,
+        parent_visible: bool,
+    ) -> Option<ElementId> {
+        let node = &rdom[id];
+        if node.state.layout.style.display == Display::None {
+            return None;
+        }
+        let layout = node.state.layout.layout?;
+        let (x, y) = (x - layout.location.x, y - layout.location.y);
+        let visible = node.state.visibility.0.unwrap_or(parent_visible);
+        let inside = x >= 0.0 && y >= 0.0 && x < layout.size.width && y < layout.size.height;
+
+        // children can overflow their parent unless it clips them, and later
+        // children are painted on top
+        if let NodeType::Element { children, .. } = &node.node_type {
+            if inside || !node.state.overflow.clips() {
+                let offset = node.state.scroll_offset;
+                let (x, y) = (x + offset.x, y + offset.y);
+                let hit_child = children
+                    .iter()
+                    .rev()
+                    .find_map(|c| hit(rdom, *c, x, y, visible));
+                if let Some(id) = hit_child {
+                    return Some(id);
+                }
+            }
+        }
+
+        (visible && inside).then(|| id)
+    }
+
+    hit(rdom, ElementId(rdom.root_id()), x, y, true)
+}
+
+/// The rects of the laid out nodes in viewport coordinates, with their taffy
+/// nodes.
+pub(crate) fn element_layouts(
+    rdom: &RealDom<NodeState>,
+) -> FxHashMap<ElementId, (LayoutRect, Node)> {
+    let mut rects = FxHashMap::default();
+    let mut stack = vec![(ElementId(rdom.root_id()), LayoutPoint::zero())];
+    while let Some((id, parent_origin)) = stack.pop() {
+        let node = &rdom[id];
+        let (layout, taffy_node) = match (node.state.layout.layout, node.state.layout.node) {
+            (Some(layout), Some(taffy_node)) => (layout, taffy_node),
+            _ => continue,
+        };
+        let origin = parent_origin + LayoutVector2D::new(layout.location.x, layout.location.y);
+        let size = LayoutSize::new(layout.size.width, layout.size.height);
+        let rect = LayoutRect::from_origin_and_size(origin, size);
+        rects.insert(id, (rect, taffy_node));
+        if let NodeType::Element { children, .. } = &node.node_type {
+            let children_origin = origin - node.state.scroll_offset;
+            stack.extend(children.iter().map(|c| (*c, children_origin)));
+        }
+    }
+    rects
+}
+
+/// Returns true if a node changed in a way that affects the layout since the
+/// layout was last computed. Paint-only changes, like a new background color,
+/// don't need a layout. The text measures have to be updated first.
+pub(crate) fn needs_layout(rdom: &RealDom<NodeState>, stretch: &Rc<RefCell<Taffy>>) -> bool {
+    // taffy marks the ancestors of the changed nodes dirty
+    let root = rdom[ElementId(rdom.root_id())].state.layout.node.unwrap();
+    stretch.borrow().dirty(root).unwrap()
+}
+
+/// Compute the layout of the whole tree and store the result in each node's
+/// state. The text measures have to be updated first.
+pub(crate) fn compute_layout(
+    rdom: &mut RealDom<NodeState>,
+    stretch: &Rc<RefCell<Taffy>>,
+    size: Size<Number>,
+) {
+    let root = rdom[ElementId(rdom.root_id())].state.layout.node.unwrap();
+
+    // calc values depend on the size of the parent, so layout is recomputed
+    // until they are settled
+    for _ in 0..MAX_CALC_PASSES {
+        // taffy reuses the cached layout of the clean subtrees whose size
+        // constraints didn't change, but it still visits every node to round
+        // the layout, so a layout stays linear in the size of the tree. Only
+        // copying the layouts back and resolving calc sizes are limited to
+        // the part of the tree that may have changed.
+        let dirty = dirty_nodes(rdom, &stretch.borrow());
+        stretch.borrow_mut().compute_layout(root, size).unwrap();
+        let updated = update_layouts(rdom, &stretch.borrow(), &dirty);
+
+        if !resolve_calc(rdom, &mut stretch.borrow_mut(), &updated) {
+            break;
+        }
+    }
+}
+
+// The nodes that changed since the last layout. Taffy marks the ancestors of
+// a changed node dirty, so the dirty nodes are found from the root.
+fn dirty_nodes(rdom: &RealDom<NodeState>, stretch: &Taffy) -> FxHashSet<ElementId> {
+    let mut dirty = FxHashSet::default();
+    let mut stack = vec![ElementId(rdom.root_id())];
+    while let Some(id) = stack.pop() {
+        let n = &rdom[id];
+        match n.state.layout.node {
+            Some(node) if stretch.dirty(node).unwrap() => (),
+            _ => continue,
+        }
+        dirty.insert(id);
+        if let NodeType::Element { children, .. } = &n.node_type {
+            stack.extend(children.iter().copied());
+        }
+    }
+    dirty
+}
+
+// Copy the layout of the nodes that may have moved or been resized from
+// taffy. Those are the children of the nodes that were laid out again, and
+// the descendants of the nodes whose size changed. Returns the nodes whose
+// layout was copied.
+fn update_layouts(
+    rdom: &mut RealDom<NodeState>,
+    stretch: &Taffy,
+    dirty: &FxHashSet<ElementId>,
+) -> Vec<ElementId> {
+    let mut updated = Vec::new();
+    let mut stack = vec![ElementId(rdom.root_id())];
+    while let Some(id) = stack.pop() {
+        let n = &mut rdom[id];
+        let node = match n.state.layout.node {
+            Some(node) => node,
+            None => continue,
+        };
+        let layout = *stretch.layout(node).unwrap();
+        let old = n.state.layout.layout.replace(layout);
+        updated.push(id);
+        let resized = old.map_or(true, |old| old.size != layout.size);
+        if dirty.contains(&id) || resized {
+            if let NodeType::Element { children, .. } = &n.node_type {
+                stack.extend(children.iter().copied());
+            }
+        }
+    }
+    updated
+}
+
+/// Resolve the calc dimensions of the `updated` nodes against the current
+/// layout of each parent. A calc size only changes when its node changed or
+/// its parent was resized, and both have their layout copied again. Returns
+/// true if any taffy style changed.
+fn resolve_calc(rdom: &RealDom<NodeState>, stretch: &mut Taffy, updated: &[ElementId]) -> bool {
+    let mut changed = false;
+    for &id in updated {
+        let n = &rdom[id];
+        let calc = &n.state.layout.calc;
+        if calc.width.is_none() && calc.height.is_none() {
+            continue;
+        }
+        let node = match n.state.layout.node {
+            Some(node) => node,
+            None => continue,
+        };
+        let parent_size = n
+            .parent
+            .and_then(|p| rdom[p].state.layout.layout)
+            .map(|l| l.size)
+            .unwrap_or(Size {
+                width: 0.0,
+                height: 0.0,
+            });
+
+        let old = *stretch.style(node).unwrap();
+        let mut style = old;
+        let extra = match n.state.layout.box_sizing {
+            BoxSizing::ContentBox => padding_border(&style),
+            BoxSizing::BorderBox => Size {
+                width: 0.0,
+                height: 0.0,
+            },
+        };
+        if let Some(calc) = &n.state.layout.calc.width {
+            style.size.width =
+                Dimension::Points(calc.resolve(parent_size.width, DEFAULT_FONT_SIZE) + extra.width);
+        }
+        if let Some(calc) = &n.state.layout.calc.height {
+            style.size.height = Dimension::Points(
+                calc.resolve(parent_size.height, DEFAULT_FONT_SIZE) + extra.height,
+            );
+        }
+        if style != old {
+            stretch.set_style(node, style).unwrap();
+            changed = true;
+        }
+    }
+    changed
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::time::Instant;
+
+    use dioxus::prelude::*;
+    use glutin::dpi::PhysicalSize;
+    use taffy::prelude::{Node, Number, Size};
+    use webrender::api::IdNamespace;
+
+    use super::*;
+    use crate::{
+        context::WindowContext,
+        state::BoxSizing,
+        window::{available_space, state_context},
+    };
+
+    const WIDTH: f32 = 800.0;
+    const HEIGHT: f32 = 600.0;
+
+    /// The real dom of a root component, updated like a window task updates
+    /// it but without a window.
+    pub(crate) struct TestDom {
+        vdom: VirtualDom,
+        pub(crate) rdom: RealDom<NodeState>,
+        pub(crate) fonts: Rc<FontRegistry>,
+        stretch: Rc<RefCell<Taffy>>,
+        stylesheet: Rc<Stylesheet>,
+        text_measures: TextMeasures,
+        size: Size<Number>,
+    }
+
+    impl TestDom {
+        pub(crate) fn new(root: Component<()>) -> Self {
+            let stretch = Rc::new(RefCell::new(Taffy::new()));
+            let fonts = Rc::new(FontRegistry::new(IdNamespace(0)));
+            let text_measures = TextMeasures::new(fonts.clone());
+
+            let mut vdom = VirtualDom::new(root);
+            let context = WindowContext::new(
+                LayoutSize::new(WIDTH, HEIGHT),
+                text_measures.measurer(&stretch),
+            );
+            vdom.base_scope().provide_context(context);
+
+            let mut dom = Self {
+                vdom,
+                rdom: RealDom::new(),
+                fonts,
+                stretch,
+                stylesheet: Rc::default(),
+                text_measures,
+                size: Size {
+                    width: Number::Defined(WIDTH),
+                    height: Number::Defined(HEIGHT),
+                },
+            };
+            let mutations = dom.vdom.rebuild();
+            let to_update = dom.rdom.apply_mutations(vec![mutations]);
+            dom.update_state(to_update);
+            dom
+        }
+
+        /// Render the root component again and apply its mutations.
+        fn rerender(&mut self) {
+            self.vdom.base_scope().needs_update();
+            self.vdom.process_all_messages();
+            let mutations = self.vdom.work_with_deadline(|| false);
+            for m in &mutations {
+                remove_layout_nodes(
+                    m,
+                    &self.rdom,
+                    &mut self.stretch.borrow_mut(),
+                    &mut self.text_measures,
+                );
+            }
+            let to_update = self.rdom.apply_mutations(mutations);
+            self.update_state(to_update);
+        }
+
+        fn update_state(&mut self, to_update: Vec<(usize, NodeMask)>) {
+            let ctx = state_context(&self.stretch, &self.stylesheet, BoxSizing::ContentBox);
+            let changed = self.rdom.update_state(&self.vdom, to_update, ctx);
+            self.text_measures.update(
+                &self.rdom,
+                &mut self.stretch.borrow_mut(),
+                changed.iter().copied(),
+            );
+            compute_layout(&mut self.rdom, &self.stretch, self.size);
+        }
+
+        /// Lay the dom out again for a window of `size` physical pixels.
+        fn resize(&mut self, size: PhysicalSize<u32>, scale_factor: f64) {
+            self.size = available_space(size, scale_factor);
+            compute_layout(&mut self.rdom, &self.stretch, self.size);
+        }
+
+        // The layouts of the elements, in depth first order
+        fn layouts(&self) -> Vec<Layout> {
+            let mut layouts = Vec::new();
+            self.rdom.traverse_depth_first(|n| {
+                if let NodeType::Element { .. } = n.node_type {
+                    layouts.extend(n.state.layout.layout);
+                }
+            });
+            layouts
+        }
+    }
+
+    fn sized_boxes(cx: Scope) -> Element {
+        cx.render(rsx! {
+            div {
+                width: "100px",
+                height: "50px",
+                margin_left: "10px",
+                div { width: "50%", height: "20px" }
+            }
+        })
+    }
+
+    // The layout is in logical pixels, so a window twice as large on a 2x
+    // display has the same layout as at 1x
+    #[test]
+    fn layout_in_logical_pixels() {
+        let mut dom = TestDom::new(sized_boxes);
+        dom.resize(PhysicalSize::new(1600, 1200), 2.0);
+
+        let layouts = dom.layouts();
+        assert_eq!(layouts.len(), 3);
+        let sizes: Vec<(f32, f32)> = layouts
+            .iter()
+            .map(|l| (l.size.width, l.size.height))
+            .collect();
+        assert_eq!(sizes, [(800.0, 600.0), (100.0, 50.0), (50.0, 20.0)]);
+        assert_eq!(layouts[1].location.x, 10.0);
+    }
+
+    static GENERATION: AtomicUsize = AtomicUsize::new(0);
+
+    // 500 styled cells with a text node each
+    fn cells(cx: Scope) -> Element {
+        let generation = GENERATION.load(Ordering::Relaxed);
+        cx.render(rsx! {
+            div {
+                flex_wrap: "wrap",
+                (0..500).map(|i| rsx! {
+                    div {
+                        key: "{i}",
+                        width: "calc(10% - 4px)",
+                        padding: "1px",
+                        border: "1px solid black",
+                        "{generation}"
+                    }
+                })
+            }
+        })
+    }
+
+    pub(super) static NO_STYLE_CACHE: AtomicBool = AtomicBool::new(false);
+
+    // Every text changes each frame, so every cell is reduced again with
+    // the same attributes and the cached style is reused. The frames are
+    // timed again with the cache off to compare. Run with
+    // `cargo test --release relayout_1000_nodes -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn relayout_1000_nodes() {
+        const FRAMES: u32 = 100;
+
+        for cache in [true, false] {
+            NO_STYLE_CACHE.store(!cache, Ordering::Relaxed);
+
+            let start = Instant::now();
+            let mut dom = TestDom::new(cells);
+            let first = start.elapsed();
+
+            let start = Instant::now();
+            for _ in 0..FRAMES {
+                GENERATION.fetch_add(1, Ordering::Relaxed);
+                dom.rerender();
+            }
+            println!(
+                "style cache {}: first layout: {:?}, relayout: {:?} per frame",
+                if cache { "on" } else { "off" },
+                first,
+                start.elapsed() / FRAMES
+            );
+        }
+        NO_STYLE_CACHE.store(false, Ordering::Relaxed);
+    }
+
+    static ROWS: AtomicUsize = AtomicUsize::new(0);
+
+    fn rows(cx: Scope) -> Element {
+        let rows = ROWS.load(Ordering::Relaxed);
+        cx.render(rsx! {
+            div {
+                (0..rows).map(|i| rsx! {
+                    div { key: "{i}", height: "10px", "row {i}" }
+                })
+            }
+        })
+    }
+
+    impl TestDom {
+        fn layout_nodes(&self) -> Vec<Node> {
+            let mut nodes = Vec::new();
+            self.rdom
+                .traverse_depth_first(|n| nodes.extend(n.state.layout.node));
+            nodes
+        }
+    }
+
+    // Mounting and unmounting 10k nodes must not leave their taffy nodes or
+    // text measures behind.
+    #[test]
+    fn churn_frees_layout_nodes() {
+        ROWS.store(0, Ordering::Relaxed);
+        let mut dom = TestDom::new(rows);
+        let empty = dom.layout_nodes();
+
+        let mut unmounted = Vec::new();
+        for _ in 0..10 {
+            ROWS.store(5_000, Ordering::Relaxed);
+            dom.rerender();
+            let mounted = dom.layout_nodes();
+            assert_eq!(mounted.len(), empty.len() + 10_000);
+            assert_eq!(dom.text_measures.measured.borrow().len(), 5_000);
+
+            ROWS.store(0, Ordering::Relaxed);
+            dom.rerender();
+            assert_eq!(dom.layout_nodes(), empty);
+            assert!(dom.text_measures.measured.borrow().is_empty());
+
+            unmounted.extend(mounted.into_iter().filter(|n| !empty.contains(n)));
+            let stretch = dom.stretch.borrow();
+            assert!(unmounted.iter().all(|&n| stretch.layout(n).is_err()));
+        }
+    }
+}
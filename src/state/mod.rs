@@ -1,30 +1,134 @@
+use std::{borrow::Cow, time::Instant};
+
 use dioxus::core as dioxus_core;
+use dioxus::core::{AttributeValue, ElementId};
 use dioxus_native_core;
+use dioxus_native_core::real_dom::RealDom;
 use dioxus_native_core::state::*;
 use dioxus_native_core_macro::{sorted_str_slice, State};
+use glutin::window::CursorIcon;
+use webrender::api::units::{LayoutPoint, LayoutVector2D};
+
+use crate::style::PseudoClass;
 
+mod animation;
+pub use animation::{AnimationDirection, AnimationState, KeyframeAnimation};
+mod border;
+pub use border::BorderSide;
+mod calc;
+pub use calc::Calc;
+mod cursor;
+pub use cursor::Cursor;
+mod data;
+pub use data::DataAttributes;
 mod layout;
-pub use layout::StretchLayout;
+#[cfg(test)]
+pub(crate) use layout::tests::TestDom;
+pub(crate) use layout::{
+    compute_layout, element_layouts, hit_test, needs_layout, remove_layout_nodes, Measurer,
+    TextMeasures,
+};
+pub use layout::{BoxSizing, LayoutContext, StretchLayout};
 mod focus;
 pub use focus::{Focus, FocusLevel, FocusState};
+mod gradient;
+pub use gradient::{ColorStop, Gradient, GradientDirection, GradientKind, StopPosition};
+mod scroll;
+pub use scroll::Overflow;
+pub(crate) use scroll::{max_scroll_offset, scroll_by, scroll_into_view, scroll_to};
+mod style;
+pub use style::PaintStyle;
+mod text;
+pub use text::{Direction, FontStyle, LineHeight, TextAlign, TextStyle, WhiteSpace};
+mod transform;
+pub use transform::{Length, Transform, TransformFunction, TransformOrigin};
+mod transition;
+pub use transition::{Easing, TimingFunction, Transition, TransitionProperty, TransitionState};
+mod visibility;
+pub use visibility::Visibility;
 
 #[derive(Clone, PartialEq, Default, State, Debug)]
 pub struct NodeState {
     // #[node_dep_state()]
     // pub(crate) mouse_effected: crate::mouse::MouseEffected,
-    #[child_dep_state(layout, Rc<RefCell<Stretch>>)]
+    #[child_dep_state(layout, LayoutContext)]
     pub layout: StretchLayout,
-    // #[state]
-    // pub style: crate::style::Style,
+    #[node_dep_state()]
+    pub style: PaintStyle,
+    #[parent_dep_state(text)]
+    pub text: TextStyle,
     #[node_dep_state()]
     pub focus: Focus,
     pub focused: bool,
+    /// Set when the cursor is over the node or one of its descendants.
+    pub hovered: bool,
+    /// Set while the node or one of its descendants is pressed.
+    pub active: bool,
+    pub transition: TransitionState,
+    pub animation: AnimationState,
     #[node_dep_state()]
     pub prevent_default: PreventDefault,
+    #[node_dep_state()]
+    pub disabled: Disabled,
+    #[node_dep_state()]
+    pub cursor: Cursor,
+    #[node_dep_state()]
+    pub visibility: Visibility,
+    #[node_dep_state()]
+    pub overflow: Overflow,
+    #[node_dep_state()]
+    pub data: DataAttributes,
+    /// How far a scroll container is scrolled.
+    pub scroll_offset: LayoutVector2D,
+}
+
+impl NodeState {
+    /// The paint style of the node in its current hover and active state,
+    /// with the current values of its animations and running transitions.
+    pub fn paint_style(&self) -> Cow<'_, PaintStyle> {
+        let style = self.style.resolve(self.hovered, self.active);
+        if self.transition.is_running() || self.animation.has_values() {
+            let mut style = style.clone();
+            // transitions override animations like in the css cascade
+            self.animation.apply(&mut style);
+            self.transition.apply(&mut style);
+            Cow::Owned(style)
+        } else {
+            Cow::Borrowed(style)
+        }
+    }
+
+    /// Transition the properties whose target changed. Returns true if the
+    /// node has transitions or animations to tick.
+    pub(crate) fn start_animations(&mut self, now: Instant) -> bool {
+        // the transitions and animations of the base style apply in every state
+        let target = self.style.resolve(self.hovered, self.active);
+        let transitions = self
+            .transition
+            .retarget(target, &self.style.transitions, now);
+        transitions || !self.style.animations.is_empty() || self.animation.is_active()
+    }
+
+    /// Advance the transitions and animations of the node. Returns true if
+    /// any of them needs more frames.
+    pub(crate) fn tick(&mut self, now: Instant) -> bool {
+        let transitions = self.transition.tick(now);
+        let underlying = self.style.resolve(self.hovered, self.active);
+        let animations = self.animation.tick(&self.style.animations, underlying, now);
+        transitions || animations
+    }
+
+    fn pseudo_class_mut(&mut self, pseudo_class: PseudoClass) -> &mut bool {
+        match pseudo_class {
+            PseudoClass::Hover => &mut self.hovered,
+            PseudoClass::Active => &mut self.active,
+        }
+    }
 }
 
-#[derive(PartialEq, Debug, Clone)]
-pub enum PreventDefault {
+/// An event with a default behavior that can be prevented.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PreventableEvent {
     Focus,
     KeyPress,
     KeyRelease,
@@ -35,16 +139,47 @@ pub enum PreventDefault {
     MouseEnter,
     MouseLeave,
     MouseOut,
-    Unknown,
     MouseOver,
     ContextMenu,
     Wheel,
     MouseUp,
+    TouchMove,
+    TouchEnd,
 }
 
-impl Default for PreventDefault {
-    fn default() -> Self {
-        PreventDefault::Unknown
+impl PreventableEvent {
+    fn parse(listener: &str) -> Option<Self> {
+        Some(match listener {
+            "onfocus" => PreventableEvent::Focus,
+            "onkeypress" => PreventableEvent::KeyPress,
+            "onkeyrelease" => PreventableEvent::KeyRelease,
+            "onkeydown" => PreventableEvent::KeyDown,
+            "onkeyup" => PreventableEvent::KeyUp,
+            "onclick" => PreventableEvent::Click,
+            "onmousedown" => PreventableEvent::MouseDown,
+            "onmouseup" => PreventableEvent::MouseUp,
+            "onmouseenter" => PreventableEvent::MouseEnter,
+            "onmouseover" => PreventableEvent::MouseOver,
+            "onmouseleave" => PreventableEvent::MouseLeave,
+            "onmouseout" => PreventableEvent::MouseOut,
+            "onwheel" => PreventableEvent::Wheel,
+            "oncontextmenu" => PreventableEvent::ContextMenu,
+            "ontouchmove" => PreventableEvent::TouchMove,
+            "ontouchend" => PreventableEvent::TouchEnd,
+            _ => return None,
+        })
+    }
+}
+
+/// The events whose default behavior is prevented on the node, from the
+/// space separated `dioxus-prevent-default` attribute like
+/// `"onclick onkeydown"`.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct PreventDefault(Vec<PreventableEvent>);
+
+impl PreventDefault {
+    pub fn prevents(&self, event: PreventableEvent) -> bool {
+        self.0.contains(&event)
     }
 }
 
@@ -64,27 +199,51 @@ impl NodeDepState<()> for PreventDefault {
         _sibling: (),
         _ctx: &Self::Ctx,
     ) -> bool {
-        let new = match node
+        let mut new = PreventDefault::default();
+        let value = node
             .attributes()
             .find(|a| a.name == "dioxus-prevent-default")
-            .and_then(|a| a.value.as_text())
-        {
-            Some("onfocus") => PreventDefault::Focus,
-            Some("onkeypress") => PreventDefault::KeyPress,
-            Some("onkeyrelease") => PreventDefault::KeyRelease,
-            Some("onkeydown") => PreventDefault::KeyDown,
-            Some("onkeyup") => PreventDefault::KeyUp,
-            Some("onclick") => PreventDefault::Click,
-            Some("onmousedown") => PreventDefault::MouseDown,
-            Some("onmouseup") => PreventDefault::MouseUp,
-            Some("onmouseenter") => PreventDefault::MouseEnter,
-            Some("onmouseover") => PreventDefault::MouseOver,
-            Some("onmouseleave") => PreventDefault::MouseLeave,
-            Some("onmouseout") => PreventDefault::MouseOut,
-            Some("onwheel") => PreventDefault::Wheel,
-            Some("oncontextmenu") => PreventDefault::ContextMenu,
-            _ => return false,
-        };
+            .and_then(|a| a.value.as_text());
+        for listener in value.unwrap_or_default().split_whitespace() {
+            match PreventableEvent::parse(listener) {
+                Some(event) if !new.prevents(event) => new.0.push(event),
+                Some(_) => (),
+                None => warn!("Invalid dioxus-prevent-default: {}", listener),
+            }
+        }
+        if new == *self {
+            false
+        } else {
+            *self = new;
+            true
+        }
+    }
+}
+
+/// Set by the `disabled` attribute. Disabled elements and their descendants
+/// don't receive events or focus.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Disabled(pub bool);
+
+impl NodeDepState<()> for Disabled {
+    type Ctx = ();
+
+    const NODE_MASK: dioxus_native_core::node_ref::NodeMask =
+        dioxus_native_core::node_ref::NodeMask::new_with_attrs(
+            dioxus_native_core::node_ref::AttributeMask::Static(&sorted_str_slice!(["disabled"])),
+        );
+
+    fn reduce(
+        &mut self,
+        node: dioxus_native_core::node_ref::NodeView,
+        _sibling: (),
+        _ctx: &Self::Ctx,
+    ) -> bool {
+        // `disabled: false` is treated as if the attribute was not set
+        let new = Disabled(
+            node.attributes()
+                .any(|a| a.name == "disabled" && is_attribute_set(&a.value)),
+        );
         if new == *self {
             false
         } else {
@@ -93,3 +252,110 @@ impl NodeDepState<()> for PreventDefault {
         }
     }
 }
+
+/// Returns true if a boolean attribute like `hidden` is set: a bool value
+/// sets it when true, and a text value unless it is `"false"`.
+pub(crate) fn is_attribute_set(value: &AttributeValue) -> bool {
+    match value {
+        AttributeValue::Bool(set) => *set,
+        AttributeValue::Text(text) => *text != "false",
+        _ => false,
+    }
+}
+
+/// Returns true if the node or one of its ancestors is disabled.
+pub(crate) fn is_disabled(rdom: &RealDom<NodeState>, id: ElementId) -> bool {
+    let mut current = Some(id);
+    while let Some(id) = current {
+        let node = &rdom[id];
+        if node.state.disabled.0 {
+            return true;
+        }
+        current = node.parent;
+    }
+    false
+}
+
+/// Returns true if the node and its ancestors are displayed, so the node is
+/// laid out and painted.
+pub(crate) fn is_displayed(rdom: &RealDom<NodeState>, id: ElementId) -> bool {
+    let mut current = Some(id);
+    while let Some(id) = current {
+        let node = &rdom[id];
+        if node.state.layout.style.display == taffy::style::Display::None {
+            return false;
+        }
+        current = node.parent;
+    }
+    true
+}
+
+/// The node or its closest ancestor that can be focused.
+pub(crate) fn focusable_ancestor(rdom: &RealDom<NodeState>, id: ElementId) -> Option<ElementId> {
+    let mut current = Some(id);
+    while let Some(id) = current {
+        let node = &rdom[id];
+        if node.state.focus.level.focusable() {
+            return Some(id);
+        }
+        current = node.parent;
+    }
+    None
+}
+
+/// The node that pressing a point in viewport coordinates would focus: the
+/// node under the point or its closest focusable ancestor. None over a
+/// disabled node or one that can't be focused, so it also tells whether the
+/// point is over something interactive, like to show a pointer cursor.
+pub(crate) fn focusable_at(rdom: &RealDom<NodeState>, point: LayoutPoint) -> Option<ElementId> {
+    let hit = hit_test(rdom, point.x, point.y).filter(|id| !is_disabled(rdom, *id))?;
+    focusable_ancestor(rdom, hit)
+}
+
+/// The cursor of the node, inherited from the closest ancestor that sets one.
+pub(crate) fn cursor_icon(rdom: &RealDom<NodeState>, id: ElementId) -> CursorIcon {
+    let mut current = Some(id);
+    while let Some(id) = current {
+        let node = &rdom[id];
+        if let Some(icon) = node.state.cursor.0 {
+            return icon;
+        }
+        current = node.parent;
+    }
+    CursorIcon::Default
+}
+
+/// A node and its ancestors, from the deepest.
+pub(crate) fn ancestor_chain(rdom: &RealDom<NodeState>, id: Option<ElementId>) -> Vec<ElementId> {
+    let mut chain = Vec::new();
+    let mut current = id;
+    while let Some(id) = current {
+        chain.push(id);
+        current = rdom[id].parent;
+    }
+    chain
+}
+
+/// Move a pseudo class from the nodes of the `old` chain to the nodes of the
+/// `new` one. Only the nodes of the two chains are visited, however large the
+/// tree is. Returns the nodes whose flag changed.
+pub(crate) fn update_pseudo_class(
+    rdom: &mut RealDom<NodeState>,
+    pseudo_class: PseudoClass,
+    old: &[ElementId],
+    new: &[ElementId],
+) -> Vec<ElementId> {
+    let mut changed = Vec::new();
+    for &id in old.iter().chain(new) {
+        // the nodes of the old chain may have been removed since
+        if let Some(n) = rdom.get_mut(id.0) {
+            let set = new.contains(&id);
+            let flag = n.state.pseudo_class_mut(pseudo_class);
+            if *flag != set {
+                *flag = set;
+                changed.push(id);
+            }
+        }
+    }
+    changed
+}
@@ -0,0 +1,183 @@
+use std::rc::Rc;
+
+use dioxus::core::ElementId;
+use dioxus_native_core::{
+    node_ref::{AttributeMask, NodeMask, NodeView},
+    real_dom::{NodeType, RealDom},
+    state::NodeDepState,
+};
+use dioxus_native_core_macro::sorted_str_slice;
+use webrender::api::units::{LayoutRect, LayoutSize, LayoutVector2D};
+
+use super::NodeState;
+use crate::style::Stylesheet;
+
+/// What a node does with the children that overflow it, set by the
+/// `overflow` style.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Overflow {
+    Visible,
+    Hidden,
+    /// Like hidden, but can't be scrolled.
+    Clip,
+    Scroll,
+    Auto,
+}
+
+impl Default for Overflow {
+    fn default() -> Self {
+        Overflow::Visible
+    }
+}
+
+impl Overflow {
+    pub fn clips(&self) -> bool {
+        *self != Overflow::Visible
+    }
+
+    pub fn is_scroll_container(&self) -> bool {
+        self.clips() && *self != Overflow::Clip
+    }
+}
+
+impl NodeDepState<()> for Overflow {
+    type Ctx = Rc<Stylesheet>;
+    const NODE_MASK: NodeMask =
+        NodeMask::new_with_attrs(AttributeMask::Static(OVERFLOW_ATTRIBUTES)).with_tag();
+
+    fn reduce(&mut self, node: NodeView<'_>, _sibling: (), stylesheet: &Self::Ctx) -> bool {
+        let mut new = Overflow::default();
+        // stylesheet rules come first so inline styles override them
+        let inline = node
+            .attributes()
+            .filter_map(|a| Some((a.name, a.value.as_text()?)));
+        for (name, value) in stylesheet.matching(&node).into_iter().chain(inline) {
+            if name == "overflow" {
+                match value.trim() {
+                    "visible" => new = Overflow::Visible,
+                    "hidden" => new = Overflow::Hidden,
+                    "clip" => new = Overflow::Clip,
+                    "scroll" => new = Overflow::Scroll,
+                    "auto" => new = Overflow::Auto,
+                    _ => warn!("Invalid overflow: {}", value),
+                }
+            }
+        }
+
+        if *self != new {
+            *self = new;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+const OVERFLOW_ATTRIBUTES: &[&str] = &sorted_str_slice!(["class", "id", "overflow"]);
+
+/// The largest offset a scroll container can be scrolled to, where the far
+/// edges of its children reach its own far edges.
+pub(crate) fn max_scroll_offset(rdom: &RealDom<NodeState>, id: ElementId) -> LayoutVector2D {
+    let node = &rdom[id];
+    let layout = match node.state.layout.layout {
+        Some(layout) => layout,
+        None => return LayoutVector2D::zero(),
+    };
+    let mut extent = LayoutSize::zero();
+    if let NodeType::Element { children, .. } = &node.node_type {
+        for child in children.iter().filter_map(|c| rdom[*c].state.layout.layout) {
+            extent.width = extent.width.max(child.location.x + child.size.width);
+            extent.height = extent.height.max(child.location.y + child.size.height);
+        }
+    }
+    LayoutVector2D::new(
+        (extent.width - layout.size.width).max(0.0),
+        (extent.height - layout.size.height).max(0.0),
+    )
+}
+
+/// Scroll a scroll container to an offset, clamped to its content. Returns
+/// true if the offset changed.
+pub(crate) fn scroll_to(
+    rdom: &mut RealDom<NodeState>,
+    id: ElementId,
+    offset: LayoutVector2D,
+) -> bool {
+    if !rdom[id].state.overflow.is_scroll_container() {
+        return false;
+    }
+    let max = max_scroll_offset(rdom, id);
+    let offset = LayoutVector2D::new(offset.x.clamp(0.0, max.x), offset.y.clamp(0.0, max.y));
+    let state = &mut rdom[id].state;
+    if state.scroll_offset != offset {
+        state.scroll_offset = offset;
+        true
+    } else {
+        false
+    }
+}
+
+/// Scroll the innermost scroll container around a node, the node included,
+/// that can still scroll by `delta`, like a mouse wheel does. The containers
+/// with hidden overflow only scroll programmatically. Returns the container
+/// that scrolled.
+pub(crate) fn scroll_by(
+    rdom: &mut RealDom<NodeState>,
+    id: ElementId,
+    delta: LayoutVector2D,
+) -> Option<ElementId> {
+    let mut current = Some(id);
+    while let Some(node) = current {
+        let state = &rdom[node].state;
+        if matches!(state.overflow, Overflow::Scroll | Overflow::Auto) {
+            let offset = state.scroll_offset + delta;
+            if scroll_to(rdom, node, offset) {
+                return Some(node);
+            }
+        }
+        current = rdom[node].parent;
+    }
+    None
+}
+
+/// Scroll the scroll containers around a node as little as possible so the
+/// node is visible, the innermost first. Returns the containers that scrolled.
+pub(crate) fn scroll_into_view(rdom: &mut RealDom<NodeState>, id: ElementId) -> Vec<ElementId> {
+    let mut scrolled = Vec::new();
+    let size = match rdom[id].state.layout.layout {
+        Some(layout) => LayoutSize::new(layout.size.width, layout.size.height),
+        None => return scrolled,
+    };
+    // the rect of the node relative to the box of `current`
+    let mut rect = LayoutRect::from_size(size);
+    let mut current = id;
+    while let Some(parent) = rdom[current].parent {
+        if let Some(layout) = rdom[current].state.layout.layout {
+            rect = rect.translate(LayoutVector2D::new(layout.location.x, layout.location.y));
+        }
+
+        let container = &rdom[parent].state;
+        if let (true, Some(layout)) = (
+            container.overflow.is_scroll_container(),
+            container.layout.layout,
+        ) {
+            // bring the far edges into view first so the near edges win if
+            // the node is larger than the container
+            let mut offset = container.scroll_offset;
+            if rect.max.x > offset.x + layout.size.width {
+                offset.x = rect.max.x - layout.size.width;
+            }
+            if rect.max.y > offset.y + layout.size.height {
+                offset.y = rect.max.y - layout.size.height;
+            }
+            offset.x = offset.x.min(rect.min.x);
+            offset.y = offset.y.min(rect.min.y);
+            if scroll_to(rdom, parent, offset) {
+                scrolled.push(parent);
+            }
+            rect = rect.translate(-rdom[parent].state.scroll_offset);
+        }
+        current = parent;
+    }
+    scrolled
+}
@@ -0,0 +1,229 @@
+use std::rc::Rc;
+
+use dioxus_native_core::{
+    node_ref::{AttributeMask, NodeMask, NodeView},
+    state::NodeDepState,
+};
+use dioxus_native_core_macro::sorted_str_slice;
+use webrender::api::{BorderRadius, ColorF};
+
+use super::{
+    animation::{apply_play_state, parse_animations, KeyframeAnimation},
+    border::{apply_border, corner_mut, parse_border_radius, parse_corner_radius, BorderSide},
+    gradient::{parse_gradient, Gradient},
+    transform::{parse_transform, parse_transform_origin, Transform},
+    transition::{parse_transitions, Transition},
+};
+use crate::style::{parse_color, PseudoClass, Stylesheet};
+
+/// Styles that only affect how a node is painted.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct PaintStyle {
+    pub background_color: Option<ColorF>,
+    /// The gradient painted over the background color.
+    pub background_image: Option<Gradient>,
+    pub opacity: Option<f32>,
+    /// The radii of the corners in layout units, from `border-radius` and its
+    /// corner longhands. The background and the clip of the node are rounded.
+    pub border_radius: Option<BorderRadius>,
+    /// The colors and styles of the top, right, bottom and left borders.
+    pub border: [BorderSide; 4],
+    pub transform: Transform,
+    /// The transitions of the properties, from the `transition` property.
+    pub transitions: Vec<Transition>,
+    /// The keyframe animations, from the `animation` property.
+    pub animations: Vec<KeyframeAnimation>,
+    /// The style while the node is hovered, if it differs. Set by `:hover`
+    /// rules and `hover:` prefixed attributes like `hover:background-color`.
+    pub hover: Option<Box<PaintStyle>>,
+    /// The style while the node is pressed, if it differs. Takes precedence
+    /// over the hover style.
+    pub active: Option<Box<PaintStyle>>,
+}
+
+impl PaintStyle {
+    /// The style to paint the node with in its current state.
+    pub fn resolve(&self, hovered: bool, active: bool) -> &PaintStyle {
+        match (&self.hover, &self.active) {
+            (_, Some(style)) if active => style,
+            (Some(style), _) if hovered => style,
+            _ => self,
+        }
+    }
+
+    /// Returns true if the style changes with the given state.
+    pub fn depends_on(&self, pseudo_class: PseudoClass) -> bool {
+        match pseudo_class {
+            PseudoClass::Hover => self.hover.is_some(),
+            PseudoClass::Active => self.active.is_some(),
+        }
+    }
+
+    fn apply(&mut self, name: &str, value: &str) {
+        match name {
+            "background-color" => match parse_color(value) {
+                Some(color) => self.background_color = Some(color),
+                None => warn!("Invalid background-color: {}", value),
+            },
+            "background-image" => match value.trim() {
+                "none" => self.background_image = None,
+                value => match parse_gradient(value) {
+                    Some(gradient) => self.background_image = Some(gradient),
+                    None => warn!("Invalid background-image: {}", value),
+                },
+            },
+            "opacity" => match parse_opacity(value) {
+                Some(opacity) => self.opacity = Some(opacity),
+                None => warn!("Invalid opacity: {}", value),
+            },
+            "border-radius" => match parse_border_radius(value) {
+                Some(radius) => self.border_radius = Some(radius),
+                None => warn!("Invalid border-radius: {}", value),
+            },
+            "border-top-left-radius"
+            | "border-top-right-radius"
+            | "border-bottom-right-radius"
+            | "border-bottom-left-radius" => match parse_corner_radius(value) {
+                Some(corner) => {
+                    let radius = self.border_radius.get_or_insert_with(BorderRadius::zero);
+                    *corner_mut(radius, name) = corner;
+                }
+                None => warn!("Invalid {}: {}", name, value),
+            },
+            name if name == "border" || name.starts_with("border-") => {
+                if !apply_border(&mut self.border, name, value) {
+                    warn!("Invalid {}: {}", name, value);
+                }
+            }
+            "transform" => match parse_transform(value) {
+                Some(functions) => self.transform.functions = functions,
+                None => warn!("Invalid transform: {}", value),
+            },
+            "transform-origin" => match parse_transform_origin(value) {
+                Some(origin) => self.transform.origin = origin,
+                None => warn!("Invalid transform-origin: {}", value),
+            },
+            "transition" => match parse_transitions(value) {
+                Some(transitions) => self.transitions = transitions,
+                None => warn!("Invalid transition: {}", value),
+            },
+            "animation" => match parse_animations(value) {
+                Some(animations) => self.animations = animations,
+                None => warn!("Invalid animation: {}", value),
+            },
+            "animation-play-state" => {
+                if !apply_play_state(&mut self.animations, value) {
+                    warn!("Invalid animation-play-state: {}", value);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    // Cascade the declarations for a node, optionally in a state. Inline
+    // attributes with the prefix of the state apply last.
+    fn cascade(node: &NodeView, stylesheet: &Stylesheet, state: Option<PseudoClass>) -> Self {
+        let mut style = PaintStyle::default();
+        // stylesheet rules come first so inline styles override them
+        let pseudo_classes: &[PseudoClass] = match &state {
+            Some(state) => std::slice::from_ref(state),
+            None => &[],
+        };
+        for (name, value) in stylesheet.matching_with(node, pseudo_classes) {
+            style.apply(name, value);
+        }
+        let inline: Vec<(&str, &str)> = node
+            .attributes()
+            .filter_map(|a| Some((a.name, a.value.as_text()?)))
+            .collect();
+        for (name, value) in &inline {
+            style.apply(name, value);
+        }
+        if let Some(state) = state {
+            for (name, value) in &inline {
+                if let Some(name) = name.strip_prefix(state.prefix()) {
+                    style.apply(name, value);
+                }
+            }
+        }
+        for animation in &mut style.animations {
+            animation.resolve(stylesheet);
+        }
+        style
+    }
+}
+
+impl NodeDepState<()> for PaintStyle {
+    type Ctx = Rc<Stylesheet>;
+    const NODE_MASK: NodeMask =
+        NodeMask::new_with_attrs(AttributeMask::Static(PAINT_ATTRIBUTES)).with_tag();
+
+    fn reduce(&mut self, node: NodeView<'_>, _sibling: (), stylesheet: &Self::Ctx) -> bool {
+        let mut new = PaintStyle::cascade(&node, stylesheet, None);
+        let variant = |state| {
+            let style = PaintStyle::cascade(&node, stylesheet, Some(state));
+            (style != new).then(|| Box::new(style))
+        };
+        let (hover, active) = (variant(PseudoClass::Hover), variant(PseudoClass::Active));
+        new.hover = hover;
+        new.active = active;
+
+        if *self != new {
+            *self = new;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+// a number or a percentage, clamped between 0 and 1
+pub(super) fn parse_opacity(value: &str) -> Option<f32> {
+    let value = value.trim();
+    let opacity = match value.strip_suffix('%') {
+        Some(percent) => percent.parse::<f32>().ok()? / 100.0,
+        None => value.parse::<f32>().ok()?,
+    };
+    (!opacity.is_nan()).then(|| opacity.clamp(0.0, 1.0))
+}
+
+const PAINT_ATTRIBUTES: &[&str] = &sorted_str_slice!([
+    "active:background-color",
+    "active:border-radius",
+    "active:opacity",
+    "active:transform",
+    "animation",
+    "animation-play-state",
+    "background-color",
+    "background-image",
+    "border",
+    "border-bottom",
+    "border-bottom-color",
+    "border-bottom-left-radius",
+    "border-bottom-right-radius",
+    "border-bottom-style",
+    "border-color",
+    "border-left",
+    "border-left-color",
+    "border-left-style",
+    "border-radius",
+    "border-right",
+    "border-right-color",
+    "border-right-style",
+    "border-style",
+    "border-top",
+    "border-top-color",
+    "border-top-left-radius",
+    "border-top-right-radius",
+    "border-top-style",
+    "class",
+    "hover:background-color",
+    "hover:border-radius",
+    "hover:opacity",
+    "hover:transform",
+    "id",
+    "opacity",
+    "transform",
+    "transform-origin",
+    "transition"
+]);
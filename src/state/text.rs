@@ -0,0 +1,273 @@
+use std::rc::Rc;
+
+use dioxus_native_core::{
+    node_ref::{AttributeMask, NodeMask, NodeView},
+    state::ParentDepState,
+};
+use dioxus_native_core_macro::sorted_str_slice;
+use webrender::api::ColorF;
+
+use crate::style::{parse_color_with_current, Stylesheet};
+
+/// The font size of the root and of `rem` values.
+const ROOT_FONT_SIZE: f32 = 16.0;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LineHeight {
+    /// The ascent, descent and line gap of the font.
+    Normal,
+    /// A multiple of the font size, inherited as is.
+    Number(f32),
+    Points(f32),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FontStyle {
+    Normal,
+    Italic,
+    Oblique,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TextAlign {
+    /// The left for left to right text, the right for right to left text.
+    Start,
+    End,
+    Left,
+    Right,
+    Center,
+    Justify,
+}
+
+/// The base direction of text, from the `direction` style.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Direction {
+    Ltr,
+    Rtl,
+}
+
+/// Whether the lines of text wrap, from the `white-space` style. The
+/// whitespace is collapsed either way.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WhiteSpace {
+    Normal,
+    Nowrap,
+}
+
+/// Text styles inherited from the parent like in css.
+#[derive(Clone, PartialEq, Debug)]
+pub struct TextStyle {
+    pub color: ColorF,
+    /// The css `font-family` list.
+    pub font_family: String,
+    pub font_size: f32,
+    pub font_weight: u16,
+    pub font_style: FontStyle,
+    pub line_height: LineHeight,
+    pub text_align: TextAlign,
+    /// The base direction of the text, or None to take the one of its first
+    /// strong character, like `dir="auto"`.
+    pub direction: Option<Direction>,
+    pub white_space: WhiteSpace,
+    /// Set by `text-decoration: underline`. Unlike in css it is inherited, which
+    /// draws the same as the decoration propagating to the descendants.
+    pub underline: bool,
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        Self {
+            color: ColorF::BLACK,
+            font_family: "sans-serif".to_string(),
+            font_size: ROOT_FONT_SIZE,
+            font_weight: 400,
+            font_style: FontStyle::Normal,
+            line_height: LineHeight::Normal,
+            text_align: TextAlign::Start,
+            direction: None,
+            white_space: WhiteSpace::Normal,
+            underline: false,
+        }
+    }
+}
+
+impl TextStyle {
+    /// Returns true if the styles lay out text the same way, ignoring the
+    /// properties that only affect painting.
+    pub fn layout_eq(&self, other: &Self) -> bool {
+        self.font_family == other.font_family
+            && self.font_size == other.font_size
+            && self.font_weight == other.font_weight
+            && self.font_style == other.font_style
+            && self.line_height == other.line_height
+            && self.white_space == other.white_space
+    }
+
+    fn apply(&mut self, name: &str, value: &str, parent: &TextStyle) {
+        match name {
+            "color" => match parse_color_with_current(value, parent.color) {
+                Some(color) => self.color = color,
+                None => warn!("Invalid color: {}", value),
+            },
+            "font-family" => self.font_family = value.to_string(),
+            "font-size" => match parse_font_size(value, parent.font_size) {
+                Some(size) => self.font_size = size,
+                None => warn!("Invalid font-size: {}", value),
+            },
+            "font-weight" => match parse_font_weight(value, parent.font_weight) {
+                Some(weight) => self.font_weight = weight,
+                None => warn!("Invalid font-weight: {}", value),
+            },
+            "font-style" => match value.split_whitespace().next() {
+                Some("normal") => self.font_style = FontStyle::Normal,
+                Some("italic") => self.font_style = FontStyle::Italic,
+                Some("oblique") => self.font_style = FontStyle::Oblique,
+                _ => warn!("Invalid font-style: {}", value),
+            },
+            "text-decoration" | "text-decoration-line" => {
+                self.underline = value.split_whitespace().any(|v| v == "underline")
+            }
+            "line-height" => match parse_line_height(value, self.font_size) {
+                Some(line_height) => self.line_height = line_height,
+                None => warn!("Invalid line-height: {}", value),
+            },
+            "text-align" => match value {
+                "start" => self.text_align = TextAlign::Start,
+                "end" => self.text_align = TextAlign::End,
+                "left" => self.text_align = TextAlign::Left,
+                "right" => self.text_align = TextAlign::Right,
+                "center" => self.text_align = TextAlign::Center,
+                "justify" => self.text_align = TextAlign::Justify,
+                _ => warn!("Invalid text-align: {}", value),
+            },
+            "direction" => match value.trim() {
+                "ltr" => self.direction = Some(Direction::Ltr),
+                "rtl" => self.direction = Some(Direction::Rtl),
+                _ => warn!("Invalid direction: {}", value),
+            },
+            "white-space" => match value.trim() {
+                "normal" => self.white_space = WhiteSpace::Normal,
+                "nowrap" => self.white_space = WhiteSpace::Nowrap,
+                _ => warn!("Invalid white-space: {}", value),
+            },
+            _ => (),
+        }
+    }
+}
+
+impl ParentDepState for TextStyle {
+    type Ctx = Rc<Stylesheet>;
+    type DepState = Self;
+    const NODE_MASK: NodeMask =
+        NodeMask::new_with_attrs(AttributeMask::Static(TEXT_ATTRIBUTES)).with_tag();
+
+    fn reduce<'a>(
+        &mut self,
+        node: NodeView,
+        parent: Option<&'a Self::DepState>,
+        stylesheet: &Self::Ctx,
+    ) -> bool {
+        let parent = parent.cloned().unwrap_or_default();
+        let mut new = parent.clone();
+
+        // font-size is applied first because line-height can be relative to it
+        let mut declarations = stylesheet.matching(&node);
+        declarations.extend(
+            node.attributes()
+                .filter_map(|a| Some((a.name, a.value.as_text()?))),
+        );
+        declarations.sort_by_key(|(name, _)| *name != "font-size");
+        for (name, value) in declarations {
+            new.apply(name, value, &parent);
+        }
+
+        if *self != new {
+            *self = new;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+fn parse_font_size(value: &str, parent: f32) -> Option<f32> {
+    let size = match value {
+        "xx-small" => 9.0,
+        "x-small" => 10.0,
+        "small" => 13.0,
+        "medium" => ROOT_FONT_SIZE,
+        "large" => 18.0,
+        "x-large" => 24.0,
+        "xx-large" => 32.0,
+        "smaller" => parent / 1.2,
+        "larger" => parent * 1.2,
+        _ => {
+            if let Some(px) = value.strip_suffix("px") {
+                px.trim().parse().ok()?
+            } else if let Some(rem) = value.strip_suffix("rem") {
+                rem.trim().parse::<f32>().ok()? * ROOT_FONT_SIZE
+            } else if let Some(em) = value.strip_suffix("em") {
+                em.trim().parse::<f32>().ok()? * parent
+            } else if let Some(percent) = value.strip_suffix('%') {
+                percent.trim().parse::<f32>().ok()? * parent / 100.0
+            } else if let Some(pt) = value.strip_suffix("pt") {
+                pt.trim().parse::<f32>().ok()? * 4.0 / 3.0
+            } else {
+                return None;
+            }
+        }
+    };
+    (size.is_finite() && size >= 0.0).then(|| size)
+}
+
+fn parse_font_weight(value: &str, parent: u16) -> Option<u16> {
+    match value {
+        "normal" => Some(400),
+        "bold" => Some(700),
+        "bolder" => Some(match parent {
+            0..=349 => 400,
+            350..=549 => 700,
+            _ => 900,
+        }),
+        "lighter" => Some(match parent {
+            0..=549 => 100,
+            550..=749 => 400,
+            _ => 700,
+        }),
+        _ => value.parse().ok().filter(|w| (1..=1000).contains(w)),
+    }
+}
+
+fn parse_line_height(value: &str, font_size: f32) -> Option<LineHeight> {
+    let line_height = if value == "normal" {
+        LineHeight::Normal
+    } else if let Some(px) = value.strip_suffix("px") {
+        LineHeight::Points(px.trim().parse().ok()?)
+    } else if let Some(em) = value.strip_suffix("em") {
+        LineHeight::Points(em.trim().parse::<f32>().ok()? * font_size)
+    } else if let Some(percent) = value.strip_suffix('%') {
+        LineHeight::Points(percent.trim().parse::<f32>().ok()? * font_size / 100.0)
+    } else {
+        LineHeight::Number(value.parse().ok()?)
+    };
+    match line_height {
+        LineHeight::Number(n) | LineHeight::Points(n) if n.is_nan() || n < 0.0 => None,
+        _ => Some(line_height),
+    }
+}
+
+const TEXT_ATTRIBUTES: &[&str] = &sorted_str_slice!([
+    "class",
+    "color",
+    "direction",
+    "font-family",
+    "font-size",
+    "font-style",
+    "font-weight",
+    "id",
+    "line-height",
+    "text-align",
+    "text-decoration",
+    "text-decoration-line",
+    "white-space"
+]);
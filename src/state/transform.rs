@@ -0,0 +1,242 @@
+use webrender::api::units::{LayoutRect, LayoutTransform};
+
+use super::gradient::parse_angle;
+use crate::utils::split_outside_parens;
+
+/// The `transform` of a node, applied about its `transform-origin`. It only
+/// changes how the node is painted, not its layout.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Transform {
+    /// The transform functions, applied from the last one.
+    pub functions: Vec<TransformFunction>,
+    pub origin: TransformOrigin,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TransformFunction {
+    Translate(Length, Length),
+    Scale(f32, f32),
+    /// A clockwise rotation in degrees.
+    Rotate(f32),
+    /// The skew angles along the x and y axes in degrees.
+    Skew(f32, f32),
+}
+
+/// A length in layout units, or relative to the size of the node.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Length {
+    Points(f32),
+    Fraction(f32),
+}
+
+impl Length {
+    // The length in device pixels for a node `size` device pixels long
+    fn resolve(&self, size: f32, scale_factor: f32) -> f32 {
+        match *self {
+            Length::Points(points) => points * scale_factor,
+            Length::Fraction(fraction) => fraction * size,
+        }
+    }
+}
+
+/// The point the transform is applied about, from the top left corner of the
+/// node. The center by default.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TransformOrigin {
+    pub x: Length,
+    pub y: Length,
+}
+
+impl Default for TransformOrigin {
+    fn default() -> Self {
+        Self {
+            x: Length::Fraction(0.5),
+            y: Length::Fraction(0.5),
+        }
+    }
+}
+
+impl Transform {
+    pub fn is_identity(&self) -> bool {
+        self.functions.is_empty()
+    }
+
+    /// The matrix of the transform of a node whose rect is in device pixels.
+    /// The lengths in layout units are scaled by `scale_factor`.
+    pub fn to_matrix(&self, rect: LayoutRect, scale_factor: f32) -> LayoutTransform {
+        let size = rect.size();
+        let origin_x = rect.min.x + self.origin.x.resolve(size.width, scale_factor);
+        let origin_y = rect.min.y + self.origin.y.resolve(size.height, scale_factor);
+
+        // the matrices transform row vectors, so the last function is first
+        let mut matrix = LayoutTransform::translation(-origin_x, -origin_y, 0.0);
+        for function in self.functions.iter().rev() {
+            let function = match *function {
+                TransformFunction::Translate(x, y) => LayoutTransform::translation(
+                    x.resolve(size.width, scale_factor),
+                    y.resolve(size.height, scale_factor),
+                    0.0,
+                ),
+                TransformFunction::Scale(x, y) => LayoutTransform::scale(x, y, 1.0),
+                TransformFunction::Rotate(degrees) => {
+                    let (sin, cos) = degrees.to_radians().sin_cos();
+                    LayoutTransform::new_2d(cos, sin, -sin, cos, 0.0, 0.0)
+                }
+                TransformFunction::Skew(x, y) => LayoutTransform::new_2d(
+                    1.0,
+                    y.to_radians().tan(),
+                    x.to_radians().tan(),
+                    1.0,
+                    0.0,
+                    0.0,
+                ),
+            };
+            matrix = matrix.then(&function);
+        }
+        matrix.then(&LayoutTransform::translation(origin_x, origin_y, 0.0))
+    }
+}
+
+/// Parse the `transform` property: `none` or a space separated list of
+/// translate, scale, rotate and skew functions.
+pub(super) fn parse_transform(value: &str) -> Option<Vec<TransformFunction>> {
+    if value.trim() == "none" {
+        return Some(Vec::new());
+    }
+    split_outside_parens(value, char::is_whitespace)
+        .into_iter()
+        .map(parse_function)
+        .collect()
+}
+
+fn parse_function(value: &str) -> Option<TransformFunction> {
+    let (name, args) = value.strip_suffix(')')?.split_once('(')?;
+    let args: Vec<&str> = args.split(',').map(str::trim).collect();
+    let lengths = || -> Option<Vec<Length>> { args.iter().map(|a| parse_length(a)).collect() };
+    let numbers = || -> Option<Vec<f32>> {
+        let numbers: Option<Vec<f32>> = args.iter().map(|a| a.parse().ok()).collect();
+        numbers.filter(|n| n.iter().all(|n| n.is_finite()))
+    };
+    let angles = || -> Option<Vec<f32>> { args.iter().map(|a| parse_angle(a)).collect() };
+    let zero = Length::Points(0.0);
+
+    Some(match (name.trim(), args.len()) {
+        ("translate", 1 | 2) => {
+            let lengths = lengths()?;
+            TransformFunction::Translate(lengths[0], *lengths.get(1).unwrap_or(&zero))
+        }
+        ("translateX", 1) => TransformFunction::Translate(lengths()?[0], zero),
+        ("translateY", 1) => TransformFunction::Translate(zero, lengths()?[0]),
+        ("scale", 1 | 2) => {
+            let numbers = numbers()?;
+            TransformFunction::Scale(numbers[0], *numbers.get(1).unwrap_or(&numbers[0]))
+        }
+        ("scaleX", 1) => TransformFunction::Scale(numbers()?[0], 1.0),
+        ("scaleY", 1) => TransformFunction::Scale(1.0, numbers()?[0]),
+        ("rotate", 1) => TransformFunction::Rotate(angles()?[0]),
+        ("skew", 1 | 2) => {
+            let angles = angles()?;
+            TransformFunction::Skew(angles[0], *angles.get(1).unwrap_or(&0.0))
+        }
+        ("skewX", 1) => TransformFunction::Skew(angles()?[0], 0.0),
+        ("skewY", 1) => TransformFunction::Skew(0.0, angles()?[0]),
+        _ => return None,
+    })
+}
+
+/// Parse the `transform-origin` property: one or two keywords or lengths. A
+/// single value sets the horizontal position, unless it is `top` or
+/// `bottom`, and the other axis is centered.
+pub(super) fn parse_transform_origin(value: &str) -> Option<TransformOrigin> {
+    let center = Length::Fraction(0.5);
+    let tokens: Vec<&str> = value.split_whitespace().collect();
+    let (x, y) = match *tokens.as_slice() {
+        [single] => match single {
+            "top" | "bottom" => (center, parse_vertical(single)?),
+            _ => (parse_horizontal(single)?, center),
+        },
+        // the keywords can be in any order
+        [first, second] => match (parse_horizontal(first), parse_vertical(second)) {
+            (Some(x), Some(y)) => (x, y),
+            _ => (parse_horizontal(second)?, parse_vertical(first)?),
+        },
+        _ => return None,
+    };
+    Some(TransformOrigin { x, y })
+}
+
+fn parse_horizontal(value: &str) -> Option<Length> {
+    match value {
+        "left" => Some(Length::Fraction(0.0)),
+        "center" => Some(Length::Fraction(0.5)),
+        "right" => Some(Length::Fraction(1.0)),
+        _ => parse_length(value),
+    }
+}
+
+fn parse_vertical(value: &str) -> Option<Length> {
+    match value {
+        "top" => Some(Length::Fraction(0.0)),
+        "center" => Some(Length::Fraction(0.5)),
+        "bottom" => Some(Length::Fraction(1.0)),
+        _ => parse_length(value),
+    }
+}
+
+fn parse_length(value: &str) -> Option<Length> {
+    let length = if let Some(percent) = value.strip_suffix('%') {
+        Length::Fraction(percent.parse::<f32>().ok()? / 100.0)
+    } else if let Some(px) = value.strip_suffix("px") {
+        Length::Points(px.parse().ok()?)
+    } else if value == "0" {
+        Length::Points(0.0)
+    } else {
+        return None;
+    };
+    match length {
+        Length::Points(v) | Length::Fraction(v) if v.is_finite() => Some(length),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use webrender::api::units::{LayoutPoint, LayoutSize};
+
+    use super::*;
+
+    fn assert_near(point: LayoutPoint, x: f32, y: f32) {
+        assert!(
+            (point.x - x).abs() < 1e-3 && (point.y - y).abs() < 1e-3,
+            "{:?} is not ({}, {})",
+            point,
+            x,
+            y
+        );
+    }
+
+    #[test]
+    fn rotate_about_top_left() {
+        let transform = Transform {
+            functions: parse_transform("rotate(90deg)").unwrap(),
+            origin: parse_transform_origin("top left").unwrap(),
+        };
+        let (left, top, width) = (10.0, 20.0, 100.0);
+        let rect = LayoutRect::from_origin_and_size(
+            LayoutPoint::new(left, top),
+            LayoutSize::new(width, 50.0),
+        );
+        let matrix = transform.to_matrix(rect, 1.0);
+
+        // the origin stays in place and the top right corner turns clockwise
+        // below it
+        let pivot = matrix
+            .transform_point2d(LayoutPoint::new(left, top))
+            .unwrap();
+        assert_near(pivot, left, top);
+        let corner = matrix
+            .transform_point2d(LayoutPoint::new(left + width, top))
+            .unwrap();
+        assert_near(corner, left, top + width);
+    }
+}
@@ -0,0 +1,450 @@
+use std::time::{Duration, Instant};
+
+use webrender::api::{units::LayoutSize, BorderRadius, ColorF};
+
+use super::{
+    border::parse_border_radius,
+    style::parse_opacity,
+    transform::{parse_transform, Length, TransformFunction},
+    PaintStyle,
+};
+use crate::style::parse_color;
+
+/// A transitioned property from the css `transition` property.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Transition {
+    pub property: TransitionProperty,
+    pub duration: Duration,
+    pub delay: Duration,
+    pub timing: TimingFunction,
+}
+
+/// A property that can be transitioned or animated.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TransitionProperty {
+    BackgroundColor,
+    Opacity,
+    BorderRadius,
+    Transform,
+}
+
+impl TransitionProperty {
+    const ALL: [TransitionProperty; 4] = [
+        TransitionProperty::BackgroundColor,
+        TransitionProperty::Opacity,
+        TransitionProperty::BorderRadius,
+        TransitionProperty::Transform,
+    ];
+
+    pub(super) fn parse(name: &str, value: &str) -> Option<(Self, AnimatedValue)> {
+        match name {
+            "background-color" => Some((
+                TransitionProperty::BackgroundColor,
+                AnimatedValue::Color(parse_color(value)?),
+            )),
+            "opacity" => Some((
+                TransitionProperty::Opacity,
+                AnimatedValue::Number(parse_opacity(value)?),
+            )),
+            "border-radius" => Some((
+                TransitionProperty::BorderRadius,
+                AnimatedValue::Radius(parse_border_radius(value)?),
+            )),
+            "transform" => Some((
+                TransitionProperty::Transform,
+                AnimatedValue::Transform(parse_transform(value)?),
+            )),
+            _ => None,
+        }
+    }
+
+    pub(super) fn get(&self, style: &PaintStyle) -> AnimatedValue {
+        match self {
+            TransitionProperty::BackgroundColor => {
+                AnimatedValue::Color(style.background_color.unwrap_or(ColorF::TRANSPARENT))
+            }
+            TransitionProperty::Opacity => AnimatedValue::Number(style.opacity.unwrap_or(1.0)),
+            TransitionProperty::BorderRadius => {
+                AnimatedValue::Radius(style.border_radius.unwrap_or_else(BorderRadius::zero))
+            }
+            TransitionProperty::Transform => {
+                AnimatedValue::Transform(style.transform.functions.clone())
+            }
+        }
+    }
+
+    pub(super) fn set(&self, style: &mut PaintStyle, value: AnimatedValue) {
+        match (self, value) {
+            (TransitionProperty::BackgroundColor, AnimatedValue::Color(c)) => {
+                style.background_color = Some(c)
+            }
+            (TransitionProperty::Opacity, AnimatedValue::Number(n)) => style.opacity = Some(n),
+            (TransitionProperty::BorderRadius, AnimatedValue::Radius(r)) => {
+                style.border_radius = Some(r)
+            }
+            (TransitionProperty::Transform, AnimatedValue::Transform(functions)) => {
+                style.transform.functions = functions
+            }
+            _ => (),
+        }
+    }
+}
+
+/// The easing of a transition or animation.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TimingFunction {
+    Linear,
+    CubicBezier(f32, f32, f32, f32),
+}
+
+impl TimingFunction {
+    pub(super) const EASE: TimingFunction = TimingFunction::CubicBezier(0.25, 0.1, 0.25, 1.0);
+
+    pub(super) fn parse(value: &str) -> Option<Self> {
+        Some(match value {
+            "linear" => TimingFunction::Linear,
+            "ease" => TimingFunction::EASE,
+            "ease-in" => TimingFunction::CubicBezier(0.42, 0.0, 1.0, 1.0),
+            "ease-out" => TimingFunction::CubicBezier(0.0, 0.0, 0.58, 1.0),
+            "ease-in-out" => TimingFunction::CubicBezier(0.42, 0.0, 0.58, 1.0),
+            _ => return None,
+        })
+    }
+
+    /// Map the elapsed fraction of a transition to the progress of its value.
+    pub(super) fn apply(&self, x: f32) -> f32 {
+        match *self {
+            TimingFunction::Linear => x,
+            TimingFunction::CubicBezier(x1, y1, x2, y2) => {
+                let bezier = |a: f32, b: f32, t: f32| {
+                    3.0 * a * t * (1.0 - t).powi(2) + 3.0 * b * t.powi(2) * (1.0 - t) + t.powi(3)
+                };
+                // x grows with t, so the t of x is found by bisection
+                let (mut low, mut high) = (0.0, 1.0);
+                for _ in 0..20 {
+                    let t = (low + high) / 2.0;
+                    if bezier(x1, x2, t) < x {
+                        low = t;
+                    } else {
+                        high = t;
+                    }
+                }
+                bezier(y1, y2, (low + high) / 2.0)
+            }
+        }
+    }
+}
+
+/// The name of [`TimingFunction`] used by the `transition` docs.
+pub type Easing = TimingFunction;
+
+/// Parse the value of the css `transition` property, like
+/// `background-color 200ms ease, opacity 0.3s`.
+pub fn parse_transitions(value: &str) -> Option<Vec<Transition>> {
+    let mut transitions = Vec::new();
+    for transition in value.split(',') {
+        let mut properties: &[TransitionProperty] = &TransitionProperty::ALL;
+        let mut times = Vec::new();
+        let mut timing = TimingFunction::EASE;
+        for token in transition.split_whitespace() {
+            if let Some(time) = parse_time(token) {
+                times.push(time);
+            } else if let Some(t) = TimingFunction::parse(token) {
+                timing = t;
+            } else {
+                properties = match token {
+                    "all" => &TransitionProperty::ALL,
+                    "background-color" => &[TransitionProperty::BackgroundColor],
+                    "opacity" => &[TransitionProperty::Opacity],
+                    "border-radius" => &[TransitionProperty::BorderRadius],
+                    "transform" => &[TransitionProperty::Transform],
+                    "none" => &[],
+                    _ => return None,
+                };
+            }
+        }
+        // the first time is the duration and the second the delay
+        let (duration, delay) = match times[..] {
+            [] => (Duration::ZERO, Duration::ZERO),
+            [duration] => (duration, Duration::ZERO),
+            [duration, delay] => (duration, delay),
+            _ => return None,
+        };
+        transitions.extend(properties.iter().map(|&property| Transition {
+            property,
+            duration,
+            delay,
+            timing,
+        }));
+    }
+    Some(transitions)
+}
+
+pub(super) fn parse_time(value: &str) -> Option<Duration> {
+    let seconds = if let Some(ms) = value.strip_suffix("ms") {
+        ms.parse::<f32>().ok()? / 1000.0
+    } else {
+        value.strip_suffix('s')?.parse::<f32>().ok()?
+    };
+    (seconds.is_finite() && seconds >= 0.0).then(|| Duration::from_secs_f32(seconds))
+}
+
+/// The value of a transitioned property.
+///
+/// Colors are interpolated in sRGB with premultiplied alpha, like browsers
+/// do, so fading from transparent doesn't go through black. Lengths, like the
+/// radii of the corners, are interpolated in layout units. Transforms are
+/// interpolated function by function when both lists have the same functions,
+/// or when one of them is `none`, and jump to the target otherwise.
+#[derive(Clone, PartialEq, Debug)]
+pub(super) enum AnimatedValue {
+    Color(ColorF),
+    Number(f32),
+    Radius(BorderRadius),
+    Transform(Vec<TransformFunction>),
+}
+
+impl AnimatedValue {
+    pub(super) fn interpolate(&self, to: &AnimatedValue, progress: f32) -> AnimatedValue {
+        let lerp = |a: f32, b: f32| a + (b - a) * progress;
+        match (self, to) {
+            (&AnimatedValue::Color(a), &AnimatedValue::Color(b)) => {
+                let (a, b) = (a.premultiplied(), b.premultiplied());
+                let alpha = lerp(a.a, b.a);
+                if alpha <= 0.0 {
+                    return AnimatedValue::Color(ColorF::TRANSPARENT);
+                }
+                AnimatedValue::Color(ColorF::new(
+                    lerp(a.r, b.r) / alpha,
+                    lerp(a.g, b.g) / alpha,
+                    lerp(a.b, b.b) / alpha,
+                    alpha,
+                ))
+            }
+            (&AnimatedValue::Number(a), &AnimatedValue::Number(b)) => {
+                AnimatedValue::Number(lerp(a, b))
+            }
+            (&AnimatedValue::Radius(a), &AnimatedValue::Radius(b)) => {
+                let corner = |a: LayoutSize, b: LayoutSize| {
+                    LayoutSize::new(lerp(a.width, b.width), lerp(a.height, b.height))
+                };
+                AnimatedValue::Radius(BorderRadius {
+                    top_left: corner(a.top_left, b.top_left),
+                    top_right: corner(a.top_right, b.top_right),
+                    bottom_right: corner(a.bottom_right, b.bottom_right),
+                    bottom_left: corner(a.bottom_left, b.bottom_left),
+                })
+            }
+            (AnimatedValue::Transform(a), AnimatedValue::Transform(b)) => {
+                // none is the identity of each function of the other list
+                let identities = |other: &[TransformFunction]| -> Vec<TransformFunction> {
+                    other.iter().map(TransformFunction::identity).collect()
+                };
+                let (a, b) = match (a.is_empty(), b.is_empty()) {
+                    (true, false) => (identities(b), b.clone()),
+                    (false, true) => (a.clone(), identities(a)),
+                    _ => (a.clone(), b.clone()),
+                };
+                let functions = (a.len() == b.len())
+                    .then(|| {
+                        a.iter()
+                            .zip(&b)
+                            .map(|(a, b)| a.interpolate(b, progress))
+                            .collect::<Option<Vec<_>>>()
+                    })
+                    .flatten();
+                AnimatedValue::Transform(functions.unwrap_or(b))
+            }
+            _ => to.clone(),
+        }
+    }
+}
+
+impl TransformFunction {
+    fn identity(&self) -> TransformFunction {
+        let zero = Length::Points(0.0);
+        match self {
+            TransformFunction::Translate(..) => TransformFunction::Translate(zero, zero),
+            TransformFunction::Scale(..) => TransformFunction::Scale(1.0, 1.0),
+            TransformFunction::Rotate(_) => TransformFunction::Rotate(0.0),
+            TransformFunction::Skew(..) => TransformFunction::Skew(0.0, 0.0),
+        }
+    }
+
+    // None if the functions are of different kinds, or mix lengths in
+    // layout units and percentages
+    fn interpolate(&self, to: &TransformFunction, progress: f32) -> Option<TransformFunction> {
+        let lerp = |a: f32, b: f32| a + (b - a) * progress;
+        let length = |a: Length, b: Length| match (a, b) {
+            (Length::Points(a), Length::Points(b)) => Some(Length::Points(lerp(a, b))),
+            (Length::Fraction(a), Length::Fraction(b)) => Some(Length::Fraction(lerp(a, b))),
+            _ => None,
+        };
+        Some(match (*self, *to) {
+            (TransformFunction::Translate(ax, ay), TransformFunction::Translate(bx, by)) => {
+                TransformFunction::Translate(length(ax, bx)?, length(ay, by)?)
+            }
+            (TransformFunction::Scale(ax, ay), TransformFunction::Scale(bx, by)) => {
+                TransformFunction::Scale(lerp(ax, bx), lerp(ay, by))
+            }
+            (TransformFunction::Rotate(a), TransformFunction::Rotate(b)) => {
+                TransformFunction::Rotate(lerp(a, b))
+            }
+            (TransformFunction::Skew(ax, ay), TransformFunction::Skew(bx, by)) => {
+                TransformFunction::Skew(lerp(ax, bx), lerp(ay, by))
+            }
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+struct Animation {
+    property: TransitionProperty,
+    from: AnimatedValue,
+    to: AnimatedValue,
+    start: Instant,
+    transition: Transition,
+    /// The value at the last tick.
+    current: AnimatedValue,
+}
+
+impl Animation {
+    fn value_at(&self, now: Instant) -> AnimatedValue {
+        let elapsed = now.saturating_duration_since(self.start + self.transition.delay);
+        let x = if self.transition.duration.is_zero() {
+            1.0
+        } else {
+            (elapsed.as_secs_f32() / self.transition.duration.as_secs_f32()).min(1.0)
+        };
+        self.from
+            .interpolate(&self.to, self.transition.timing.apply(x))
+    }
+
+    fn is_finished(&self, now: Instant) -> bool {
+        now >= self.start + self.transition.delay + self.transition.duration
+    }
+}
+
+/// The running transitions of a node.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct TransitionState {
+    /// The last target value of each transitioned property.
+    targets: Vec<(TransitionProperty, AnimatedValue)>,
+    running: Vec<Animation>,
+}
+
+impl TransitionState {
+    pub fn is_running(&self) -> bool {
+        !self.running.is_empty()
+    }
+
+    /// Start transitions to the properties of `style` that changed since the
+    /// last call. A property that is already transitioning starts again
+    /// from its current value. Returns true if any transition is running.
+    pub(crate) fn retarget(
+        &mut self,
+        style: &PaintStyle,
+        transitions: &[Transition],
+        now: Instant,
+    ) -> bool {
+        let mut targets = Vec::with_capacity(transitions.len());
+        for transition in transitions {
+            let property = transition.property;
+            let to = property.get(style);
+            targets.push((property, to.clone()));
+
+            let last = self.targets.iter().find(|(p, _)| *p == property);
+            let from = match last {
+                Some((_, last)) if *last != to => self
+                    .running
+                    .iter()
+                    .find(|a| a.property == property)
+                    .map_or_else(|| last.clone(), |a| a.value_at(now)),
+                // the first value of a property is not transitioned
+                _ => continue,
+            };
+            self.running.retain(|a| a.property != property);
+            self.running.push(Animation {
+                property,
+                current: from.clone(),
+                from,
+                to,
+                start: now,
+                transition: *transition,
+            });
+        }
+        self.targets = targets;
+        // properties that are not transitioned anymore stop
+        self.running
+            .retain(|a| transitions.iter().any(|t| t.property == a.property));
+        self.is_running()
+    }
+
+    /// Update the values of the running transitions and drop the finished
+    /// ones. Returns true if any transition is still running.
+    pub(crate) fn tick(&mut self, now: Instant) -> bool {
+        self.running.retain(|a| !a.is_finished(now));
+        for animation in &mut self.running {
+            animation.current = animation.value_at(now);
+        }
+        self.is_running()
+    }
+
+    /// Override the properties of `style` with the values of the last tick.
+    pub fn apply(&self, style: &mut PaintStyle) {
+        for animation in &self.running {
+            animation.property.set(style, animation.current.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transform_transition() {
+        let transitions = parse_transitions("transform 200ms ease-in").unwrap();
+        assert_eq!(transitions.len(), 1);
+        assert_eq!(transitions[0].property, TransitionProperty::Transform);
+        assert_eq!(transitions[0].duration, Duration::from_millis(200));
+        assert_eq!(
+            transitions[0].timing,
+            TimingFunction::CubicBezier(0.42, 0.0, 1.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn interpolate_radii() {
+        let radius = |value: &str| AnimatedValue::Radius(parse_border_radius(value).unwrap());
+        assert_eq!(
+            radius("0").interpolate(&radius("10px 20px / 4px"), 0.5),
+            radius("5px 10px / 2px")
+        );
+    }
+
+    #[test]
+    fn interpolate_transforms() {
+        let transform = |value: &str| AnimatedValue::Transform(parse_transform(value).unwrap());
+
+        // none goes through the identity of each function
+        assert_eq!(
+            transform("none").interpolate(&transform("rotate(90deg) translateX(20px)"), 0.5),
+            transform("rotate(45deg) translate(10px, 0px)")
+        );
+        assert_eq!(
+            transform("scale(2)").interpolate(&transform("none"), 0.5),
+            transform("scale(1.5)")
+        );
+        // different functions jump to the target
+        assert_eq!(
+            transform("rotate(90deg)").interpolate(&transform("scale(2)"), 0.5),
+            transform("scale(2)")
+        );
+        assert_eq!(
+            transform("translateX(10px)").interpolate(&transform("translateX(50%)"), 0.5),
+            transform("translateX(50%)")
+        );
+    }
+}
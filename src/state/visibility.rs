@@ -0,0 +1,49 @@
+use std::rc::Rc;
+
+use dioxus_native_core::{
+    node_ref::{AttributeMask, NodeMask, NodeView},
+    state::NodeDepState,
+};
+use dioxus_native_core_macro::sorted_str_slice;
+
+use crate::style::Stylesheet;
+
+/// Set by the `visibility` style. Hidden nodes keep their place in the layout
+/// but are not painted and can't be hovered or clicked. None inherits the
+/// visibility of the parent, so a visible child of a hidden node is painted.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Visibility(pub Option<bool>);
+
+impl NodeDepState<()> for Visibility {
+    type Ctx = Rc<Stylesheet>;
+    const NODE_MASK: NodeMask =
+        NodeMask::new_with_attrs(AttributeMask::Static(VISIBILITY_ATTRIBUTES)).with_tag();
+
+    fn reduce(&mut self, node: NodeView<'_>, _sibling: (), stylesheet: &Self::Ctx) -> bool {
+        let mut new = Visibility::default();
+        // stylesheet rules come first so inline styles override them
+        let inline = node
+            .attributes()
+            .filter_map(|a| Some((a.name, a.value.as_text()?)));
+        for (name, value) in stylesheet.matching(&node).into_iter().chain(inline) {
+            if name == "visibility" {
+                match value.trim() {
+                    "visible" => new = Visibility(Some(true)),
+                    // there are no table rows to collapse
+                    "hidden" | "collapse" => new = Visibility(Some(false)),
+                    "inherit" => new = Visibility(None),
+                    _ => warn!("Invalid visibility: {}", value),
+                }
+            }
+        }
+
+        if *self != new {
+            *self = new;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+const VISIBILITY_ATTRIBUTES: &[&str] = &sorted_str_slice!(["class", "id", "visibility"]);
@@ -0,0 +1,420 @@
+use webrender::api::ColorF;
+
+/// Parse a css color. `currentColor` resolves to black, use
+/// [`parse_color_with_current`] to resolve it to the inherited text color.
+pub fn parse_color(value: &str) -> Option<ColorF> {
+    parse_color_with_current(value, ColorF::BLACK)
+}
+
+/// Parse a css color, resolving `currentColor` to `current`.
+///
+/// Supports hex notations, `rgb()`/`rgba()` and `hsl()`/`hsla()` with both the
+/// comma and the space separated syntax, named colors and `transparent`.
+/// Returns None for anything else.
+pub fn parse_color_with_current(value: &str, current: ColorF) -> Option<ColorF> {
+    let value = value.trim().to_ascii_lowercase();
+
+    if let Some(hex) = value.strip_prefix('#') {
+        return parse_hex(hex);
+    }
+
+    if let Some(open) = value.find('(') {
+        let args = value[open + 1..].strip_suffix(')')?;
+        return match value[..open].trim() {
+            "rgb" | "rgba" => parse_rgb(args),
+            "hsl" | "hsla" => parse_hsl(args),
+            _ => None,
+        };
+    }
+
+    match value.as_str() {
+        "transparent" => Some(ColorF::TRANSPARENT),
+        "currentcolor" => Some(current),
+        name => NAMED_COLORS
+            .binary_search_by_key(&name, |&(name, _)| name)
+            .ok()
+            .map(|i| {
+                let rgb = NAMED_COLORS[i].1;
+                rgb8((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8, 255)
+            }),
+    }
+}
+
+fn rgb8(r: u8, g: u8, b: u8, a: u8) -> ColorF {
+    ColorF::new(
+        r as f32 / 255.0,
+        g as f32 / 255.0,
+        b as f32 / 255.0,
+        a as f32 / 255.0,
+    )
+}
+
+fn parse_hex(hex: &str) -> Option<ColorF> {
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let digit = |i: usize| u8::from_str_radix(&hex[i..i + 1], 16).ok();
+    let byte = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+
+    match hex.len() {
+        3 | 4 => {
+            let a = if hex.len() == 4 { digit(3)? * 17 } else { 255 };
+            Some(rgb8(digit(0)? * 17, digit(1)? * 17, digit(2)? * 17, a))
+        }
+        6 | 8 => {
+            let a = if hex.len() == 8 { byte(6)? } else { 255 };
+            Some(rgb8(byte(0)?, byte(2)?, byte(4)?, a))
+        }
+        _ => None,
+    }
+}
+
+/// Split the arguments of a color function into its three channels and the
+/// optional alpha, accepting both `1, 2, 3, 0.5` and `1 2 3 / 0.5`.
+fn split_args(args: &str) -> Option<([&str; 3], Option<&str>)> {
+    let (channels, alpha): (Vec<&str>, Option<&str>) = if args.contains(',') {
+        let mut parts: Vec<&str> = args.split(',').map(str::trim).collect();
+        let alpha = if parts.len() == 4 { parts.pop() } else { None };
+        (parts, alpha)
+    } else {
+        let (channels, alpha) = match args.split_once('/') {
+            Some((channels, alpha)) => (channels, Some(alpha.trim())),
+            None => (args, None),
+        };
+        (channels.split_whitespace().collect(), alpha)
+    };
+
+    match channels[..] {
+        [a, b, c] => Some(([a, b, c], alpha)),
+        _ => None,
+    }
+}
+
+/// Parse a number or a percentage, returning the value as a fraction of `max`.
+fn parse_fraction(value: &str, max: f32) -> Option<f32> {
+    let fraction = match value.strip_suffix('%') {
+        Some(percent) => percent.trim().parse::<f32>().ok()? / 100.0,
+        None => value.parse::<f32>().ok()? / max,
+    };
+    if fraction.is_finite() {
+        Some(fraction.clamp(0.0, 1.0))
+    } else {
+        None
+    }
+}
+
+fn parse_alpha(alpha: Option<&str>) -> Option<f32> {
+    alpha.map_or(Some(1.0), |a| parse_fraction(a, 1.0))
+}
+
+fn parse_rgb(args: &str) -> Option<ColorF> {
+    let ([r, g, b], alpha) = split_args(args)?;
+    Some(ColorF::new(
+        parse_fraction(r, 255.0)?,
+        parse_fraction(g, 255.0)?,
+        parse_fraction(b, 255.0)?,
+        parse_alpha(alpha)?,
+    ))
+}
+
+fn parse_hue(hue: &str) -> Option<f32> {
+    let degrees = if let Some(deg) = hue.strip_suffix("deg") {
+        deg.parse::<f32>().ok()?
+    } else if let Some(grad) = hue.strip_suffix("grad") {
+        grad.parse::<f32>().ok()? * 0.9
+    } else if let Some(rad) = hue.strip_suffix("rad") {
+        rad.parse::<f32>().ok()?.to_degrees()
+    } else if let Some(turn) = hue.strip_suffix("turn") {
+        turn.parse::<f32>().ok()? * 360.0
+    } else {
+        hue.parse::<f32>().ok()?
+    };
+    if degrees.is_finite() {
+        Some(degrees.rem_euclid(360.0))
+    } else {
+        None
+    }
+}
+
+fn parse_hsl(args: &str) -> Option<ColorF> {
+    let ([h, s, l], alpha) = split_args(args)?;
+    let h = parse_hue(h)? / 360.0;
+    let s = parse_fraction(s, 100.0)?;
+    let l = parse_fraction(l, 100.0)?;
+
+    let q = if l < 0.5 {
+        l * (1.0 + s)
+    } else {
+        l + s - l * s
+    };
+    let p = 2.0 * l - q;
+    let channel = |t: f32| {
+        let t = t.rem_euclid(1.0);
+        if t < 1.0 / 6.0 {
+            p + (q - p) * 6.0 * t
+        } else if t < 1.0 / 2.0 {
+            q
+        } else if t < 2.0 / 3.0 {
+            p + (q - p) * (2.0 / 3.0 - t) * 6.0
+        } else {
+            p
+        }
+    };
+
+    Some(ColorF::new(
+        channel(h + 1.0 / 3.0),
+        channel(h),
+        channel(h - 1.0 / 3.0),
+        parse_alpha(alpha)?,
+    ))
+}
+
+// Sorted by name so it can be binary searched
+const NAMED_COLORS: &[(&str, u32)] = &[
+    ("aliceblue", 0xf0f8ff),
+    ("antiquewhite", 0xfaebd7),
+    ("aqua", 0x00ffff),
+    ("aquamarine", 0x7fffd4),
+    ("azure", 0xf0ffff),
+    ("beige", 0xf5f5dc),
+    ("bisque", 0xffe4c4),
+    ("black", 0x000000),
+    ("blanchedalmond", 0xffebcd),
+    ("blue", 0x0000ff),
+    ("blueviolet", 0x8a2be2),
+    ("brown", 0xa52a2a),
+    ("burlywood", 0xdeb887),
+    ("cadetblue", 0x5f9ea0),
+    ("chartreuse", 0x7fff00),
+    ("chocolate", 0xd2691e),
+    ("coral", 0xff7f50),
+    ("cornflowerblue", 0x6495ed),
+    ("cornsilk", 0xfff8dc),
+    ("crimson", 0xdc143c),
+    ("cyan", 0x00ffff),
+    ("darkblue", 0x00008b),
+    ("darkcyan", 0x008b8b),
+    ("darkgoldenrod", 0xb8860b),
+    ("darkgray", 0xa9a9a9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xa9a9a9),
+    ("darkkhaki", 0xbdb76b),
+    ("darkmagenta", 0x8b008b),
+    ("darkolivegreen", 0x556b2f),
+    ("darkorange", 0xff8c00),
+    ("darkorchid", 0x9932cc),
+    ("darkred", 0x8b0000),
+    ("darksalmon", 0xe9967a),
+    ("darkseagreen", 0x8fbc8f),
+    ("darkslateblue", 0x483d8b),
+    ("darkslategray", 0x2f4f4f),
+    ("darkslategrey", 0x2f4f4f),
+    ("darkturquoise", 0x00ced1),
+    ("darkviolet", 0x9400d3),
+    ("deeppink", 0xff1493),
+    ("deepskyblue", 0x00bfff),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1e90ff),
+    ("firebrick", 0xb22222),
+    ("floralwhite", 0xfffaf0),
+    ("forestgreen", 0x228b22),
+    ("fuchsia", 0xff00ff),
+    ("gainsboro", 0xdcdcdc),
+    ("ghostwhite", 0xf8f8ff),
+    ("gold", 0xffd700),
+    ("goldenrod", 0xdaa520),
+    ("gray", 0x808080),
+    ("green", 0x008000),
+    ("greenyellow", 0xadff2f),
+    ("grey", 0x808080),
+    ("honeydew", 0xf0fff0),
+    ("hotpink", 0xff69b4),
+    ("indianred", 0xcd5c5c),
+    ("indigo", 0x4b0082),
+    ("ivory", 0xfffff0),
+    ("khaki", 0xf0e68c),
+    ("lavender", 0xe6e6fa),
+    ("lavenderblush", 0xfff0f5),
+    ("lawngreen", 0x7cfc00),
+    ("lemonchiffon", 0xfffacd),
+    ("lightblue", 0xadd8e6),
+    ("lightcoral", 0xf08080),
+    ("lightcyan", 0xe0ffff),
+    ("lightgoldenrodyellow", 0xfafad2),
+    ("lightgray", 0xd3d3d3),
+    ("lightgreen", 0x90ee90),
+    ("lightgrey", 0xd3d3d3),
+    ("lightpink", 0xffb6c1),
+    ("lightsalmon", 0xffa07a),
+    ("lightseagreen", 0x20b2aa),
+    ("lightskyblue", 0x87cefa),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xb0c4de),
+    ("lightyellow", 0xffffe0),
+    ("lime", 0x00ff00),
+    ("limegreen", 0x32cd32),
+    ("linen", 0xfaf0e6),
+    ("magenta", 0xff00ff),
+    ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66cdaa),
+    ("mediumblue", 0x0000cd),
+    ("mediumorchid", 0xba55d3),
+    ("mediumpurple", 0x9370db),
+    ("mediumseagreen", 0x3cb371),
+    ("mediumslateblue", 0x7b68ee),
+    ("mediumspringgreen", 0x00fa9a),
+    ("mediumturquoise", 0x48d1cc),
+    ("mediumvioletred", 0xc71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xf5fffa),
+    ("mistyrose", 0xffe4e1),
+    ("moccasin", 0xffe4b5),
+    ("navajowhite", 0xffdead),
+    ("navy", 0x000080),
+    ("oldlace", 0xfdf5e6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6b8e23),
+    ("orange", 0xffa500),
+    ("orangered", 0xff4500),
+    ("orchid", 0xda70d6),
+    ("palegoldenrod", 0xeee8aa),
+    ("palegreen", 0x98fb98),
+    ("paleturquoise", 0xafeeee),
+    ("palevioletred", 0xdb7093),
+    ("papayawhip", 0xffefd5),
+    ("peachpuff", 0xffdab9),
+    ("peru", 0xcd853f),
+    ("pink", 0xffc0cb),
+    ("plum", 0xdda0dd),
+    ("powderblue", 0xb0e0e6),
+    ("purple", 0x800080),
+    ("rebeccapurple", 0x663399),
+    ("red", 0xff0000),
+    ("rosybrown", 0xbc8f8f),
+    ("royalblue", 0x4169e1),
+    ("saddlebrown", 0x8b4513),
+    ("salmon", 0xfa8072),
+    ("sandybrown", 0xf4a460),
+    ("seagreen", 0x2e8b57),
+    ("seashell", 0xfff5ee),
+    ("sienna", 0xa0522d),
+    ("silver", 0xc0c0c0),
+    ("skyblue", 0x87ceeb),
+    ("slateblue", 0x6a5acd),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xfffafa),
+    ("springgreen", 0x00ff7f),
+    ("steelblue", 0x4682b4),
+    ("tan", 0xd2b48c),
+    ("teal", 0x008080),
+    ("thistle", 0xd8bfd8),
+    ("tomato", 0xff6347),
+    ("turquoise", 0x40e0d0),
+    ("violet", 0xee82ee),
+    ("wheat", 0xf5deb3),
+    ("white", 0xffffff),
+    ("whitesmoke", 0xf5f5f5),
+    ("yellow", 0xffff00),
+    ("yellowgreen", 0x9acd32),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_color(value: &str, expected: Option<[f32; 4]>) {
+        let color = parse_color(value).map(|c| [c.r, c.g, c.b, c.a]);
+        match (color, expected) {
+            (Some(color), Some(expected)) => assert!(
+                color
+                    .iter()
+                    .zip(expected)
+                    .all(|(a, b)| (a - b).abs() < 1e-3),
+                "{value}: {color:?} != {expected:?}"
+            ),
+            (color, expected) => assert_eq!(color, expected, "{value}"),
+        }
+    }
+
+    #[test]
+    fn hex() {
+        assert_color("#f00", Some([1.0, 0.0, 0.0, 1.0]));
+        assert_color("#0f08", Some([0.0, 1.0, 0.0, 0x88 as f32 / 255.0]));
+        assert_color("#0000ff", Some([0.0, 0.0, 1.0, 1.0]));
+        assert_color("#FF000080", Some([1.0, 0.0, 0.0, 128.0 / 255.0]));
+        assert_color("#ff00", Some([1.0, 1.0, 0.0, 0.0]));
+    }
+
+    #[test]
+    fn rgb() {
+        let orange = Some([1.0, 0.5, 0.0, 1.0]);
+        assert_color("rgb(255, 127.5, 0)", orange);
+        assert_color("rgb(255 127.5 0)", orange);
+        assert_color("rgb(100%, 50%, 0%)", orange);
+        assert_color("rgb(100% 50% 0%)", orange);
+        assert_color("rgba(255, 127.5, 0, 0.5)", Some([1.0, 0.5, 0.0, 0.5]));
+        assert_color("rgb(255 127.5 0 / 50%)", Some([1.0, 0.5, 0.0, 0.5]));
+        assert_color("  RGB( 255 , 127.5 , 0 )  ", orange);
+    }
+
+    #[test]
+    fn hsl() {
+        let red = Some([1.0, 0.0, 0.0, 1.0]);
+        assert_color("hsl(0, 100%, 50%)", red);
+        assert_color("hsl(360 100% 50%)", red);
+        assert_color("hsl(120deg, 100%, 25%)", Some([0.0, 0.5, 0.0, 1.0]));
+        assert_color("hsl(0.5turn 100% 50%)", Some([0.0, 1.0, 1.0, 1.0]));
+        assert_color("hsla(240, 100%, 50%, 0.25)", Some([0.0, 0.0, 1.0, 0.25]));
+        assert_color("hsl(240 100% 50% / 25%)", Some([0.0, 0.0, 1.0, 0.25]));
+        assert_color("hsl(0, 0%, 100%)", Some([1.0, 1.0, 1.0, 1.0]));
+    }
+
+    #[test]
+    fn clamping() {
+        assert_color("rgb(300, -20, 0)", Some([1.0, 0.0, 0.0, 1.0]));
+        assert_color("rgb(150%, 0%, -50%)", Some([1.0, 0.0, 0.0, 1.0]));
+        assert_color("rgba(0, 0, 0, 2)", Some([0.0, 0.0, 0.0, 1.0]));
+        assert_color("rgb(0 0 0 / -10%)", Some([0.0, 0.0, 0.0, 0.0]));
+        assert_color("hsl(-120, 200%, 50%)", Some([0.0, 0.0, 1.0, 1.0]));
+    }
+
+    #[test]
+    fn keywords() {
+        assert_color("rebeccapurple", Some([0.4, 0.2, 0.6, 1.0]));
+        assert_color("Red", Some([1.0, 0.0, 0.0, 1.0]));
+        assert_color("transparent", Some([0.0, 0.0, 0.0, 0.0]));
+        assert_color("currentColor", Some([0.0, 0.0, 0.0, 1.0]));
+        let white = ColorF::WHITE;
+        assert_eq!(parse_color_with_current("currentcolor", white), Some(white));
+    }
+
+    #[test]
+    fn garbage() {
+        for value in [
+            "",
+            "#",
+            "#ff",
+            "#fffff",
+            "#ggg",
+            "#ffé",
+            "rgb(",
+            "rgb()",
+            "rgb(1, 2)",
+            "rgb(1, 2, 3, 4, 5)",
+            "rgb(1 2 3 4)",
+            "rgb(a, b, c)",
+            "rgb(1, 2, 3",
+            "rgb(nan, 0, 0)",
+            "rgb(inf, 0, 0)",
+            "hsl(0, 100%, 50%, x)",
+            "hsl(1foo, 100%, 50%)",
+            "cmyk(0, 0, 0, 0)",
+            "notacolor",
+        ] {
+            assert_color(value, None);
+        }
+    }
+}
@@ -0,0 +1,6 @@
+mod color;
+pub use color::{parse_color, parse_color_with_current};
+mod stylesheet;
+pub use stylesheet::{Keyframe, PseudoClass, Stylesheet};
+mod user_agent;
+pub use user_agent::USER_AGENT_CSS;
@@ -0,0 +1,372 @@
+use std::cmp::Ordering;
+
+use dioxus_native_core::node_ref::NodeView;
+use fxhash::FxHashMap;
+
+/// A set of css rules with simple selectors (tag, class and id, no combinators).
+#[derive(Clone, Default, Debug)]
+pub struct Stylesheet {
+    rules: Vec<Rule>,
+    // rules are indexed by their most specific part so matching a node only
+    // has to look at the rules that can possibly match it
+    by_id: FxHashMap<String, Vec<usize>>,
+    by_class: FxHashMap<String, Vec<usize>>,
+    by_tag: FxHashMap<String, Vec<usize>>,
+    universal: Vec<usize>,
+    keyframes: FxHashMap<String, Vec<Keyframe>>,
+}
+
+/// A keyframe of an `@keyframes` rule.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Keyframe {
+    /// The position of the keyframe in the animation, from 0 to 1.
+    pub offset: f32,
+    pub declarations: Vec<(String, String)>,
+}
+
+#[derive(Clone, Debug)]
+struct Rule {
+    selector: Selector,
+    declarations: Vec<(String, String)>,
+    /// User agent rules come before all the others in the cascade.
+    user_agent: bool,
+}
+
+/// The dynamic states of a node that selectors can depend on.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PseudoClass {
+    Hover,
+    Active,
+}
+
+impl PseudoClass {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "hover" => Some(PseudoClass::Hover),
+            "active" => Some(PseudoClass::Active),
+            _ => None,
+        }
+    }
+
+    /// The prefix of inline attributes that only apply in this state, like
+    /// `hover:background-color`.
+    pub(crate) fn prefix(&self) -> &'static str {
+        match self {
+            PseudoClass::Hover => "hover:",
+            PseudoClass::Active => "active:",
+        }
+    }
+}
+
+/// A compound selector like `div.primary#submit`, `*` or `button:hover`.
+#[derive(Clone, Default, Debug)]
+struct Selector {
+    tag: Option<String>,
+    id: Option<String>,
+    classes: Vec<String>,
+    pseudo_class: Option<PseudoClass>,
+}
+
+impl Selector {
+    fn parse(selector: &str) -> Option<Self> {
+        let selector = selector.trim();
+        // a single pseudo class is supported at the end of the selector
+        let (selector, pseudo_class) = match selector.split_once(':') {
+            Some((selector, pseudo_class)) => (selector, Some(PseudoClass::parse(pseudo_class)?)),
+            None => (selector, None),
+        };
+        if selector.is_empty()
+            || selector.contains(|c: char| c.is_whitespace() || "+>~[:".contains(c))
+        {
+            return None;
+        }
+
+        let mut parsed = Selector {
+            pseudo_class,
+            ..Default::default()
+        };
+        let mut rest = selector;
+        let tag_len = rest.find(|c| c == '.' || c == '#').unwrap_or(rest.len());
+        match &rest[..tag_len] {
+            "" | "*" => (),
+            tag => parsed.tag = Some(tag.to_ascii_lowercase()),
+        }
+        rest = &rest[tag_len..];
+
+        while let Some(prefix) = rest.chars().next() {
+            rest = &rest[1..];
+            let len = rest.find(|c| c == '.' || c == '#').unwrap_or(rest.len());
+            let name = &rest[..len];
+            if name.is_empty() {
+                return None;
+            }
+            match prefix {
+                '.' => parsed.classes.push(name.to_string()),
+                _ if parsed.id.is_none() => parsed.id = Some(name.to_string()),
+                _ => return None,
+            }
+            rest = &rest[len..];
+        }
+
+        Some(parsed)
+    }
+
+    fn specificity(&self) -> (usize, usize, usize) {
+        (
+            self.id.is_some() as usize,
+            self.classes.len() + self.pseudo_class.is_some() as usize,
+            self.tag.is_some() as usize,
+        )
+    }
+
+    fn matches(
+        &self,
+        tag: &str,
+        id: Option<&str>,
+        classes: &[&str],
+        pseudo_classes: &[PseudoClass],
+    ) -> bool {
+        self.pseudo_class
+            .map_or(true, |p| pseudo_classes.contains(&p))
+            && self
+                .tag
+                .as_deref()
+                .map_or(true, |t| tag.eq_ignore_ascii_case(t))
+            && self.id.as_deref().map_or(true, |i| id == Some(i))
+            && self.classes.iter().all(|c| classes.contains(&c.as_str()))
+    }
+}
+
+impl Stylesheet {
+    pub fn parse(css: &str) -> Self {
+        let mut stylesheet = Self::default();
+        stylesheet.add(css);
+        stylesheet
+    }
+
+    /// Parse the css and append its rules to the stylesheet. Rules that can't
+    /// be parsed are skipped.
+    pub fn add(&mut self, css: &str) {
+        self.add_rules(css, false);
+    }
+
+    /// Add default styles that any other rule or inline style overrides,
+    /// regardless of specificity.
+    pub fn add_user_agent(&mut self, css: &str) {
+        self.add_rules(css, true);
+    }
+
+    fn add_rules(&mut self, css: &str, user_agent: bool) {
+        let css = strip_comments(css);
+        let mut rest = css.as_str();
+
+        while let Some(open) = rest.find('{') {
+            if let Some(name) = rest[..open].trim().strip_prefix("@keyframes") {
+                let name = name.trim().to_string();
+                let close = match matching_brace(&rest[open..]) {
+                    Some(close) => open + close,
+                    None => {
+                        warn!("Unclosed css block: {}", rest);
+                        break;
+                    }
+                };
+                let keyframes = parse_keyframes(&rest[open + 1..close]);
+                self.add_keyframes(name, keyframes);
+                rest = &rest[close + 1..];
+                continue;
+            }
+
+            let close = match rest[open..].find('}') {
+                Some(close) => open + close,
+                None => {
+                    warn!("Unclosed css block: {}", rest);
+                    break;
+                }
+            };
+            let selectors = &rest[..open];
+            let declarations = parse_declarations(&rest[open + 1..close]);
+            rest = &rest[close + 1..];
+
+            for selector in selectors.split(',') {
+                match Selector::parse(selector) {
+                    Some(selector) => self.push(Rule {
+                        selector,
+                        declarations: declarations.clone(),
+                        user_agent,
+                    }),
+                    None => warn!("Unsupported css selector: {}", selector.trim()),
+                }
+            }
+        }
+    }
+
+    fn push(&mut self, rule: Rule) {
+        let index = self.rules.len();
+        let selector = &rule.selector;
+        if let Some(id) = &selector.id {
+            self.by_id.entry(id.clone()).or_default().push(index);
+        } else if let Some(class) = selector.classes.first() {
+            self.by_class.entry(class.clone()).or_default().push(index);
+        } else if let Some(tag) = &selector.tag {
+            self.by_tag.entry(tag.clone()).or_default().push(index);
+        } else {
+            self.universal.push(index);
+        }
+        self.rules.push(rule);
+    }
+
+    /// Add a named set of keyframes that the `animation` property can use,
+    /// like an `@keyframes` rule. Replaces the keyframes with the same name.
+    pub fn add_keyframes(&mut self, name: impl Into<String>, mut keyframes: Vec<Keyframe>) {
+        keyframes.sort_by(|a, b| a.offset.partial_cmp(&b.offset).unwrap_or(Ordering::Equal));
+        self.keyframes.insert(name.into(), keyframes);
+    }
+
+    /// The keyframes with the name, sorted by offset.
+    pub fn keyframes(&self, name: &str) -> Option<&[Keyframe]> {
+        self.keyframes.get(name).map(Vec::as_slice)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// The declarations of all the rules matching the node, in cascade order:
+    /// less specific rules come first so later declarations override earlier ones.
+    /// Rules with a pseudo class are skipped.
+    pub fn matching(&self, node: &NodeView) -> Vec<(&str, &str)> {
+        self.matching_with(node, &[])
+    }
+
+    /// Like `matching`, but the node is in the given dynamic states.
+    pub fn matching_with(
+        &self,
+        node: &NodeView,
+        pseudo_classes: &[PseudoClass],
+    ) -> Vec<(&str, &str)> {
+        // text nodes are never matched, they inherit from their parent instead
+        let tag = match node.tag() {
+            Some(tag) if !self.is_empty() => tag,
+            _ => return Vec::new(),
+        };
+        let mut id = None;
+        let mut classes = Vec::new();
+        for attr in node.attributes() {
+            match attr.name {
+                "id" => id = attr.value.as_text(),
+                "class" => classes.extend(attr.value.as_text().unwrap_or("").split_whitespace()),
+                _ => (),
+            }
+        }
+
+        let mut matched: Vec<usize> = self.universal.clone();
+        if let Some(rules) = id.and_then(|id| self.by_id.get(id)) {
+            matched.extend(rules);
+        }
+        for class in &classes {
+            if let Some(rules) = self.by_class.get(*class) {
+                matched.extend(rules);
+            }
+        }
+        if let Some(rules) = self.by_tag.get(&tag.to_ascii_lowercase()) {
+            matched.extend(rules);
+        }
+        matched.retain(|i| {
+            self.rules[*i]
+                .selector
+                .matches(tag, id, &classes, pseudo_classes)
+        });
+        matched.sort_unstable_by_key(|i| {
+            let rule = &self.rules[*i];
+            (!rule.user_agent, rule.selector.specificity(), *i)
+        });
+        matched.dedup();
+
+        matched
+            .into_iter()
+            .flat_map(|i| self.rules[i].declarations.iter())
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect()
+    }
+}
+
+fn strip_comments(css: &str) -> String {
+    let mut stripped = String::with_capacity(css.len());
+    let mut rest = css;
+    while let Some(start) = rest.find("/*") {
+        stripped.push_str(&rest[..start]);
+        rest = match rest[start + 2..].find("*/") {
+            Some(end) => &rest[start + 2 + end + 2..],
+            None => "",
+        };
+    }
+    stripped.push_str(rest);
+    stripped
+}
+
+// the position of the brace closing the block that starts at the beginning
+fn matching_brace(block: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in block.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
+// the blocks of an @keyframes rule, like `from { .. } 50% { .. }`
+fn parse_keyframes(block: &str) -> Vec<Keyframe> {
+    let mut keyframes = Vec::new();
+    let mut rest = block;
+    while let Some(open) = rest.find('{') {
+        let close = match rest[open..].find('}') {
+            Some(close) => open + close,
+            None => break,
+        };
+        let declarations = parse_declarations(&rest[open + 1..close]);
+        for selector in rest[..open].split(',') {
+            let offset = match selector.trim() {
+                "from" => Some(0.0),
+                "to" => Some(1.0),
+                s => s
+                    .strip_suffix('%')
+                    .and_then(|p| p.trim().parse::<f32>().ok())
+                    .filter(|p| (0.0..=100.0).contains(p))
+                    .map(|p| p / 100.0),
+            };
+            match offset {
+                Some(offset) => keyframes.push(Keyframe {
+                    offset,
+                    declarations: declarations.clone(),
+                }),
+                None => warn!("Invalid keyframe selector: {}", selector.trim()),
+            }
+        }
+        rest = &rest[close + 1..];
+    }
+    keyframes
+}
+
+fn parse_declarations(block: &str) -> Vec<(String, String)> {
+    block
+        .split(';')
+        .filter_map(|declaration| {
+            let (name, value) = declaration.split_once(':')?;
+            let name = name.trim().to_ascii_lowercase();
+            let value = value.trim().trim_end_matches("!important").trim_end();
+            if name.is_empty() || value.is_empty() {
+                None
+            } else {
+                Some((name, value.to_string()))
+            }
+        })
+        .collect()
+}
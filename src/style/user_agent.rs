@@ -0,0 +1,25 @@
+/// Default styles of the html elements, roughly following the css of browsers.
+pub const USER_AGENT_CSS: &str = r#"
+h1 { font-size: 2em; font-weight: bold; margin-top: 21px; margin-bottom: 21px; }
+h2 { font-size: 1.5em; font-weight: bold; margin-top: 20px; margin-bottom: 20px; }
+h3 { font-size: 1.17em; font-weight: bold; margin-top: 19px; margin-bottom: 19px; }
+h4 { font-weight: bold; margin-top: 21px; margin-bottom: 21px; }
+h5 { font-size: 0.83em; font-weight: bold; margin-top: 22px; margin-bottom: 22px; }
+h6 { font-size: 0.67em; font-weight: bold; margin-top: 25px; margin-bottom: 25px; }
+p { margin-top: 16px; margin-bottom: 16px; }
+
+button, input {
+    padding-top: 1px;
+    padding-bottom: 1px;
+    padding-left: 6px;
+    padding-right: 6px;
+    border-width: 2px;
+}
+button { background-color: #efefef; }
+input { background-color: white; }
+
+strong, b { font-weight: bold; }
+em, i { font-style: italic; }
+code, pre, kbd, samp { font-family: monospace; }
+a { color: #0000ee; text-decoration: underline; }
+"#;
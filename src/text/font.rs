@@ -0,0 +1,344 @@
+use std::cell::{Cell, RefCell};
+
+use fxhash::FxHashMap;
+use webrender::{
+    api::{FontInstanceKey, FontKey, IdNamespace},
+    Transaction,
+};
+
+/// Index of a font face loaded in a [`FontRegistry`].
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct FontId(usize);
+
+/// Vertical metrics of a font at a given size.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct FontMetrics {
+    pub ascent: f32,
+    pub descent: f32,
+    pub line_gap: f32,
+    /// Distance from the baseline to the top of the underline, positive downwards.
+    pub underline_position: f32,
+    pub underline_thickness: f32,
+}
+
+/// A glyph shaped from a run of text, at the size the run was shaped at.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ShapedGlyph {
+    pub index: u32,
+    /// The byte offset in the run of the first character the glyph is drawn
+    /// for. A ligature is drawn for several characters.
+    pub cluster: usize,
+    pub advance: f32,
+    pub x_offset: f32,
+    /// The offset from the baseline, positive downwards.
+    pub y_offset: f32,
+}
+
+struct Face {
+    data: Vec<u8>,
+    index: u32,
+    key: FontKey,
+    weight: u16,
+    style: fontdb::Style,
+    units_per_em: f32,
+    ascender: f32,
+    descender: f32,
+    line_gap: f32,
+    underline: ttf_parser::LineMetrics,
+    // whether the face has a glyph for the characters looked up so far
+    coverage: RefCell<FxHashMap<char, bool>>,
+}
+
+enum PendingResource {
+    Font(FontKey, Vec<u8>, u32),
+    Instance(FontInstanceKey, FontKey, f32),
+}
+
+/// Loads the system fonts and registers the faces and instances used by the
+/// display list with webrender.
+pub struct FontRegistry {
+    db: fontdb::Database,
+    namespace: IdNamespace,
+    next_key: Cell<u32>,
+    faces: RefCell<Vec<Face>>,
+    loaded: RefCell<FxHashMap<fontdb::ID, FontId>>,
+    queries: RefCell<FxHashMap<(String, u16, fontdb::Style), Vec<FontId>>>,
+    // the faces found for the characters the fonts of their text don't have
+    fallbacks: RefCell<FxHashMap<(char, u16, fontdb::Style), Option<FontId>>>,
+    instances: RefCell<FxHashMap<(FontId, u32), FontInstanceKey>>,
+    pending: RefCell<Vec<PendingResource>>,
+}
+
+impl FontRegistry {
+    pub fn new(namespace: IdNamespace) -> Self {
+        let mut db = fontdb::Database::new();
+        db.load_system_fonts();
+        info!("Loaded {} font faces", db.len());
+
+        Self {
+            db,
+            namespace,
+            next_key: Cell::new(0),
+            faces: RefCell::default(),
+            loaded: RefCell::default(),
+            queries: RefCell::default(),
+            fallbacks: RefCell::default(),
+            instances: RefCell::default(),
+            pending: RefCell::default(),
+        }
+    }
+
+    fn next_key(&self) -> u32 {
+        let key = self.next_key.get();
+        self.next_key.set(key + 1);
+        key
+    }
+
+    /// Find the face matching a css `font-family` list, weight and style,
+    /// falling back to any sans-serif face, and load it.
+    pub fn resolve(&self, family: &str, weight: u16, style: fontdb::Style) -> Option<FontId> {
+        self.resolve_families(family, weight, style)
+            .first()
+            .copied()
+    }
+
+    /// Find and load the faces of each family of a css `font-family` list
+    /// that has one, in order and followed by a sans-serif face, matching a
+    /// weight and style. The glyphs missing from a face are taken from the
+    /// next one.
+    pub fn resolve_families(&self, family: &str, weight: u16, style: fontdb::Style) -> Vec<FontId> {
+        let query_key = (family.to_string(), weight, style);
+        if let Some(fonts) = self.queries.borrow().get(&query_key) {
+            return fonts.clone();
+        }
+
+        let mut families: Vec<fontdb::Family> = family
+            .split(',')
+            .map(|f| f.trim().trim_matches(|c| c == '"' || c == '\''))
+            .filter(|f| !f.is_empty())
+            .map(|f| match f {
+                "serif" => fontdb::Family::Serif,
+                "sans-serif" => fontdb::Family::SansSerif,
+                "cursive" => fontdb::Family::Cursive,
+                "fantasy" => fontdb::Family::Fantasy,
+                "monospace" => fontdb::Family::Monospace,
+                name => fontdb::Family::Name(name),
+            })
+            .collect();
+        families.push(fontdb::Family::SansSerif);
+
+        let mut fonts = Vec::new();
+        for family in families {
+            let font = self
+                .db
+                .query(&fontdb::Query {
+                    families: &[family],
+                    weight: fontdb::Weight(weight),
+                    style,
+                    ..Default::default()
+                })
+                .and_then(|id| self.load(id));
+            if let Some(font) = font.filter(|font| !fonts.contains(font)) {
+                fonts.push(font);
+            }
+        }
+        if fonts.is_empty() {
+            fonts.extend(self.db.faces().first().and_then(|face| self.load(face.id)));
+        }
+        if fonts.is_empty() {
+            warn!("No font found for font-family: {}", family);
+        }
+
+        self.queries.borrow_mut().insert(query_key, fonts.clone());
+        fonts
+    }
+
+    /// Find and load a system face that has a glyph for a character, for the
+    /// characters the faces of a `font-family` list don't have, like CJK in a
+    /// latin font. The faces already loaded are tried first, then the faces
+    /// of the closest style and weight.
+    pub fn fallback(&self, c: char, weight: u16, style: fontdb::Style) -> Option<FontId> {
+        let key = (c, weight, style);
+        if let Some(font) = self.fallbacks.borrow().get(&key) {
+            return *font;
+        }
+
+        let loaded = self.faces.borrow().len();
+        let font = (0..loaded)
+            .map(FontId)
+            .filter(|font| {
+                let faces = self.faces.borrow();
+                faces[font.0].weight == weight && faces[font.0].style == style
+            })
+            .find(|font| self.has_glyph(*font, c))
+            .or_else(|| {
+                let mut candidates: Vec<&fontdb::FaceInfo> = self.db.faces().iter().collect();
+                candidates.sort_by_key(|face| {
+                    (
+                        face.style != style,
+                        (face.weight.0 as i32 - weight as i32).abs(),
+                    )
+                });
+                let id = candidates.into_iter().map(|face| face.id).find(|id| {
+                    self.db
+                        .with_face_data(*id, |data, index| {
+                            let parsed = ttf_parser::Face::from_slice(data, index).ok()?;
+                            parsed.glyph_index(c)
+                        })
+                        .flatten()
+                        .is_some()
+                })?;
+                self.load(id)
+            });
+
+        self.fallbacks.borrow_mut().insert(key, font);
+        font
+    }
+
+    fn load(&self, id: fontdb::ID) -> Option<FontId> {
+        if let Some(font) = self.loaded.borrow().get(&id) {
+            return Some(*font);
+        }
+
+        let info = self.db.face(id)?;
+        let (weight, style) = (info.weight.0, info.style);
+        let face = self.db.with_face_data(id, |data, index| {
+            let parsed = match ttf_parser::Face::from_slice(data, index) {
+                Ok(parsed) => parsed,
+                Err(e) => {
+                    error!("Failed to parse font face: {}", e);
+                    return None;
+                }
+            };
+            Some(Face {
+                data: data.to_vec(),
+                index,
+                key: FontKey::new(self.namespace, self.next_key()),
+                weight,
+                style,
+                units_per_em: parsed.units_per_em() as f32,
+                ascender: parsed.ascender() as f32,
+                descender: parsed.descender() as f32,
+                line_gap: parsed.line_gap() as f32,
+                underline: parsed
+                    .underline_metrics()
+                    .unwrap_or(ttf_parser::LineMetrics {
+                        position: -(parsed.units_per_em() as i16) / 10,
+                        thickness: parsed.units_per_em() as i16 / 20,
+                    }),
+                coverage: RefCell::default(),
+            })
+        })??;
+
+        self.pending.borrow_mut().push(PendingResource::Font(
+            face.key,
+            face.data.clone(),
+            face.index,
+        ));
+
+        let mut faces = self.faces.borrow_mut();
+        let font = FontId(faces.len());
+        faces.push(face);
+        self.loaded.borrow_mut().insert(id, font);
+        Some(font)
+    }
+
+    /// The vertical metrics of the font at `size`.
+    pub fn metrics(&self, font: FontId, size: f32) -> FontMetrics {
+        let faces = self.faces.borrow();
+        let face = &faces[font.0];
+        let scale = size / face.units_per_em;
+        FontMetrics {
+            ascent: face.ascender * scale,
+            descent: -face.descender * scale,
+            line_gap: face.line_gap * scale,
+            // fonts store the position of the center of the line, upwards
+            underline_position: -(face.underline.position as f32 * scale)
+                - face.underline.thickness as f32 * scale / 2.0,
+            underline_thickness: face.underline.thickness as f32 * scale,
+        }
+    }
+
+    /// Returns true if the font has a glyph for a character.
+    pub fn has_glyph(&self, font: FontId, c: char) -> bool {
+        let faces = self.faces.borrow();
+        let face = &faces[font.0];
+
+        let cached = face.coverage.borrow().get(&c).copied();
+        cached.unwrap_or_else(|| {
+            let covered = ttf_parser::Face::from_slice(&face.data, face.index)
+                .ok()
+                .and_then(|parsed| parsed.glyph_index(c))
+                .is_some();
+            face.coverage.borrow_mut().insert(c, covered);
+            covered
+        })
+    }
+
+    /// Shape a run of text of a single font and direction at `size` with the
+    /// ligatures, kerning and mark positioning of the font. The glyphs are in
+    /// visual order, so the clusters of a right to left run decrease.
+    pub fn shape(&self, font: FontId, text: &str, size: f32, rtl: bool) -> Vec<ShapedGlyph> {
+        let faces = self.faces.borrow();
+        let face = &faces[font.0];
+        let parsed = match rustybuzz::Face::from_slice(&face.data, face.index) {
+            Some(parsed) => parsed,
+            None => {
+                error!("Failed to parse font face for shaping");
+                return Vec::new();
+            }
+        };
+
+        let mut buffer = rustybuzz::UnicodeBuffer::new();
+        buffer.push_str(text);
+        buffer.set_direction(if rtl {
+            rustybuzz::Direction::RightToLeft
+        } else {
+            rustybuzz::Direction::LeftToRight
+        });
+        let shaped = rustybuzz::shape(&parsed, &[], buffer);
+
+        let scale = size / face.units_per_em;
+        shaped
+            .glyph_infos()
+            .iter()
+            .zip(shaped.glyph_positions())
+            .map(|(info, position)| ShapedGlyph {
+                index: info.glyph_id,
+                cluster: info.cluster as usize,
+                advance: position.x_advance as f32 * scale,
+                x_offset: position.x_offset as f32 * scale,
+                // fonts go upwards
+                y_offset: -position.y_offset as f32 * scale,
+            })
+            .collect()
+    }
+
+    /// The webrender instance of the font at `size`, registering it if needed.
+    pub fn instance(&self, font: FontId, size: f32) -> FontInstanceKey {
+        *self
+            .instances
+            .borrow_mut()
+            .entry((font, size.to_bits()))
+            .or_insert_with(|| {
+                let key = FontInstanceKey::new(self.namespace, self.next_key());
+                let font_key = self.faces.borrow()[font.0].key;
+                self.pending
+                    .borrow_mut()
+                    .push(PendingResource::Instance(key, font_key, size));
+                key
+            })
+    }
+
+    /// Add the fonts and instances registered since the last flush to the transaction.
+    pub fn flush(&self, txn: &mut Transaction) {
+        for resource in self.pending.borrow_mut().drain(..) {
+            match resource {
+                PendingResource::Font(key, data, index) => txn.add_raw_font(key, data, index),
+                PendingResource::Instance(key, font_key, size) => {
+                    txn.add_font_instance(key, font_key, size, None, None, Vec::new())
+                }
+            }
+        }
+    }
+}
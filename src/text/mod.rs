@@ -0,0 +1,286 @@
+mod font;
+pub use font::{FontId, FontMetrics, FontRegistry, ShapedGlyph};
+
+use std::ops::Range;
+
+use unicode_bidi::{BidiInfo, Level};
+use unicode_linebreak::linebreaks;
+
+use crate::state::{Direction, FontStyle, LineHeight, TextAlign, TextStyle, WhiteSpace};
+
+/// A glyph positioned relative to the start of its line.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Glyph {
+    /// The font the glyph is taken from.
+    pub font: FontId,
+    pub index: u32,
+    pub x: f32,
+    /// The offset from the baseline, positive downwards, like the one of a
+    /// mark placed above its base.
+    pub y: f32,
+    /// The number of word gaps before the glyph, used to justify the line.
+    pub gap: usize,
+}
+
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Line {
+    pub glyphs: Vec<Glyph>,
+    pub width: f32,
+    /// The number of gaps between the words of the line.
+    pub gaps: usize,
+}
+
+impl Line {
+    /// The x position of each glyph when the line is aligned in a box of
+    /// `width`. The last line of a justified paragraph is aligned to the left,
+    /// and so are the lines aligned to the start. See
+    /// [`TextLayout::line_align`] for the alignment of right to left text.
+    pub fn align(
+        &self,
+        align: TextAlign,
+        width: f32,
+        last: bool,
+    ) -> impl Iterator<Item = f32> + '_ {
+        let (offset, extra_per_gap) = self.alignment(align, width, last);
+        self.glyphs
+            .iter()
+            .map(move |g| offset + g.x + g.gap as f32 * extra_per_gap)
+    }
+
+    /// The start and end x position of the aligned line.
+    pub fn aligned_span(&self, align: TextAlign, width: f32, last: bool) -> (f32, f32) {
+        let (offset, extra_per_gap) = self.alignment(align, width, last);
+        (
+            offset,
+            offset + self.width + self.gaps as f32 * extra_per_gap,
+        )
+    }
+
+    // The offset of the line and the extra space added to each word gap.
+    fn alignment(&self, align: TextAlign, width: f32, last: bool) -> (f32, f32) {
+        let free = (width - self.width).max(0.0);
+        match align {
+            TextAlign::Left | TextAlign::Start => (0.0, 0.0),
+            TextAlign::Right | TextAlign::End => (free, 0.0),
+            TextAlign::Center => (free / 2.0, 0.0),
+            TextAlign::Justify if last || self.gaps == 0 => (0.0, 0.0),
+            TextAlign::Justify => (0.0, free / self.gaps as f32),
+        }
+    }
+}
+
+/// The result of laying out the text of a text node.
+#[derive(Clone, PartialEq, Debug)]
+pub struct TextLayout {
+    /// The first font of the `font-family` list, whose metrics position the
+    /// lines. None if there is no font at all.
+    pub font: Option<FontId>,
+    pub lines: Vec<Line>,
+    /// Distance from the top of a line to its baseline, including half of
+    /// the extra space added by `line-height`.
+    pub ascent: f32,
+    pub line_height: f32,
+    /// Whether the text is right to left, which aligns it to the right by
+    /// default.
+    pub rtl: bool,
+}
+
+impl TextLayout {
+    pub fn width(&self) -> f32 {
+        self.lines.iter().map(|l| l.width).fold(0.0, f32::max)
+    }
+
+    pub fn height(&self) -> f32 {
+        self.lines.len() as f32 * self.line_height
+    }
+
+    /// The physical alignment of a line: `start` and `end` depend on the
+    /// direction of the text, like the last line of justified text.
+    pub fn line_align(&self, align: TextAlign, last: bool) -> TextAlign {
+        let (start, end) = if self.rtl {
+            (TextAlign::Right, TextAlign::Left)
+        } else {
+            (TextAlign::Left, TextAlign::Right)
+        };
+        match align {
+            TextAlign::Start => start,
+            TextAlign::End => end,
+            TextAlign::Justify if last => start,
+            align => align,
+        }
+    }
+}
+
+/// Lay out the text of a text node with its resolved style. The lines break
+/// where the text doesn't fit in `max_width`, if any, unless its
+/// `white-space` is `nowrap`.
+pub fn layout_text(
+    fonts: &FontRegistry,
+    text: &str,
+    style: &TextStyle,
+    max_width: Option<f32>,
+) -> TextLayout {
+    let font_style = match style.font_style {
+        FontStyle::Normal => fontdb::Style::Normal,
+        FontStyle::Italic => fontdb::Style::Italic,
+        FontStyle::Oblique => fontdb::Style::Oblique,
+    };
+    let families = fonts.resolve_families(&style.font_family, style.font_weight, font_style);
+    let font = match families.first() {
+        Some(font) => *font,
+        None => {
+            return TextLayout {
+                font: None,
+                lines: Vec::new(),
+                ascent: 0.0,
+                line_height: 0.0,
+                rtl: false,
+            }
+        }
+    };
+    let metrics = fonts.metrics(font, style.font_size);
+
+    // each character is taken from the first font that has it, then from a
+    // system font, or else drawn with the missing glyph of the first font
+    let font_of = |c| {
+        families
+            .iter()
+            .copied()
+            .find(|font| fonts.has_glyph(*font, c))
+            .or_else(|| fonts.fallback(c, style.font_weight, font_style))
+            .unwrap_or(font)
+    };
+
+    // collapse whitespace like css `white-space: normal`
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    // the bidi levels of the text, with the direction of the text or the one
+    // of its first strong character
+    let level = style.direction.map(|direction| match direction {
+        Direction::Ltr => Level::ltr(),
+        Direction::Rtl => Level::rtl(),
+    });
+    let bidi = BidiInfo::new(&text, level);
+    let rtl = bidi
+        .paragraphs
+        .first()
+        .map_or(style.direction == Some(Direction::Rtl), |p| {
+            p.level.is_rtl()
+        });
+
+    // the text is shaped once, in items of a single font and direction, and
+    // the lines take the glyphs of their part of it
+    let mut items: Vec<Item> = Vec::new();
+    for (i, c) in text.char_indices() {
+        let (font, rtl) = (font_of(c), bidi.levels[i].is_rtl());
+        let end = i + c.len_utf8();
+        match items.last_mut() {
+            Some(item) if item.font == font && item.rtl == rtl => item.range.end = end,
+            _ => items.push(Item {
+                font,
+                rtl,
+                range: i..end,
+                glyphs: Vec::new(),
+            }),
+        }
+    }
+    // the advance of the glyphs of each cluster, at its first byte
+    let mut advances = vec![0.0; text.len()];
+    for item in &mut items {
+        let run = &text[item.range.clone()];
+        item.glyphs = fonts.shape(item.font, run, style.font_size, item.rtl);
+        for glyph in &mut item.glyphs {
+            glyph.cluster += item.range.start;
+            advances[glyph.cluster] += glyph.advance;
+        }
+    }
+
+    // wrap the text at the break opportunities of the unicode line breaking
+    // algorithm, in logical order: after the spaces, but also after hyphens
+    // or between CJK characters. The spaces the lines break at are dropped,
+    // and a word longer than the whole line overflows it
+    let max_width = max_width.filter(|_| style.white_space == WhiteSpace::Normal);
+    let mut ranges = Vec::new();
+    // the line ends with the content of the segment before the last break
+    let (mut start, mut end, mut width, mut last_break) = (0, 0, 0.0, 0);
+    for (position, _) in linebreaks(&text) {
+        let content_end = last_break + text[last_break..position].trim_end_matches(' ').len();
+        let content_width: f32 = advances[last_break..content_end].iter().sum();
+        if last_break != start
+            && max_width.map_or(false, |max| width + content_width > max + WRAP_TOLERANCE)
+        {
+            ranges.push(start..end);
+            start = last_break;
+            width = 0.0;
+        }
+        width += advances[last_break..position].iter().sum::<f32>();
+        end = content_end;
+        last_break = position;
+    }
+    ranges.push(start..end);
+
+    // each line is reordered to the visual order of the bidi algorithm
+    let lines = ranges
+        .into_iter()
+        .map(|range| {
+            let runs = match bidi.paragraphs.first() {
+                Some(paragraph) => bidi.visual_runs(paragraph, range).1,
+                None => Vec::new(),
+            };
+            let mut line = Line::default();
+            for run in runs.into_iter().filter(|run| !run.is_empty()) {
+                let mut run_items: Vec<&Item> = items
+                    .iter()
+                    .filter(|item| item.range.start < run.end && run.start < item.range.end)
+                    .collect();
+                if bidi.levels[run.start].is_rtl() {
+                    run_items.reverse();
+                }
+                for item in run_items {
+                    let glyphs = item.glyphs.iter().filter(|g| run.contains(&g.cluster));
+                    for glyph in glyphs {
+                        line.glyphs.push(Glyph {
+                            font: item.font,
+                            index: glyph.index,
+                            x: line.width + glyph.x_offset,
+                            y: glyph.y_offset,
+                            gap: line.gaps,
+                        });
+                        line.width += glyph.advance;
+                        if text[glyph.cluster..].starts_with(' ') {
+                            line.gaps += 1;
+                        }
+                    }
+                }
+            }
+            line
+        })
+        .collect();
+
+    let content_height = metrics.ascent + metrics.descent;
+    let line_height = match style.line_height {
+        LineHeight::Normal => content_height + metrics.line_gap,
+        LineHeight::Number(n) => n * style.font_size,
+        LineHeight::Points(p) => p,
+    };
+
+    TextLayout {
+        font: Some(font),
+        lines,
+        ascent: metrics.ascent + (line_height - content_height) / 2.0,
+        line_height,
+        rtl,
+    }
+}
+
+/// The text is laid out again at its measured width when it is painted, so
+/// the rounding of the widths mustn't break its lines differently.
+const WRAP_TOLERANCE: f32 = 0.01;
+
+// A part of the text shaped with a single font and direction.
+struct Item {
+    font: FontId,
+    rtl: bool,
+    range: Range<usize>,
+    glyphs: Vec<ShapedGlyph>,
+}
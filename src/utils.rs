@@ -21,3 +21,29 @@ impl RectBuilder for (i32, i32) {
         )
     }
 }
+
+/// Split a value on the separators that are not between parentheses, like
+/// the arguments of a css function that contain colors such as
+/// `rgb(0, 0, 0)`. Empty parts are skipped.
+pub(crate) fn split_outside_parens(value: &str, is_separator: impl Fn(char) -> bool) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in value.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            c if depth == 0 && is_separator(c) => {
+                if !value[start..i].trim().is_empty() {
+                    parts.push(value[start..i].trim());
+                }
+                start = i + c.len_utf8();
+            }
+            _ => (),
+        }
+    }
+    if !value[start..].trim().is_empty() {
+        parts.push(value[start..].trim());
+    }
+    parts
+}
@@ -1,101 +1,168 @@
-use std::{cell::RefCell, rc::Rc, sync::Arc};
+use std::{
+    any::Any,
+    cell::{Cell, RefCell},
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
 use anymap::AnyMap;
-use crossbeam_channel::Receiver;
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
 use dioxus::{
     core::{ElementId, EventPriority, SchedulerMsg, UserEvent},
-    events::KeyboardData,
-    html::input_data::keyboard_types::{Code, Key, Location, Modifiers},
+    events::{
+        ClipboardData, FocusData, KeyboardData, MouseData, PointerData, TouchData, WheelData,
+    },
+    html::{
+        geometry::WheelDelta,
+        input_data::{
+            keyboard_types::{Code, Key, Location, Modifiers},
+            MouseButton as DomMouseButton, MouseButtonSet,
+        },
+    },
     prelude::{Component, VirtualDom},
 };
 use dioxus_native_core::real_dom::RealDom;
+use fxhash::{FxHashMap, FxHashSet};
 use gleam::gl;
 use glutin::{
-    event::{ElementState, Event, StartCause, VirtualKeyCode, WindowEvent},
+    dpi::{PhysicalPosition, PhysicalSize, Position},
+    error::NotSupportedError,
+    event::{
+        ElementState, Event, MouseButton, StartCause, TouchPhase, VirtualKeyCode, WindowEvent,
+    },
     event_loop::{EventLoop, EventLoopProxy},
-    window::{WindowBuilder, WindowId},
-    NotCurrent, PossiblyCurrent, WindowedContext,
+    window::{CursorIcon, WindowBuilder, WindowId},
+    NotCurrent, PossiblyCurrent, RawContext, WindowedContext,
 };
+use image::RgbaImage;
 use taffy::{
     prelude::{Number, Size},
     Taffy,
 };
 use webrender::{
-    api::{units::DeviceIntSize, *},
+    api::{
+        units::{DeviceIntRect, DeviceIntSize, LayoutPoint, LayoutSize, LayoutVector2D},
+        *,
+    },
     DebugFlags, RenderApi, Renderer, ShaderPrecacheFlags, Transaction,
 };
 
-use crate::state::{FocusState, NodeState};
+use crate::{
+    config::{Accelerator, FrameStats, WindowConfig},
+    context::{
+        root_element, EventTarget, PointerCaptureRequest, ScrollRequest, Touch, TouchList,
+        WindowContext,
+    },
+    keyboard::translate_key,
+    mouse::{mouse_data, pointer_data, translate_button, wheel_delta, MOUSE_POINTER_ID},
+    state::{
+        ancestor_chain, compute_layout, cursor_icon, element_layouts, focusable_ancestor,
+        focusable_at, hit_test, is_disabled, is_displayed, needs_layout, remove_layout_nodes,
+        scroll_by, scroll_into_view, scroll_to, update_pseudo_class, BoxSizing, FocusState,
+        LayoutContext, NodeState, PreventableEvent, TextMeasures,
+    },
+    style::{PseudoClass, Stylesheet},
+    text::FontRegistry,
+};
 
 #[derive(Debug)]
 pub struct Window {
     id: WindowId,
-    event_tx: crossbeam_channel::Sender<Event<'static, Redraw>>,
+    /// The platform window. The gl context lives in the task but the window
+    /// stays on the main thread so it can be updated from the event loop.
+    window: Arc<glutin::window::Window>,
+    event_tx: crossbeam_channel::Sender<TaskMessage>,
+    /// The nearest instant the task asked to be woken up at.
+    wake_at: Cell<Option<Instant>>,
+    /// Set once the task is done, after a close request or Escape.
+    closed: Cell<bool>,
 }
 
 impl Window {
     /// Spawn a Window task in the background and return a Window instance.
-    pub fn new(root: Component<()>, event_loop: &EventLoop<Redraw>) -> Self {
+    pub fn new<P: 'static + Send>(
+        root: Component<P>,
+        props: P,
+        mut cfg: WindowConfig,
+        event_loop: &EventLoop<RendererEvent>,
+    ) -> Self {
         // Create glutin's WindowedContext
-        let window_builder = WindowBuilder::new()
-            // .with_decorations(false)
-            .with_transparent(true);
+        let mut window_builder = WindowBuilder::new()
+            .with_title(cfg.title.as_str())
+            .with_transparent(cfg.transparent)
+            .with_decorations(cfg.decorations)
+            .with_always_on_top(cfg.always_on_top);
+        if let Some(size) = cfg.inner_size {
+            window_builder = window_builder.with_inner_size(size);
+        }
+        if let Some(position) = cfg.position {
+            window_builder = window_builder.with_position(position);
+        }
+        if let Some(f) = cfg.window_builder.take() {
+            window_builder = f(window_builder);
+        }
         let windowed_context = glutin::ContextBuilder::new()
             .with_gl(glutin::GlRequest::GlThenGles {
                 opengl_version: (3, 2),
                 opengles_version: (3, 0),
             })
+            // the framebuffer needs an alpha channel to show what is behind
+            // a transparent window
+            .with_pixel_format(24, if cfg.transparent { 8 } else { 0 })
             .build_windowed(window_builder, &event_loop)
             .unwrap();
         let proxy = event_loop.create_proxy();
 
-        Window::spawn(root, windowed_context, proxy)
+        Window::spawn(root, props, cfg, windowed_context, proxy)
     }
 
     /// Spawn a Window task in the background and return a Window instance.
-    pub fn spawn(
-        root: Component<()>,
+    pub fn spawn<P: 'static + Send>(
+        root: Component<P>,
+        props: P,
+        mut cfg: WindowConfig,
         windowed_context: WindowedContext<NotCurrent>,
-        proxy: EventLoopProxy<Redraw>,
+        proxy: EventLoopProxy<RendererEvent>,
     ) -> Self {
         let id = windowed_context.window().id();
         let (event_tx, event_rx) = crossbeam_channel::unbounded();
+        #[cfg(feature = "hot-reload")]
+        let hot_reload_tx = event_tx.clone();
+        // Safety: the task holds a reference to the window and drops the
+        // context before it
+        let (gl_context, window) = unsafe { windowed_context.split() };
+        let window = Arc::new(window);
+        let task_window = window.clone();
 
         // Spawn and run a WindowTask
         std::thread::spawn(move || {
+            let window = task_window;
             // Create gl Api
-            let windowed_context = unsafe { windowed_context.make_current().unwrap() };
-            let gl = match windowed_context.get_api() {
-                glutin::Api::OpenGl => unsafe {
-                    gl::GlFns::load_with(|symbol| {
-                        windowed_context.get_proc_address(symbol) as *const _
-                    })
-                },
-                glutin::Api::OpenGlEs => unsafe {
-                    gl::GlesFns::load_with(|symbol| {
-                        windowed_context.get_proc_address(symbol) as *const _
-                    })
-                },
-                glutin::Api::WebGl => unimplemented!(),
-            };
+            let gl_context = unsafe { gl_context.make_current().unwrap() };
+            let gl = load_gl(&gl_context);
 
             info!("OpenGL version {}", gl.get_string(gl::VERSION));
-            let device_pixel_ratio = windowed_context.window().scale_factor() as f32;
-            info!("Device pixel ratio: {}", device_pixel_ratio);
+            let scale_factor = window.scale_factor();
+            info!("Scale factor: {}", scale_factor);
 
             // Setup options for Webrender
             let debug_flags = DebugFlags::ECHO_DRIVER_MESSAGES | DebugFlags::TEXTURE_CACHE_DBG;
             let opts = webrender::WebRenderOptions {
                 resource_override_path: None,
                 precache_flags: ShaderPrecacheFlags::FULL_COMPILE,
-                clear_color: ColorF::new(0.3, 0.0, 0.0, 0.5),
+                clear_color: cfg.renderer_clear_color(),
                 debug_flags,
                 //allow_texture_swizzling: false,
                 ..Default::default()
             };
-            let size = windowed_context.window().inner_size();
+            let size = window.inner_size();
             let device_size = DeviceIntSize::new(size.width as i32, size.height as i32);
-            let notifier = Box::new(Notifier::new(id, proxy.clone()));
+            let frame_ready = Arc::new(AtomicBool::new(false));
+            let notifier = Box::new(Notifier::new(id, proxy.clone(), frame_ready.clone()));
 
             // Create Webrender
             let (renderer, sender) =
@@ -105,7 +172,8 @@ impl Window {
             let epoch = Epoch(0);
             let pipeline_id = PipelineId(0, 0);
 
-            let layout_size = device_size.to_f32() / euclid::Scale::new(device_pixel_ratio);
+            // display lists are in device pixels
+            let layout_size = LayoutSize::new(size.width as f32, size.height as f32);
             let mut txn = Transaction::new();
             let mut builder = DisplayListBuilder::new(pipeline_id);
             builder.begin();
@@ -118,99 +186,333 @@ impl Window {
             // Create Real DOM
             let mut rdom: RealDom<NodeState> = RealDom::new();
 
+            let stretch = Rc::new(RefCell::new(Taffy::new()));
+            let stylesheet = Rc::new(cfg.take_stylesheet());
+            let fonts = Rc::new(FontRegistry::new(api.get_namespace_id()));
+            let mut text_measures = TextMeasures::new(fonts.clone());
+
             // Create Virtual DOM
-            let mut vdom = VirtualDom::new(root);
+            let mut vdom = VirtualDom::new_with_props(root, props);
+            let measurer = text_measures.measurer(&stretch);
+            let context = WindowContext::new(logical_size(size, scale_factor), measurer);
+            vdom.base_scope().provide_context(context.clone());
+            #[cfg(feature = "hot-reload")]
+            crate::hot_reload::init(&vdom, hot_reload_tx);
             let mutations = vdom.rebuild();
+            let mut state = WindowState::default();
+            state.focus.record_mounted(&mutations);
 
             // Update real dom's nodes
             let to_update = rdom.apply_mutations(vec![mutations]);
-            let stretch = Rc::new(RefCell::new(Taffy::new()));
-            let mut ctx = AnyMap::new();
-            ctx.insert(stretch.clone());
 
             // Update the style and layout
+            let box_sizing = cfg.box_sizing;
+            let ctx = state_context(&stretch, &stylesheet, box_sizing);
             let to_rerender = rdom.update_state(&vdom, to_update, ctx);
-            let size = Size {
-                width: Number::Defined(size.width as f32),
-                height: Number::Defined(size.height as f32),
-            };
-            stretch
-                .borrow_mut()
-                .compute_layout(
-                    rdom[ElementId(rdom.root_id())].state.layout.node.unwrap(),
-                    size,
-                )
-                .unwrap();
-            rdom.traverse_depth_first_mut(|n| {
-                if let Some(node) = n.state.layout.node {
-                    n.state.layout.layout = Some(*stretch.borrow().layout(node).unwrap());
-                }
-            });
+            text_measures.update(
+                &rdom,
+                &mut stretch.borrow_mut(),
+                to_rerender.iter().copied(),
+            );
+            compute_layout(&mut rdom, &stretch, available_space(size, scale_factor));
+            set_layouts(&context, &rdom, &vdom);
+            if let Some(focused) = state.focus.autofocus(&mut rdom) {
+                WindowTask::send_focus_events(&context, None, Some(focused));
+            }
+            // record the first values of the transitioned properties
+            WindowTask::start_animations(
+                &mut state.animating,
+                &mut rdom,
+                to_rerender.iter().copied(),
+            );
             let dirty_nodes = DirtyNodes::Some(to_rerender.into_iter().collect());
 
-            proxy.send_event(Redraw(id)).unwrap();
+            proxy.send_event(RendererEvent::Redraw(id)).unwrap();
 
-            let state = WindowState::default();
             let task = WindowTask {
                 event_rx,
                 proxy,
                 state,
-                windowed_context,
+                gl_context,
+                window,
+                gl,
                 renderer,
-                pipeline_id,
+                frame_ready,
+                builder,
                 document_id,
                 epoch,
                 api,
                 rdom,
                 vdom,
+                context,
                 stretch,
+                stylesheet,
+                box_sizing,
+                frame_interval: cfg.frame_interval,
+                wheel_line_height: cfg.wheel_line_height,
+                close_on_escape: cfg.close_on_escape,
+                accelerators: std::mem::take(&mut cfg.accelerators),
+                on_frame_stats: cfg.on_frame_stats.take(),
+                fonts,
+                text_measures,
                 dirty_nodes,
             };
 
             task.run();
         });
 
-        Self { id, event_tx }
+        Self {
+            id,
+            window,
+            event_tx,
+            wake_at: Cell::new(None),
+            closed: Cell::new(false),
+        }
     }
 
     pub fn id(&self) -> &WindowId {
         &self.id
     }
 
-    pub fn send_event(&self, event: Event<Redraw>) {
+    /// The instant the event loop has to wake up at for the callbacks
+    /// scheduled with
+    /// [`WindowContext::schedule_wake`](crate::WindowContext::schedule_wake),
+    /// if any.
+    pub fn next_wake(&self) -> Option<Instant> {
+        self.wake_at.get()
+    }
+
+    /// Returns true once the window task is done. The window is hidden and
+    /// can be dropped to close it.
+    pub fn is_closed(&self) -> bool {
+        self.closed.get()
+    }
+
+    pub fn send_event(&self, event: Event<RendererEvent>) {
+        // requests from the task that have to run on the main thread
+        match event {
+            Event::UserEvent(RendererEvent::SetCursor(id, icon)) => {
+                if id == self.id {
+                    self.window.set_cursor_icon(icon);
+                }
+                return;
+            }
+            Event::UserEvent(RendererEvent::SetAlwaysOnTop(id, always_on_top)) => {
+                if id == self.id {
+                    self.window.set_always_on_top(always_on_top);
+                }
+                return;
+            }
+            Event::UserEvent(RendererEvent::Close(id)) => {
+                if id == self.id {
+                    self.window.set_visible(false);
+                    self.closed.set(true);
+                }
+                return;
+            }
+            Event::UserEvent(RendererEvent::ScheduleWake(id, at)) => {
+                if id == self.id {
+                    let at = self.wake_at.get().map_or(at, |wake_at| wake_at.min(at));
+                    self.wake_at.set(Some(at));
+                }
+                return;
+            }
+            _ => (),
+        }
+        // the task checks its scheduled callbacks on every event, including
+        // the one waking the event loop
+        if self.wake_at.get().map_or(false, |at| at <= Instant::now()) {
+            self.wake_at.set(None);
+        }
+
+        // the event borrows the new size, so it can't be sent as is
+        if let Event::WindowEvent {
+            window_id,
+            event:
+                WindowEvent::ScaleFactorChanged {
+                    scale_factor,
+                    new_inner_size,
+                },
+        } = &event
+        {
+            if *window_id == self.id {
+                self.event_tx
+                    .send(TaskMessage::ScaleFactorChanged(
+                        *scale_factor,
+                        **new_inner_size,
+                    ))
+                    .unwrap_or_else(|e| error!("{}", e));
+            }
+            return;
+        }
+
         if let Some(event) = event.to_static() {
-            self.event_tx.send(event).unwrap_or_else(|e| {
+            self.event_tx
+                .send(TaskMessage::Event(event))
+                .unwrap_or_else(|e| {
+                    error!("{}", e);
+                });
+        }
+    }
+
+    /// The position of the top left corner of the window, decorations
+    /// included, in physical pixels from the top left corner of the desktop.
+    /// Fails on the platforms that don't expose it, like wayland.
+    pub fn outer_position(&self) -> Result<PhysicalPosition<i32>, NotSupportedError> {
+        self.window.outer_position()
+    }
+
+    /// Move the window, in physical or logical pixels. It is ignored on the
+    /// platforms that don't support it.
+    pub fn set_outer_position(&self, position: impl Into<Position>) {
+        self.window.set_outer_position(position);
+    }
+
+    /// Keep the window above the other windows or not.
+    pub fn set_always_on_top(&self, always_on_top: bool) {
+        self.window.set_always_on_top(always_on_top);
+    }
+
+    /// Dispatch an event to the listeners of the window's VirtualDom directly,
+    /// without an OS event. Useful to drive a UI from tests. The event keeps its
+    /// priority, while the events of the window take theirs from their kind.
+    pub fn dispatch_event(&self, event: UserEvent) {
+        self.event_tx
+            .send(TaskMessage::UserEvent(event))
+            .unwrap_or_else(|e| {
                 error!("{}", e);
             });
+    }
+
+    /// Block until the window task has handled every event sent before, applied
+    /// the pending mutations of the VirtualDom, laid them out and built a
+    /// display list of the result.
+    pub fn sync(&self) {
+        let (done_tx, done_rx) = crossbeam_channel::bounded(1);
+        if let Err(e) = self.event_tx.send(TaskMessage::Sync(done_tx)) {
+            error!("{}", e);
+            return;
+        }
+        let _ = done_rx.recv();
+    }
+
+    /// Run a function with the window's gl handle, for driver queries or
+    /// custom gl. The context is only current on the window task's thread, so
+    /// the function runs there and its result is sent back. Returns None if
+    /// the window task has stopped.
+    pub fn with_gl<R: Send + 'static>(
+        &self,
+        f: impl FnOnce(&dyn gl::Gl) -> R + Send + 'static,
+    ) -> Option<R> {
+        let (result_tx, result_rx) = crossbeam_channel::bounded(1);
+        let f = Box::new(move |gl: &dyn gl::Gl| {
+            let _ = result_tx.send(f(gl));
+        });
+        if let Err(e) = self.event_tx.send(TaskMessage::WithGl(f)) {
+            error!("{}", e);
+            return None;
         }
+        result_rx.recv().ok()
+    }
+
+    /// Read the RGBA pixels of a rect of the last rendered frame, in device
+    /// pixels from the top left corner. Rows are ordered from top to bottom.
+    pub fn read_pixels(&self, rect: DeviceIntRect) -> Vec<u8> {
+        let (pixels_tx, pixels_rx) = crossbeam_channel::bounded(1);
+        if let Err(e) = self.event_tx.send(TaskMessage::ReadPixels(rect, pixels_tx)) {
+            error!("{}", e);
+            return Vec::new();
+        }
+        pixels_rx.recv().unwrap_or_default()
+    }
+
+    /// Capture the last rendered frame, for screenshots and golden image tests.
+    /// It can be saved as a png with `RgbaImage::save`.
+    ///
+    /// Webrender renders with premultiplied alpha, the returned image has
+    /// straight alpha like image files expect.
+    pub fn capture_frame(&self) -> RgbaImage {
+        let size = self.window.inner_size();
+        let rect =
+            DeviceIntRect::from_size(DeviceIntSize::new(size.width as i32, size.height as i32));
+        let mut pixels = self.read_pixels(rect);
+        unpremultiply(&mut pixels);
+        RgbaImage::from_raw(size.width, size.height, pixels)
+            .unwrap_or_else(|| RgbaImage::new(size.width, size.height))
     }
 }
 
+/// Events sent by the window tasks and the renderer to the event loop.
 #[derive(Debug, Clone, Copy)]
-pub struct Redraw(WindowId);
+pub enum RendererEvent {
+    /// A new frame is ready to be drawn.
+    Redraw(WindowId),
+    /// A frame was drawn and swapped on screen.
+    FrameRendered(WindowId),
+    /// Change the cursor icon of the window.
+    SetCursor(WindowId, CursorIcon),
+    /// A registered accelerator was pressed.
+    Accelerator(WindowId, Accelerator),
+    /// Keep the window above the other windows or not.
+    SetAlwaysOnTop(WindowId, bool),
+    /// Wake the event loop up at an instant, for the callbacks scheduled by
+    /// the components.
+    ScheduleWake(WindowId, Instant),
+    /// The task is done and dropped its gl context, so the window can be
+    /// closed.
+    Close(WindowId),
+}
+
+// Messages sent from a Window to its task
+pub(crate) enum TaskMessage {
+    Event(Event<'static, RendererEvent>),
+    ReadPixels(DeviceIntRect, Sender<Vec<u8>>),
+    UserEvent(UserEvent),
+    WithGl(Box<dyn FnOnce(&dyn gl::Gl) + Send>),
+    ScaleFactorChanged(f64, PhysicalSize<u32>),
+    Sync(Sender<()>),
+    #[cfg(feature = "hot-reload")]
+    HotReload(dioxus_rsx_interpreter::SetManyRsxMessage),
+}
 
 struct Notifier {
     id: WindowId,
-    events_proxy: EventLoopProxy<Redraw>,
+    events_proxy: EventLoopProxy<RendererEvent>,
+    /// Set when webrender built a frame, which has to be painted even if no
+    /// node is dirty, like after a scroll.
+    frame_ready: Arc<AtomicBool>,
 }
 
 impl Notifier {
-    fn new(id: WindowId, events_proxy: EventLoopProxy<Redraw>) -> Notifier {
-        Notifier { id, events_proxy }
+    fn new(
+        id: WindowId,
+        events_proxy: EventLoopProxy<RendererEvent>,
+        frame_ready: Arc<AtomicBool>,
+    ) -> Notifier {
+        Notifier {
+            id,
+            events_proxy,
+            frame_ready,
+        }
     }
 }
 
 impl RenderNotifier for Notifier {
     fn clone(&self) -> Box<dyn RenderNotifier> {
-        Box::new(Notifier::new(self.id, self.events_proxy.clone()))
+        Box::new(Notifier::new(
+            self.id,
+            self.events_proxy.clone(),
+            self.frame_ready.clone(),
+        ))
     }
 
     fn wake_up(&self, _composite_needed: bool) {
         #[cfg(not(target_os = "android"))]
-        let _ = self.events_proxy.send_event(Redraw(self.id));
+        let _ = self.events_proxy.send_event(RendererEvent::Redraw(self.id));
     }
 
     fn new_frame_ready(&self, _: DocumentId, _scrolled: bool, composite_needed: bool) {
+        self.frame_ready.store(true, Ordering::Release);
         self.wake_up(composite_needed);
     }
 }
@@ -219,23 +521,188 @@ impl RenderNotifier for Notifier {
 struct WindowState {
     modifiers: Modifiers,
     focus: FocusState,
+    /// Keys that are currently held down, used to detect auto-repeat.
+    pressed_keys: FxHashSet<VirtualKeyCode>,
+    /// The code, location and repeat flag of the last key pressed, for the
+    /// keypress events of the characters it types.
+    typing_key: Option<(Code, Location, bool)>,
+    /// The cursor icon last requested from the main thread.
+    cursor: CursorIcon,
+    /// The last position of the cursor in the window, if it is inside.
+    cursor_position: Option<(f32, f32)>,
+    /// The position of the cursor when it was last inside, for the events
+    /// sent when it leaves.
+    last_cursor_position: (f32, f32),
+    /// The hovered node and its ancestors, from the deepest.
+    hovered: Vec<ElementId>,
+    /// The mouse buttons held down.
+    buttons: MouseButtonSet,
+    /// The origin of the window content on the screen, in layout units.
+    window_origin: (f32, f32),
+    /// The node pressed with the mouse or the space key, until it is released.
+    pressed: Option<ElementId>,
+    /// The pressed node and its ancestors, from the deepest.
+    active: Vec<ElementId>,
+    /// The node the mouse is captured by, which gets its events wherever the
+    /// cursor is until the buttons are released.
+    captured: Option<ElementId>,
+    /// The fingers on the screen, by the id winit gives them.
+    touches: FxHashMap<u64, TouchPointer>,
+    /// The number of touches started, to give each one its own pointer id.
+    touch_count: i32,
+    /// The nodes with running transitions or animations.
+    animating: FxHashSet<ElementId>,
 }
 
+// A finger on the screen, as a pointer
+struct TouchPointer {
+    pointer_id: i32,
+    /// The node the touch started on, which gets all its pointer events.
+    target: Option<ElementId>,
+    /// The first of simultaneous touches is the primary pointer.
+    primary: bool,
+    position: (f32, f32),
+    /// Where the touch started, to tell taps from pans.
+    start: (f32, f32),
+    pressure: f32,
+    down: bool,
+    /// Whether lifting the finger still clicks: it hasn't moved further than
+    /// [`TOUCH_SLOP`] and no other finger touched the screen.
+    tap: bool,
+}
+
+impl WindowState {
+    // The data of a mouse event at the cursor, relative to the target
+    fn mouse_data(
+        &self,
+        context: &WindowContext,
+        target: Option<ElementId>,
+        trigger_button: Option<DomMouseButton>,
+    ) -> MouseData {
+        mouse_data(
+            self.cursor_position.unwrap_or(self.last_cursor_position),
+            self.window_origin,
+            target.and_then(|target| context.element_rect(target)),
+            trigger_button,
+            self.buttons,
+            self.modifiers,
+        )
+    }
+
+    // The data of a pointer event of the mouse at the cursor
+    fn pointer_data(
+        &self,
+        context: &WindowContext,
+        target: Option<ElementId>,
+        trigger_button: Option<DomMouseButton>,
+    ) -> PointerData {
+        let mouse = self.mouse_data(context, target, trigger_button);
+        // a mouse without pressure support is half pressed while a button is
+        // held, like in browsers
+        let pressure = if self.buttons.is_empty() { 0.0 } else { 0.5 };
+        pointer_data(&mouse, MOUSE_POINTER_ID, "mouse", pressure, true)
+    }
+
+    // The data of a pointer event of a touch, relative to its target
+    fn touch_pointer_data(
+        &self,
+        context: &WindowContext,
+        touch: &TouchPointer,
+        trigger_button: Option<DomMouseButton>,
+    ) -> PointerData {
+        let mut held = MouseButtonSet::empty();
+        if touch.down {
+            held.insert(DomMouseButton::Primary);
+        }
+        let mouse = mouse_data(
+            touch.position,
+            self.window_origin,
+            touch.target.and_then(|target| context.element_rect(target)),
+            trigger_button,
+            held,
+            self.modifiers,
+        );
+        let pressure = if touch.down { touch.pressure } else { 0.0 };
+        pointer_data(&mouse, touch.pointer_id, "touch", pressure, touch.primary)
+    }
+
+    // The touches of an event caused by the touch of winit id `changed`
+    fn touch_list(&self, changed: u64) -> TouchList {
+        let touch = |pointer: &TouchPointer| Touch {
+            identifier: pointer.pointer_id,
+            target: pointer.target,
+            position: LayoutPoint::new(pointer.position.0, pointer.position.1),
+            force: if pointer.down { pointer.pressure } else { 0.0 },
+        };
+        let mut touches: Vec<Touch> = self
+            .touches
+            .values()
+            .filter(|pointer| pointer.down)
+            .map(touch)
+            .collect();
+        touches.sort_by_key(|touch| touch.identifier);
+        TouchList {
+            touches,
+            changed_touches: self.touches.get(&changed).map(touch).into_iter().collect(),
+        }
+    }
+
+    // The data of a touch event, which only has the modifiers
+    fn touch_data(&self) -> TouchData {
+        TouchData {
+            alt_key: self.modifiers.contains(Modifiers::ALT),
+            ctrl_key: self.modifiers.contains(Modifiers::CONTROL),
+            meta_key: self.modifiers.contains(Modifiers::META),
+            shift_key: self.modifiers.contains(Modifiers::SHIFT),
+        }
+    }
+}
+
+/// The minimum time between two layouts of a window being resized, about a
+/// frame.
+const RESIZE_INTERVAL: Duration = Duration::from_millis(16);
+
+/// The distance in layout units a finger can move before its touch is a pan
+/// instead of a tap.
+const TOUCH_SLOP: f32 = 10.0;
+
+/// The time between two animation frames when nothing is painted and the
+/// frame rate isn't capped, about a frame at 60Hz.
+const ANIMATION_FRAME_INTERVAL: Duration = Duration::from_millis(16);
+
 struct WindowTask {
-    event_rx: Receiver<Event<'static, Redraw>>,
-    proxy: EventLoopProxy<Redraw>,
+    event_rx: Receiver<TaskMessage>,
+    proxy: EventLoopProxy<RendererEvent>,
     state: WindowState,
 
-    windowed_context: WindowedContext<PossiblyCurrent>,
+    gl_context: RawContext<PossiblyCurrent>,
+    window: Arc<glutin::window::Window>,
+    gl: Rc<dyn gl::Gl>,
     renderer: Renderer,
-    pipeline_id: PipelineId,
+    /// Whether webrender built a frame that wasn't painted yet.
+    frame_ready: Arc<AtomicBool>,
+    /// Kept between frames to reuse its buffers.
+    builder: DisplayListBuilder,
     document_id: DocumentId,
     epoch: Epoch,
     api: RenderApi,
 
     rdom: RealDom<NodeState>,
     vdom: VirtualDom,
+    context: WindowContext,
     stretch: Rc<RefCell<Taffy>>,
+    stylesheet: Rc<Stylesheet>,
+    box_sizing: BoxSizing,
+    /// The minimum time between two frames, if the frame rate is capped.
+    frame_interval: Option<Duration>,
+    /// The distance scrolled by a line of a mouse wheel, in layout units.
+    wheel_line_height: f32,
+    /// Whether Escape closes the window.
+    close_on_escape: bool,
+    accelerators: Vec<(Accelerator, Box<dyn FnMut() + Send>)>,
+    on_frame_stats: Option<Box<dyn FnMut(FrameStats) + Send>>,
+    fonts: Rc<FontRegistry>,
+    text_measures: TextMeasures,
     dirty_nodes: DirtyNodes,
 }
 
@@ -246,81 +713,284 @@ impl WindowTask {
             event_rx,
             proxy,
             mut state,
-            windowed_context,
+            gl_context,
+            window,
+            gl,
             mut renderer,
-            pipeline_id,
+            frame_ready,
+            mut builder,
             document_id,
             epoch,
             mut api,
             mut rdom,
             mut vdom,
+            context,
             stretch,
+            stylesheet,
+            box_sizing,
+            frame_interval,
+            wheel_line_height,
+            close_on_escape,
+            mut accelerators,
+            mut on_frame_stats,
+            fonts,
+            mut text_measures,
             mut dirty_nodes,
         } = self;
-        let window = windowed_context.window();
         let id = window.id();
         let mut size = window.inner_size();
+        let mut scale_factor = window.scale_factor();
+        state.window_origin = window_origin(&window, scale_factor);
         let mut resize = None;
+        let mut syncs = Vec::new();
+        // when the last frame was rendered, and when a deferred repaint is due
+        let mut last_frame: Option<Instant> = None;
+        // when the last repaint started, even if it had nothing to paint
+        let mut last_redraw: Option<Instant> = None;
+        let mut redraw_at: Option<Instant> = None;
+        // whether the task asked for the pending repaint, the other ones come
+        // from the platform
+        let mut redraw_requested = false;
+        // when the last resize was laid out
+        let mut last_resize: Option<Instant> = None;
+        // the layout time since the last frame, for the frame stats
+        let mut layout_time = Duration::ZERO;
+        // the wake last asked to the event loop
+        let mut scheduled_wake: Option<Instant> = None;
 
         let mut running = true;
         while running {
-            if let Ok(event) = event_rx.recv() {
-                match event {
+            // the scroll containers scrolled while handling this message
+            let mut scrolled = Vec::new();
+            let resize_at = resize.and(last_resize).map(|l| l + RESIZE_INTERVAL);
+            // the queries of the components are answered without waiting
+            let query_at = context.has_focusable_queries().then(Instant::now);
+            let message = match redraw_at.into_iter().chain(resize_at).chain(query_at).min() {
+                Some(deadline) => event_rx.recv_deadline(deadline),
+                None => event_rx.recv().map_err(RecvTimeoutError::from),
+            };
+            let started = Instant::now();
+            let handled = message.is_ok();
+            match message {
+                Ok(TaskMessage::ReadPixels(rect, pixels_tx)) => {
+                    // the back buffer is undefined after a swap, so the current
+                    // frame is rendered again without swapping
+                    let device_size = DeviceIntSize::new(size.width as i32, size.height as i32);
+                    renderer.update();
+                    renderer.render(device_size, 0).unwrap();
+                    let _ = pixels_tx.send(read_pixels(&*gl, rect, device_size.height));
+                }
+                Ok(TaskMessage::UserEvent(event)) => WindowTask::send_event(&context, event),
+                Ok(TaskMessage::WithGl(f)) => f(&*gl),
+                // answered once the mutations are applied below
+                Ok(TaskMessage::Sync(done_tx)) => syncs.push(done_tx),
+                Ok(TaskMessage::ScaleFactorChanged(factor, new_size)) => {
+                    scale_factor = factor;
+                    state.window_origin = window_origin(&window, scale_factor);
+                    context.set_size(logical_size(new_size, scale_factor));
+                    resize = Some(new_size);
+                    // laid out right away to match the new scale factor
+                    last_resize = None;
+                }
+                #[cfg(feature = "hot-reload")]
+                Ok(TaskMessage::HotReload(msgs)) => {
+                    // the vdom rerenders the edited scopes below
+                    dioxus_rsx_interpreter::RSX_CONTEXT.extend(msgs);
+                    dirty_nodes = DirtyNodes::All;
+                    proxy.send_event(RendererEvent::Redraw(id)).unwrap();
+                }
+                Ok(TaskMessage::Event(event)) => match event {
                     Event::NewEvents(event) => match event {
-                        StartCause::Init => window.request_redraw(),
+                        StartCause::Init => {
+                            redraw_requested = true;
+                            window.request_redraw();
+                        }
                         _ => (),
                     },
-                    Event::MainEventsCleared => window.request_redraw(),
                     Event::WindowEvent { window_id, event } if window_id == id => match event {
                         WindowEvent::CloseRequested => running = false,
                         WindowEvent::KeyboardInput { input, .. } => {
-                            if let Some(key) = input.virtual_keycode {
-                                // TODO parse the right key
+                            // glutin doesn't report repeats, so a press of a key that
+                            // is already down is an auto-repeat
+                            let key_input = input.virtual_keycode.map(|key| {
+                                let repeat = match input.state {
+                                    ElementState::Pressed => !state.pressed_keys.insert(key),
+                                    ElementState::Released => {
+                                        state.pressed_keys.remove(&key);
+                                        false
+                                    }
+                                };
+                                (key, repeat)
+                            });
+
+                            // accelerators take the key before the focused element,
+                            // which doesn't get it then
+                            let key_input = key_input.filter(|&(key, repeat)| {
+                                if input.state != ElementState::Pressed || repeat {
+                                    return true;
+                                }
+                                let accelerator = Accelerator::new(state.modifiers, key);
+                                match accelerators.iter_mut().find(|(a, _)| *a == accelerator) {
+                                    Some((_, callback)) => {
+                                        callback();
+                                        proxy
+                                            .send_event(RendererEvent::Accelerator(id, accelerator))
+                                            .unwrap_or_else(|e| error!("{}", e));
+                                        false
+                                    }
+                                    None => true,
+                                }
+                            });
+
+                            if let Some((key, repeat)) = key_input {
+                                let (key_value, code, location) =
+                                    translate_key(key, state.modifiers);
                                 let data = KeyboardData::new(
-                                    Key::F10,
-                                    Code::KeyA,
-                                    Location::Standard,
-                                    false,
+                                    key_value,
+                                    code,
+                                    location,
+                                    repeat,
                                     state.modifiers,
                                 );
 
-                                // keypress events are only triggered when a key that has text is pressed
                                 if let ElementState::Pressed = input.state {
-                                    WindowTask::send_event(
-                                        &vdom,
-                                        UserEvent {
-                                            scope_id: None,
-                                            priority: EventPriority::Medium,
-                                            element: Some(ElementId(1)),
-                                            name: "keypress",
-                                            data: Arc::new(data.clone()),
-                                            bubbles: true,
-                                        },
-                                    );
+                                    // the keypress events are sent for the characters the
+                                    // key types, see ReceivedCharacter
+                                    state.typing_key = Some((code, location, repeat));
 
-                                    if key == VirtualKeyCode::Tab {
-                                        state.focus.progress(
+                                    // holding Tab doesn't race through the focus order
+                                    if key == VirtualKeyCode::Tab && !repeat {
+                                        let blurred = state.focus.last_focused_id;
+                                        if state.focus.progress(
                                             &mut rdom,
                                             !state.modifiers.contains(Modifiers::SHIFT),
-                                        );
+                                        ) {
+                                            WindowTask::send_focus_events(
+                                                &context,
+                                                blurred,
+                                                state.focus.last_focused_id,
+                                            );
+                                            if let Some(focused) = state.focus.last_focused_id {
+                                                scrolled
+                                                    .extend(scroll_into_view(&mut rdom, focused));
+                                            }
+                                        }
                                     }
                                 }
 
+                                // holding space presses the focused node like a mouse button
+                                if key == VirtualKeyCode::Space && !repeat {
+                                    state.pressed = match input.state {
+                                        ElementState::Pressed => {
+                                            state.focus.last_focused_id.filter(|id| {
+                                                !is_disabled(&rdom, *id) && is_displayed(&rdom, *id)
+                                            })
+                                        }
+                                        ElementState::Released => None,
+                                    };
+                                    WindowTask::set_pseudo_class(
+                                        &proxy,
+                                        &mut rdom,
+                                        &mut dirty_nodes,
+                                        &mut state.animating,
+                                        id,
+                                        PseudoClass::Active,
+                                        &mut state.active,
+                                        state.pressed,
+                                    );
+                                }
+
+                                let name = match input.state {
+                                    ElementState::Pressed => "keydown",
+                                    ElementState::Released => "keyup",
+                                };
+                                let target = state.focus.last_focused_id.filter(|id| {
+                                    !is_disabled(&rdom, *id) && is_displayed(&rdom, *id)
+                                });
                                 WindowTask::send_event(
-                                    &vdom,
-                                    UserEvent {
-                                        scope_id: None,
-                                        priority: EventPriority::Medium,
-                                        element: state.focus.last_focused_id,
-                                        name: match input.state {
-                                            ElementState::Pressed => "keydown",
-                                            ElementState::Released => "keyup",
-                                        },
-                                        data: Arc::new(data),
-                                        bubbles: true,
-                                    },
+                                    &context,
+                                    user_event(name, target, Arc::new(data), true),
                                 );
+
+                                // like a close request, the loop ends after the keydown
+                                // is dispatched below
+                                if close_on_escape
+                                    && key == VirtualKeyCode::Escape
+                                    && input.state == ElementState::Pressed
+                                    && !repeat
+                                    && !target.map_or(false, |target| {
+                                        prevents(&rdom, target, PreventableEvent::KeyDown)
+                                    })
+                                {
+                                    running = false;
+                                }
+
+                                // after their keydown, the shortcuts send a clipboard event.
+                                // Nothing is copied or pasted without a handler, there is no
+                                // selection or editable element to act on yet
+                                if let Some(name) = clipboard_event(key, state.modifiers)
+                                    .filter(|_| input.state == ElementState::Pressed && !repeat)
+                                {
+                                    WindowTask::send_event(
+                                        &context,
+                                        user_event(name, target, Arc::new(ClipboardData {}), true),
+                                    );
+                                }
+                            }
+                        }
+                        // the text typed with the layout of the user. Some
+                        // platforms report it before the key event, so the code
+                        // of the key may be unknown. Only the keypress listeners
+                        // see it, there are no editing buffers to insert it in yet
+                        WindowEvent::ReceivedCharacter(c) => {
+                            let key_value = match c {
+                                '\r' | '\n' => Key::Enter,
+                                c if c.is_control() => continue,
+                                c => Key::Character(c.to_string()),
+                            };
+                            let (code, location, repeat) = state.typing_key.unwrap_or((
+                                Code::Unidentified,
+                                Location::Standard,
+                                false,
+                            ));
+                            let data = KeyboardData::new(
+                                key_value,
+                                code,
+                                location,
+                                repeat,
+                                state.modifiers,
+                            );
+                            let target = state
+                                .focus
+                                .last_focused_id
+                                .filter(|id| !is_disabled(&rdom, *id) && is_displayed(&rdom, *id));
+                            WindowTask::send_event(
+                                &context,
+                                user_event("keypress", target, Arc::new(data), true),
+                            );
+                        }
+                        WindowEvent::Focused(focused) => {
+                            // the keys and buttons released in another window are never
+                            // reported, so nothing is held when the focus returns
+                            if !focused {
+                                state.modifiers = Modifiers::empty();
+                                state.pressed_keys.clear();
+                                state.buttons = MouseButtonSet::empty();
+                                state.typing_key = None;
+                                if state.pressed.take().is_some() {
+                                    WindowTask::set_pseudo_class(
+                                        &proxy,
+                                        &mut rdom,
+                                        &mut dirty_nodes,
+                                        &mut state.animating,
+                                        id,
+                                        PseudoClass::Active,
+                                        &mut state.active,
+                                        None,
+                                    );
+                                }
                             }
+                            context.set_focused(focused);
                         }
                         WindowEvent::ModifiersChanged(mods) => {
                             let mut modifiers = Modifiers::empty();
@@ -338,104 +1008,1209 @@ impl WindowTask {
                             }
                             state.modifiers = modifiers;
                         }
-                        WindowEvent::Resized(s) => resize = Some(s),
-                        // TODO mouse state
-                        // WindowEvent::CursorMoved {
-                        // WindowEvent::MouseInput {
+                        WindowEvent::CursorMoved { position, .. } => {
+                            let position = position.to_logical::<f32>(scale_factor);
+                            state.cursor_position = Some((position.x, position.y));
+                            state.last_cursor_position = (position.x, position.y);
+                            WindowTask::update_hover(
+                                &proxy,
+                                &mut state,
+                                &mut rdom,
+                                &mut dirty_nodes,
+                                &context,
+                                id,
+                            );
+                            let target = state.hovered.first().copied();
+                            let data = state.mouse_data(&context, target, None);
+                            WindowTask::send_event(
+                                &context,
+                                user_event("mousemove", target, Arc::new(data), true),
+                            );
+                            let data = state.pointer_data(&context, target, None);
+                            WindowTask::send_event(
+                                &context,
+                                user_event("pointermove", target, Arc::new(data), true),
+                            );
+                        }
+                        // the nodes under the cursor are all left
+                        WindowEvent::CursorLeft { .. } => {
+                            state.cursor_position = None;
+                            WindowTask::update_hover(
+                                &proxy,
+                                &mut state,
+                                &mut rdom,
+                                &mut dirty_nodes,
+                                &context,
+                                id,
+                            );
+                        }
+                        WindowEvent::MouseWheel { delta, .. } => {
+                            let shift = state.modifiers.contains(Modifiers::SHIFT);
+                            let delta = wheel_delta(delta, scale_factor, wheel_line_height, shift);
+                            let target = state
+                                .cursor_position
+                                .and_then(|(x, y)| hit_test(&rdom, x, y));
+                            if let Some(target) = target {
+                                // trackpads send many small deltas, momentum included
+                                let data = WheelData::new(WheelDelta::pixels(
+                                    delta.x as f64,
+                                    delta.y as f64,
+                                    0.0,
+                                ));
+                                WindowTask::send_event(
+                                    &context,
+                                    user_event("wheel", Some(target), Arc::new(data), true),
+                                );
+
+                                if !prevents(&rdom, target, PreventableEvent::Wheel) {
+                                    scrolled.extend(scroll_by(&mut rdom, target, delta));
+                                }
+                            }
+                        }
+                        WindowEvent::Touch(touch) => {
+                            let position = touch.location.to_logical::<f32>(scale_factor);
+                            let position = (position.x, position.y);
+                            // a screen without pressure support is half pressed, like
+                            // in browsers
+                            let pressure = touch.force.map_or(0.5, |f| f.normalized() as f32);
+                            if touch.phase == TouchPhase::Started {
+                                // a second finger makes a gesture of the touches
+                                for other in state.touches.values_mut() {
+                                    other.tap = false;
+                                }
+                                state.touch_count += 1;
+                                let pointer = TouchPointer {
+                                    pointer_id: MOUSE_POINTER_ID + state.touch_count,
+                                    target: hit_test(&rdom, position.0, position.1)
+                                        .filter(|id| !is_disabled(&rdom, *id)),
+                                    primary: state.touches.is_empty(),
+                                    position,
+                                    start: position,
+                                    pressure,
+                                    down: true,
+                                    tap: state.touches.is_empty(),
+                                };
+                                state.touches.insert(touch.id, pointer);
+                            }
+                            // a touch that started in another window is ignored
+                            if let Some(pointer) = state.touches.get_mut(&touch.id) {
+                                let previous = pointer.position;
+                                pointer.position = position;
+                                pointer.pressure = pressure;
+                                pointer.down =
+                                    matches!(touch.phase, TouchPhase::Started | TouchPhase::Moved);
+                                let (dx, dy) =
+                                    (position.0 - pointer.start.0, position.1 - pointer.start.1);
+                                let panning = dx.hypot(dy) > TOUCH_SLOP;
+                                if panning {
+                                    pointer.tap = false;
+                                }
+                                let (target, tap) = (pointer.target, pointer.tap);
+                                let names: &[&'static str] = match touch.phase {
+                                    TouchPhase::Started => {
+                                        &["pointerover", "pointerenter", "pointerdown"]
+                                    }
+                                    TouchPhase::Moved => &["pointermove"],
+                                    TouchPhase::Ended => {
+                                        &["pointerup", "pointerout", "pointerleave"]
+                                    }
+                                    TouchPhase::Cancelled => {
+                                        &["pointercancel", "pointerout", "pointerleave"]
+                                    }
+                                };
+                                WindowTask::send_touch_pointer_events(
+                                    &rdom,
+                                    &context,
+                                    &state,
+                                    &state.touches[&touch.id],
+                                    names,
+                                );
+
+                                // the touch events follow the pointer events, and go to
+                                // the node the touch started on
+                                let target = target.filter(|target| rdom.get(target.0).is_some());
+                                if let Some(target) = target {
+                                    let name = match touch.phase {
+                                        TouchPhase::Started => "touchstart",
+                                        TouchPhase::Moved => "touchmove",
+                                        TouchPhase::Ended => "touchend",
+                                        TouchPhase::Cancelled => "touchcancel",
+                                    };
+                                    WindowTask::send_touch_event(
+                                        &context,
+                                        user_event(
+                                            name,
+                                            Some(target),
+                                            Arc::new(state.touch_data()),
+                                            true,
+                                        ),
+                                        state.touch_list(touch.id),
+                                    );
+                                }
+
+                                match (touch.phase, target) {
+                                    // a single finger pans the scroll containers under
+                                    // it, moving their content with it
+                                    (TouchPhase::Moved, Some(target))
+                                        if panning
+                                            && state.touches.len() == 1
+                                            && !prevents(
+                                                &rdom,
+                                                target,
+                                                PreventableEvent::TouchMove,
+                                            ) =>
+                                    {
+                                        let delta = LayoutVector2D::new(
+                                            previous.0 - position.0,
+                                            previous.1 - position.1,
+                                        );
+                                        scrolled.extend(scroll_by(&mut rdom, target, delta));
+                                    }
+                                    // a quick tap clicks the closest node that has both
+                                    // the touched and the released nodes in it
+                                    (TouchPhase::Ended, Some(target))
+                                        if tap
+                                            && !prevents(
+                                                &rdom,
+                                                target,
+                                                PreventableEvent::TouchEnd,
+                                            ) =>
+                                    {
+                                        let clicked = hit_test(&rdom, position.0, position.1)
+                                            .filter(|id| !is_disabled(&rdom, *id))
+                                            .and_then(|released| {
+                                                common_ancestor(&rdom, target, released)
+                                            });
+                                        if let Some(clicked) = clicked {
+                                            let data = mouse_data(
+                                                position,
+                                                state.window_origin,
+                                                context.element_rect(clicked),
+                                                Some(DomMouseButton::Primary),
+                                                MouseButtonSet::empty(),
+                                                state.modifiers,
+                                            );
+                                            WindowTask::send_event(
+                                                &context,
+                                                user_event(
+                                                    "click",
+                                                    Some(clicked),
+                                                    Arc::new(data),
+                                                    true,
+                                                ),
+                                            );
+                                        }
+                                    }
+                                    _ => (),
+                                }
+                            }
+                            if matches!(touch.phase, TouchPhase::Ended | TouchPhase::Cancelled) {
+                                state.touches.remove(&touch.id);
+                            }
+                        }
+                        WindowEvent::Moved(_) => {
+                            state.window_origin = window_origin(&window, scale_factor);
+                        }
+                        WindowEvent::Resized(s) => {
+                            context.set_size(logical_size(s, scale_factor));
+                            resize = Some(s);
+                        }
+                        WindowEvent::MouseInput {
+                            state: button_state,
+                            button,
+                            ..
+                        } => {
+                            let held_before = !state.buttons.is_empty();
+                            match button_state {
+                                ElementState::Pressed => {
+                                    state.buttons.insert(translate_button(button))
+                                }
+                                ElementState::Released => {
+                                    state.buttons.remove(translate_button(button))
+                                }
+                            };
+
+                            let hit = state
+                                .cursor_position
+                                .and_then(|(x, y)| hit_test(&rdom, x, y))
+                                .filter(|id| !is_disabled(&rdom, *id));
+                            // the node that captured the mouse gets its events
+                            let target = state
+                                .captured
+                                .filter(|node| rdom.get(node.0).is_some())
+                                .or(hit);
+                            let name = match button_state {
+                                ElementState::Pressed => "mousedown",
+                                ElementState::Released => "mouseup",
+                            };
+                            if target.is_some() {
+                                let data = state.mouse_data(
+                                    &context,
+                                    target,
+                                    Some(translate_button(button)),
+                                );
+                                WindowTask::send_event(
+                                    &context,
+                                    user_event(name, target, Arc::new(data), true),
+                                );
+
+                                // the mouse pointer is down while any button is, the
+                                // other buttons change it like moves
+                                let pointer_name = match button_state {
+                                    ElementState::Pressed if !held_before => "pointerdown",
+                                    ElementState::Released if state.buttons.is_empty() => {
+                                        "pointerup"
+                                    }
+                                    _ => "pointermove",
+                                };
+                                let data = state.pointer_data(
+                                    &context,
+                                    target,
+                                    Some(translate_button(button)),
+                                );
+                                WindowTask::send_event(
+                                    &context,
+                                    user_event(pointer_name, target, Arc::new(data), true),
+                                );
+                            }
+
+                            // a right press opens the context menu, and so does a
+                            // control press on macos, which isn't a click then
+                            let context_menu = button_state == ElementState::Pressed
+                                && (button == MouseButton::Right
+                                    || (cfg!(target_os = "macos")
+                                        && button == MouseButton::Left
+                                        && state.modifiers.contains(Modifiers::CONTROL)));
+                            if context_menu && target.is_some() {
+                                let data = state.mouse_data(
+                                    &context,
+                                    target,
+                                    Some(DomMouseButton::Secondary),
+                                );
+                                WindowTask::send_event(
+                                    &context,
+                                    user_event("contextmenu", target, Arc::new(data), true),
+                                );
+                            }
+
+                            if button == MouseButton::Left && !context_menu {
+                                // a click is sent to the closest node that has both the
+                                // pressed and the released nodes in it, like in browsers.
+                                // Releasing outside a captured node doesn't click it
+                                let clicked = match (state.pressed, button_state) {
+                                    (Some(pressed), ElementState::Released) => {
+                                        hit.and_then(|hit| common_ancestor(&rdom, pressed, hit))
+                                    }
+                                    _ => None,
+                                };
+                                if let Some(clicked) = clicked {
+                                    let data = state.mouse_data(
+                                        &context,
+                                        Some(clicked),
+                                        Some(DomMouseButton::Primary),
+                                    );
+                                    WindowTask::send_event(
+                                        &context,
+                                        user_event("click", Some(clicked), Arc::new(data), true),
+                                    );
+                                }
+
+                                // the press ends on release anywhere, like in browsers
+                                state.pressed = match button_state {
+                                    ElementState::Pressed => target,
+                                    ElementState::Released => None,
+                                };
+                                WindowTask::set_pseudo_class(
+                                    &proxy,
+                                    &mut rdom,
+                                    &mut dirty_nodes,
+                                    &mut state.animating,
+                                    id,
+                                    PseudoClass::Active,
+                                    &mut state.active,
+                                    state.pressed,
+                                );
+
+                                // pressing a node focuses it or its closest focusable
+                                // ancestor, unless it prevents its mousedown or the
+                                // ancestor prevents its focus
+                                let focused = state.pressed.filter(|target| {
+                                    let prevent_default = &rdom[*target].state.prevent_default;
+                                    !prevent_default.prevents(PreventableEvent::MouseDown)
+                                });
+                                let focused = focused
+                                    .and_then(|target| focusable_ancestor(&rdom, target))
+                                    .filter(|focused| {
+                                        let prevent_default = &rdom[*focused].state.prevent_default;
+                                        !prevent_default.prevents(PreventableEvent::Focus)
+                                    });
+                                if let Some(focused) = focused {
+                                    let blurred = state.focus.last_focused_id;
+                                    if blurred != Some(focused) {
+                                        state.focus.set_focus(&mut rdom, focused);
+                                        WindowTask::send_focus_events(
+                                            &context,
+                                            blurred,
+                                            Some(focused),
+                                        );
+                                    }
+                                }
+                            }
+
+                            // the first button pressed captures the mouse for the
+                            // pressed node, until all the buttons are released
+                            let captured = match button_state {
+                                ElementState::Pressed if !held_before => target,
+                                ElementState::Released if state.buttons.is_empty() => None,
+                                _ => state.captured,
+                            };
+                            WindowTask::set_pointer_capture(
+                                &proxy,
+                                &mut state,
+                                &mut rdom,
+                                &mut dirty_nodes,
+                                &context,
+                                id,
+                                captured,
+                            );
+                        }
                         _ => (),
                     },
-                    Event::UserEvent(Redraw(w)) if w == id => window.request_redraw(),
+                    Event::UserEvent(RendererEvent::Redraw(w)) if w == id => {
+                        // with a capped frame rate, the repaints requested
+                        // before the next frame is due wait for it
+                        let next_frame = frame_interval.zip(last_frame).map(|(i, l)| l + i);
+                        match next_frame {
+                            Some(at) if at > Instant::now() => {
+                                redraw_at.get_or_insert(at);
+                            }
+                            _ => {
+                                redraw_requested = true;
+                                window.request_redraw();
+                            }
+                        }
+                    }
                     Event::RedrawRequested(w) if w == id => {
+                        let frame_time = Instant::now();
+                        last_redraw = Some(frame_time);
+                        // the deferred repaint is painted now
+                        redraw_at = None;
+                        // the platform asks for a repaint when the window is
+                        // uncovered, so the whole frame is painted again
+                        if !std::mem::take(&mut redraw_requested) {
+                            dirty_nodes = DirtyNodes::All;
+                        }
+
+                        // advance the running transitions and animations, and
+                        // repaint their nodes one last time when they stop
+                        if !state.animating.is_empty() {
+                            let now = Instant::now();
+                            let animating: Vec<ElementId> = state.animating.drain().collect();
+                            for node in animating {
+                                // the node may have been removed
+                                if let Some(n) = rdom.get_mut(node.0) {
+                                    if n.state.tick(now) {
+                                        state.animating.insert(node);
+                                    }
+                                    if let DirtyNodes::Some(nodes) = &mut dirty_nodes {
+                                        nodes.push(node);
+                                    }
+                                }
+                            }
+                        }
+
                         let nodes = if state.focus.clean() {
                             DirtyNodes::All
                         } else {
                             std::mem::take(&mut dirty_nodes)
                         };
 
-                        if !nodes.is_empty() {
+                        // a scroll moves the scroll frames of the last display
+                        // list, so webrender may have a frame to paint without
+                        // a dirty node
+                        let frame_built = frame_ready.swap(false, Ordering::AcqRel);
+                        if !nodes.is_empty() || frame_built {
                             let device_size =
                                 DeviceIntSize::new(size.width as i32, size.height as i32);
-                            let device_pixel_ratio = window.scale_factor() as f32;
-                            let layout_size =
-                                device_size.to_f32() / euclid::Scale::new(device_pixel_ratio);
-
-                            crate::render::render(
-                                pipeline_id,
-                                document_id,
-                                epoch,
-                                &mut api,
-                                layout_size,
-                            );
+                            let started = Instant::now();
+                            if !nodes.is_empty() {
+                                crate::render::render(
+                                    document_id,
+                                    epoch,
+                                    &mut api,
+                                    &mut builder,
+                                    &rdom,
+                                    &fonts,
+                                    LayoutSize::new(size.width as f32, size.height as f32),
+                                    scale_factor as f32,
+                                );
+                            }
+                            let display_list_time = started.elapsed();
+                            match &nodes {
+                                DirtyNodes::All => {
+                                    trace!("Built the display list in {:?}", display_list_time)
+                                }
+                                DirtyNodes::Some(nodes) if nodes.is_empty() => {
+                                    trace!("Painted the frame built by webrender")
+                                }
+                                DirtyNodes::Some(nodes) => trace!(
+                                    "Built the display list for {} dirty nodes in {:?}",
+                                    nodes.len(),
+                                    display_list_time
+                                ),
+                            }
 
                             renderer.update();
                             renderer.render(device_size, 0).unwrap();
                             let _ = renderer.flush_pipeline_info();
-                            windowed_context.swap_buffers().ok();
+                            gl_context.swap_buffers().ok();
+                            last_frame = Some(Instant::now());
+
+                            if let Some(callback) = &mut on_frame_stats {
+                                let mut node_count = 0;
+                                rdom.traverse_depth_first(|_| node_count += 1);
+                                let dirty_node_count = match &nodes {
+                                    DirtyNodes::All => node_count,
+                                    DirtyNodes::Some(nodes) => nodes.len(),
+                                };
+                                callback(FrameStats {
+                                    layout_time,
+                                    display_list_time,
+                                    node_count,
+                                    dirty_node_count,
+                                });
+                            }
+                            layout_time = Duration::ZERO;
+                            proxy
+                                .send_event(RendererEvent::FrameRendered(id))
+                                .unwrap_or_else(|e| error!("{}", e));
+                        }
+
+                        // keep repainting until the transitions and animations stop
+                        if !state.animating.is_empty() {
+                            proxy
+                                .send_event(RendererEvent::Redraw(id))
+                                .unwrap_or_else(|e| error!("{}", e));
                         }
 
+                        // their changes are processed below and painted in
+                        // the next frame
+                        context.run_animation_frames(frame_time);
+
                         dirty_nodes = DirtyNodes::default();
                     }
                     _ => (),
+                },
+                // a deferred repaint or resize is due
+                Err(RecvTimeoutError::Timeout) => {
+                    if redraw_at.map_or(false, |at| at <= Instant::now()) {
+                        redraw_at = None;
+                        redraw_requested = true;
+                        window.request_redraw();
+                    }
                 }
+                // the window was dropped
+                Err(RecvTimeoutError::Disconnected) => running = false,
+            }
+            if handled {
+                trace!("Handled a message in {:?}", started.elapsed());
             }
 
+            // a drag resize is laid out at most once per interval, with the
+            // latest size
+            let resize_due =
+                resize.is_some() && last_resize.map_or(true, |l| l.elapsed() >= RESIZE_INTERVAL);
+
+            // the event loop wakes up when the callbacks are due
+            context.run_due_wakes(Instant::now());
+            for (point, callback) in context.take_focusable_queries() {
+                callback(focusable_at(&rdom, point));
+            }
+            WindowTask::dispatch_events(&mut vdom, &rdom, &context);
             vdom.process_all_messages();
-            if resize.is_some() || vdom.has_work() {
+            if resize_due || vdom.has_work() {
+                let started = Instant::now();
                 let mutations = { vdom.work_with_deadline(|| false) };
+                let edits: usize = mutations.iter().map(|m| m.edits.len()).sum();
 
+                let mut focus_lost_in = None;
                 for m in mutations.iter() {
-                    // TODO self.prune(m);
-                    state.focus.prune(m, &rdom);
+                    focus_lost_in = state.focus.prune(m, &rdom).or(focus_lost_in);
+                    state.focus.record_mounted(m);
+                    remove_layout_nodes(m, &rdom, &mut stretch.borrow_mut(), &mut text_measures);
                 }
 
                 // Update the real dom's nodes
                 let to_update = rdom.apply_mutations(mutations);
-                let mut ctx = AnyMap::new();
-                ctx.insert(stretch.clone());
-
+                trace!(
+                    "Applied {} edits to {} nodes in {:?}",
+                    edits,
+                    to_update.len(),
+                    started.elapsed()
+                );
+                // the removed node can't hear its blur anymore, but its
+                // ancestors hear the focusout
+                if let Some(parent) = focus_lost_in.filter(|p| rdom.get(p.0).is_some()) {
+                    let event = user_event("focusout", Some(parent), Arc::new(FocusData {}), true);
+                    WindowTask::send_event(&context, event);
+                }
                 // Update the style and layout
+                let started = Instant::now();
+                let updated = to_update.len();
+                let ctx = state_context(&stretch, &stylesheet, box_sizing);
                 let to_rerender = rdom.update_state(&vdom, to_update, ctx);
+                // a text whose content or font changed gets a new measure
+                // function, once per batch
+                text_measures.update(
+                    &rdom,
+                    &mut stretch.borrow_mut(),
+                    to_rerender.iter().copied(),
+                );
+                trace!(
+                    "Updated the state of {} nodes, {} changed, in {:?}",
+                    updated,
+                    to_rerender.len(),
+                    started.elapsed()
+                );
+                // the mounted nodes can take the focus once their state is known
+                let blurred = state.focus.last_focused_id;
+                if let Some(focused) = state.focus.autofocus(&mut rdom) {
+                    WindowTask::send_focus_events(&context, blurred, Some(focused));
+                }
+                WindowTask::start_animations(
+                    &mut state.animating,
+                    &mut rdom,
+                    to_rerender.iter().copied(),
+                );
 
-                if !to_rerender.is_empty() || resize.is_some() {
-                    if let Some(s) = resize.take() {
+                // paint-only changes skip the layout
+                let relayout =
+                    resize_due || (!to_rerender.is_empty() && needs_layout(&rdom, &stretch));
+                if relayout {
+                    if let Some(s) = resize.filter(|_| resize_due) {
+                        resize = None;
                         dirty_nodes = DirtyNodes::All;
                         size = s;
+                        last_resize = Some(Instant::now());
                     }
 
-                    stretch
-                        .borrow_mut()
-                        .compute_layout(
-                            rdom[ElementId(rdom.root_id())].state.layout.node.unwrap(),
-                            Size {
-                                width: Number::Defined(size.width as f32),
-                                height: Number::Defined(size.height as f32),
-                            },
-                        )
-                        .unwrap();
-
-                    rdom.traverse_depth_first_mut(|n| {
-                        if let Some(node) = n.state.layout.node {
-                            n.state.layout.layout = Some(*stretch.borrow().layout(node).unwrap());
-                        }
-                    });
+                    let started = Instant::now();
+                    compute_layout(&mut rdom, &stretch, available_space(size, scale_factor));
+                    layout_time += started.elapsed();
+                    trace!(
+                        "Laid out after {} nodes changed in {:?}",
+                        to_rerender.len(),
+                        started.elapsed()
+                    );
+                    set_layouts(&context, &rdom, &vdom);
+                }
 
+                if !to_rerender.is_empty() || relayout {
+                    // the node under the cursor may have moved or been hidden
+                    WindowTask::update_hover(
+                        &proxy,
+                        &mut state,
+                        &mut rdom,
+                        &mut dirty_nodes,
+                        &context,
+                        id,
+                    );
                     if let DirtyNodes::Some(nodes) = &mut dirty_nodes {
                         nodes.extend(to_rerender.into_iter());
                     }
-                    proxy.send_event(Redraw(id)).unwrap();
+                    proxy.send_event(RendererEvent::Redraw(id)).unwrap();
+                }
+            }
+
+            for request in context.take_scroll_requests() {
+                match request {
+                    ScrollRequest::To(node, offset) if rdom.get(node.0).is_some() => {
+                        if scroll_to(&mut rdom, node, offset) {
+                            scrolled.push(node);
+                        }
+                    }
+                    ScrollRequest::IntoView(node) if rdom.get(node.0).is_some() => {
+                        scrolled.extend(scroll_into_view(&mut rdom, node));
+                    }
+                    _ => (),
+                }
+            }
+            for request in context.take_pointer_capture_requests() {
+                let captured = match request {
+                    PointerCaptureRequest::Set(node)
+                        if rdom.get(node.0).is_some() && !state.buttons.is_empty() =>
+                    {
+                        Some(node)
+                    }
+                    PointerCaptureRequest::Release(node) if state.captured == Some(node) => None,
+                    _ => continue,
+                };
+                WindowTask::set_pointer_capture(
+                    &proxy,
+                    &mut state,
+                    &mut rdom,
+                    &mut dirty_nodes,
+                    &context,
+                    id,
+                    captured,
+                );
+            }
+            if let Some(always_on_top) = context.take_always_on_top_request() {
+                proxy
+                    .send_event(RendererEvent::SetAlwaysOnTop(id, always_on_top))
+                    .unwrap_or_else(|e| error!("{}", e));
+            }
+            // the next frame may have nothing to paint, so a repaint is
+            // deferred for the animation frame callbacks
+            if context.has_animation_frames() && redraw_at.is_none() {
+                let interval = frame_interval.unwrap_or(ANIMATION_FRAME_INTERVAL);
+                redraw_at = Some(last_redraw.map_or_else(Instant::now, |l| l + interval));
+            }
+
+            // the event loop forgets a wake once it is reached, so the next
+            // one is asked after the callbacks run
+            let next_wake = context.next_wake();
+            if next_wake != scheduled_wake {
+                scheduled_wake = next_wake;
+                if let Some(at) = next_wake {
+                    proxy
+                        .send_event(RendererEvent::ScheduleWake(id, at))
+                        .unwrap_or_else(|e| error!("{}", e));
+                }
+            }
+
+            if !scrolled.is_empty() {
+                crate::render::scroll(
+                    document_id,
+                    &mut api,
+                    builder.pipeline_id,
+                    &rdom,
+                    &scrolled,
+                    scale_factor as f32,
+                );
+                WindowTask::scrolled(
+                    &proxy,
+                    &mut state,
+                    &mut rdom,
+                    &mut dirty_nodes,
+                    &context,
+                    &vdom,
+                    id,
+                );
+            }
+
+            if !syncs.is_empty() {
+                crate::render::render(
+                    document_id,
+                    epoch,
+                    &mut api,
+                    &mut builder,
+                    &rdom,
+                    &fonts,
+                    LayoutSize::new(size.width as f32, size.height as f32),
+                    scale_factor as f32,
+                );
+                for done_tx in syncs.drain(..) {
+                    let _ = done_tx.send(());
                 }
             }
         }
 
         renderer.deinit();
+        // the context has to be dropped before the window
+        drop(gl_context);
+        drop(window);
+        // the main thread closes the window once it is told, unless the
+        // event loop is already gone
+        let _ = proxy.send_event(RendererEvent::Close(id));
+    }
+
+    // Update the window after scroll containers scrolled. Webrender moves
+    // their scroll frames, so nothing is repainted, but everything in them
+    // moved, so the element rects are shared again and the hovered node may
+    // change.
+    fn scrolled(
+        proxy: &EventLoopProxy<RendererEvent>,
+        state: &mut WindowState,
+        rdom: &mut RealDom<NodeState>,
+        dirty_nodes: &mut DirtyNodes,
+        context: &WindowContext,
+        vdom: &VirtualDom,
+        id: WindowId,
+    ) {
+        set_layouts(context, rdom, vdom);
+        WindowTask::update_hover(proxy, state, rdom, dirty_nodes, context, id);
     }
 
-    // Send UserEvent to vdom's schedular
-    fn send_event(vdom: &VirtualDom, event: UserEvent) {
-        vdom.get_scheduler_channel()
-            .unbounded_send(SchedulerMsg::Event(event))
-            .unwrap_or_else(|e| error!("{}", e));
+    // Hit test the last cursor position to update the hovered nodes and the
+    // cursor icon. Only the nodes with a hover style that changed repaint.
+    // When the deepest hovered node changes, the mouse events of the nodes
+    // the cursor left and entered are sent. The node that captured the mouse
+    // stays hovered, so the other nodes aren't entered or left meanwhile.
+    fn update_hover(
+        proxy: &EventLoopProxy<RendererEvent>,
+        state: &mut WindowState,
+        rdom: &mut RealDom<NodeState>,
+        dirty_nodes: &mut DirtyNodes,
+        context: &WindowContext,
+        id: WindowId,
+    ) {
+        let hovered = state
+            .captured
+            .filter(|node| rdom.get(node.0).is_some())
+            .or_else(|| {
+                state
+                    .cursor_position
+                    .and_then(|(x, y)| hit_test(rdom, x, y))
+            });
+
+        let left = WindowTask::set_pseudo_class(
+            proxy,
+            rdom,
+            dirty_nodes,
+            &mut state.animating,
+            id,
+            PseudoClass::Hover,
+            &mut state.hovered,
+            hovered,
+        );
+
+        let icon = hovered.map_or(CursorIcon::Default, |id| cursor_icon(rdom, id));
+        WindowTask::set_cursor(proxy, state, id, icon);
+
+        if left.first() == state.hovered.first() {
+            return;
+        }
+
+        // the removed nodes can't hear their events anymore
+        let left: Vec<ElementId> = left
+            .into_iter()
+            .filter(|node| rdom.get(node.0).is_some())
+            .collect();
+        // the mouse is also a pointer
+        let send = |name, pointer_name, target: ElementId, bubbles| {
+            let data = state.mouse_data(context, Some(target), None);
+            WindowTask::send_event(
+                context,
+                user_event(name, Some(target), Arc::new(data), bubbles),
+            );
+            let data = state.pointer_data(context, Some(target), None);
+            WindowTask::send_event(
+                context,
+                user_event(pointer_name, Some(target), Arc::new(data), bubbles),
+            );
+        };
+        if let Some(target) = left.first() {
+            send("mouseout", "pointerout", *target, true);
+        }
+        // leaving from the deepest node and entering from the outermost
+        for node in left.iter().filter(|n| !state.hovered.contains(n)) {
+            send("mouseleave", "pointerleave", *node, false);
+        }
+        if let Some(target) = state.hovered.first() {
+            send("mouseover", "pointerover", *target, true);
+        }
+        for node in state.hovered.iter().rev().filter(|n| !left.contains(n)) {
+            send("mouseenter", "pointerenter", *node, false);
+        }
     }
+
+    // Move the capture of the mouse to a node, or release it with None. The
+    // nodes losing and getting the capture are told, and the hovered nodes
+    // are updated since the capture decides them.
+    fn set_pointer_capture(
+        proxy: &EventLoopProxy<RendererEvent>,
+        state: &mut WindowState,
+        rdom: &mut RealDom<NodeState>,
+        dirty_nodes: &mut DirtyNodes,
+        context: &WindowContext,
+        id: WindowId,
+        captured: Option<ElementId>,
+    ) {
+        if state.captured == captured {
+            return;
+        }
+        let lost = std::mem::replace(&mut state.captured, captured);
+        let send = |name, target: ElementId| {
+            let data = state.pointer_data(context, Some(target), None);
+            WindowTask::send_event(
+                context,
+                user_event(name, Some(target), Arc::new(data), true),
+            );
+        };
+        if let Some(lost) = lost.filter(|node| rdom.get(node.0).is_some()) {
+            send("lostpointercapture", lost);
+        }
+        if let Some(captured) = captured {
+            send("gotpointercapture", captured);
+        }
+        WindowTask::update_hover(proxy, state, rdom, dirty_nodes, context, id);
+    }
+
+    // Send pointer events of a touch to the node it started on. The enter and
+    // leave events are sent to the node and each of its ancestors, entering
+    // from the outermost and leaving from the deepest.
+    fn send_touch_pointer_events(
+        rdom: &RealDom<NodeState>,
+        context: &WindowContext,
+        state: &WindowState,
+        touch: &TouchPointer,
+        names: &[&'static str],
+    ) {
+        let target = match touch.target.filter(|target| rdom.get(target.0).is_some()) {
+            Some(target) => target,
+            None => return,
+        };
+        let chain = ancestor_chain(rdom, Some(target));
+        for &name in names {
+            let trigger =
+                matches!(name, "pointerdown" | "pointerup").then(|| DomMouseButton::Primary);
+            let data = Arc::new(state.touch_pointer_data(context, touch, trigger));
+            let send = |node| {
+                let bubbles = !matches!(name, "pointerenter" | "pointerleave");
+                WindowTask::send_event(
+                    context,
+                    user_event(name, Some(node), data.clone(), bubbles),
+                );
+            };
+            match name {
+                "pointerenter" => chain.iter().rev().for_each(|node| send(*node)),
+                "pointerleave" => chain.iter().for_each(|node| send(*node)),
+                _ => send(target),
+            }
+        }
+    }
+
+    // Move a pseudo class from the `flagged` chain to the target node and its
+    // ancestors, returning the previous chain. Only the nodes with a style for
+    // the pseudo class repaint.
+    #[allow(clippy::too_many_arguments)]
+    fn set_pseudo_class(
+        proxy: &EventLoopProxy<RendererEvent>,
+        rdom: &mut RealDom<NodeState>,
+        dirty_nodes: &mut DirtyNodes,
+        animating: &mut FxHashSet<ElementId>,
+        id: WindowId,
+        pseudo_class: PseudoClass,
+        flagged: &mut Vec<ElementId>,
+        target: Option<ElementId>,
+    ) -> Vec<ElementId> {
+        let chain = ancestor_chain(rdom, target);
+        let changed: Vec<ElementId> = update_pseudo_class(rdom, pseudo_class, flagged, &chain)
+            .into_iter()
+            .filter(|n| rdom[*n].state.style.depends_on(pseudo_class))
+            .collect();
+        WindowTask::start_animations(animating, rdom, changed.iter().copied());
+        if !changed.is_empty() {
+            if let DirtyNodes::Some(nodes) = dirty_nodes {
+                nodes.extend(changed);
+            }
+            proxy
+                .send_event(RendererEvent::Redraw(id))
+                .unwrap_or_else(|e| error!("{}", e));
+        }
+        std::mem::replace(flagged, chain)
+    }
+
+    // Start the transitions of the nodes whose style changed and keep track of
+    // the nodes with transitions or animations.
+    fn start_animations(
+        animating: &mut FxHashSet<ElementId>,
+        rdom: &mut RealDom<NodeState>,
+        nodes: impl IntoIterator<Item = ElementId>,
+    ) {
+        let now = Instant::now();
+        for node in nodes {
+            if rdom[node].state.start_animations(now) {
+                animating.insert(node);
+            }
+        }
+    }
+
+    // Ask the main thread to change the cursor, unless it is already shown.
+    fn set_cursor(
+        proxy: &EventLoopProxy<RendererEvent>,
+        state: &mut WindowState,
+        id: WindowId,
+        icon: CursorIcon,
+    ) {
+        if state.cursor != icon {
+            state.cursor = icon;
+            proxy
+                .send_event(RendererEvent::SetCursor(id, icon))
+                .unwrap_or_else(|e| error!("{}", e));
+        }
+    }
+
+    // Send blur and focusout to the node that lost the focus, then focus and
+    // focusin to the node that gained it. Only the `in` and `out` events bubble.
+    fn send_focus_events(
+        context: &WindowContext,
+        blurred: Option<ElementId>,
+        focused: Option<ElementId>,
+    ) {
+        let events = [
+            (blurred, "blur", false),
+            (blurred, "focusout", true),
+            (focused, "focus", false),
+            (focused, "focusin", true),
+        ];
+        for (element, name, bubbles) in events {
+            if element.is_some() {
+                let event = user_event(name, element, Arc::new(FocusData {}), bubbles);
+                WindowTask::send_event(context, event);
+            }
+        }
+    }
+
+    // Queue an event for the vdom. The queued events are handled one at a
+    // time before the vdom works, see `dispatch_events`
+    fn send_event(context: &WindowContext, event: UserEvent) {
+        context.queue_event(event, None);
+    }
+
+    // Queue a touch event, whose handlers can get its touches from the context
+    fn send_touch_event(context: &WindowContext, event: UserEvent, touches: TouchList) {
+        context.queue_event(event, Some(touches));
+    }
+
+    // Call the listeners of the queued events, with the target and the touches
+    // of each event shared with its handlers. The vdom calls the listeners
+    // from the target up, until a handler stops the propagation or one of an
+    // event that doesn't bubble runs. The events nobody listens to are
+    // dropped.
+    fn dispatch_events(vdom: &mut VirtualDom, rdom: &RealDom<NodeState>, context: &WindowContext) {
+        for (event, touches) in context.take_events() {
+            if !is_listened(rdom, &event) {
+                continue;
+            }
+            let target = event
+                .element
+                .filter(|element| rdom.get(element.0).is_some())
+                .map(|element| EventTarget {
+                    element,
+                    attributes: rdom[element].state.data.0.clone(),
+                });
+            context.set_event_target(target);
+            context.set_event_touches(touches);
+            vdom.process_message(SchedulerMsg::Event(event));
+        }
+        context.set_event_target(None);
+        context.set_event_touches(None);
+    }
+}
+
+// Returns true if the target of an event listens to it, or one of its
+// ancestors if it bubbles. The vdom looks for the closest listener even for
+// the events that don't bubble, so the parents of their target mustn't hear
+// them.
+fn is_listened(rdom: &RealDom<NodeState>, event: &UserEvent) -> bool {
+    let target = match event
+        .element
+        .filter(|element| rdom.get(element.0).is_some())
+    {
+        Some(target) => target,
+        None => return false,
+    };
+    let listening: FxHashSet<ElementId> = rdom
+        .get_listening_sorted(event.name)
+        .iter()
+        .map(|node| node.id)
+        .collect();
+    if !event.bubbles {
+        return listening.contains(&target);
+    }
+    let mut current = Some(target);
+    while let Some(node) = current {
+        if listening.contains(&node) {
+            return true;
+        }
+        current = rdom[node].parent;
+    }
+    false
+}
+
+// An event for the vdom, with the priority of its kind
+fn user_event(
+    name: &'static str,
+    element: Option<ElementId>,
+    data: Arc<dyn Any + Send + Sync>,
+    bubbles: bool,
+) -> UserEvent {
+    UserEvent {
+        scope_id: None,
+        priority: event_priority(name),
+        element,
+        name,
+        data,
+        bubbles,
+    }
+}
+
+// The input of the user is handled before the other events, so the app stays
+// responsive while components do heavy work. Observers of the layout come
+// last.
+fn event_priority(name: &str) -> EventPriority {
+    match name {
+        "keydown" | "keyup" | "keypress" | "click" | "dblclick" | "contextmenu" | "mousedown"
+        | "mouseup" | "mousemove" | "mouseenter" | "mouseleave" | "mouseover" | "mouseout"
+        | "wheel" | "pointerdown" | "pointermove" | "pointerup" | "pointercancel"
+        | "pointerover" | "pointerout" | "pointerenter" | "pointerleave" | "gotpointercapture"
+        | "lostpointercapture" | "touchstart" | "touchmove" | "touchend" | "touchcancel"
+        | "focus" | "blur" | "focusin" | "focusout" | "copy" | "cut" | "paste" => {
+            EventPriority::High
+        }
+        "scroll" | "resize" => EventPriority::Low,
+        _ => EventPriority::Medium,
+    }
+}
+
+// The clipboard event of a shortcut, with Cmd on macos and Ctrl elsewhere
+fn clipboard_event(key: VirtualKeyCode, modifiers: Modifiers) -> Option<&'static str> {
+    let command = if cfg!(target_os = "macos") {
+        Modifiers::META
+    } else {
+        Modifiers::CONTROL
+    };
+    if modifiers != command {
+        return None;
+    }
+    match key {
+        VirtualKeyCode::C => Some("copy"),
+        VirtualKeyCode::X => Some("cut"),
+        VirtualKeyCode::V => Some("paste"),
+        _ => None,
+    }
+}
+
+// Share the rects of the elements with the components after a layout
+pub(crate) fn set_layouts(context: &WindowContext, rdom: &RealDom<NodeState>, vdom: &VirtualDom) {
+    context.set_layouts(element_layouts(rdom), |scope| {
+        vdom.get_scope(scope)
+            .and_then(|scope| root_element(scope.root_node()))
+    });
+}
+
+// The closest node that is an inclusive ancestor of both nodes. The pressed
+// node may have been removed since.
+fn common_ancestor(rdom: &RealDom<NodeState>, a: ElementId, b: ElementId) -> Option<ElementId> {
+    rdom.get(a.0)?;
+    let mut ancestors = Vec::new();
+    let mut current = Some(a);
+    while let Some(node) = current {
+        ancestors.push(node);
+        current = rdom[node].parent;
+    }
+    let mut current = Some(b);
+    while let Some(node) = current {
+        if ancestors.contains(&node) {
+            return Some(node);
+        }
+        current = rdom[node].parent;
+    }
+    None
+}
+
+// Returns true if a node or one of its ancestors, which a bubbling event
+// reaches, prevents the default behavior of the event
+fn prevents(rdom: &RealDom<NodeState>, id: ElementId, event: PreventableEvent) -> bool {
+    let mut current = Some(id);
+    while let Some(node) = current {
+        if rdom[node].state.prevent_default.prevents(event) {
+            return true;
+        }
+        current = rdom[node].parent;
+    }
+    false
+}
+
+// The origin of the content of the window on the screen, in logical pixels
+fn window_origin(window: &glutin::window::Window, scale_factor: f64) -> (f32, f32) {
+    window
+        .inner_position()
+        .map(|position| {
+            let position = position.to_logical::<f32>(scale_factor);
+            (position.x, position.y)
+        })
+        .unwrap_or_default()
+}
+
+// The size of the window in logical pixels, the unit of the layout
+fn logical_size(size: PhysicalSize<u32>, scale_factor: f64) -> LayoutSize {
+    let size = size.to_logical::<f32>(scale_factor);
+    LayoutSize::new(size.width, size.height)
+}
+
+// The space available to the root node for a window of `size`
+pub(crate) fn available_space(size: PhysicalSize<u32>, scale_factor: f64) -> Size<Number> {
+    let size = logical_size(size, scale_factor);
+    Size {
+        width: Number::Defined(size.width),
+        height: Number::Defined(size.height),
+    }
+}
+
+// Read the RGBA pixels of a rect of the bound framebuffer, with the rows from
+// top to bottom
+pub(crate) fn read_pixels(
+    gl: &dyn gl::Gl,
+    rect: DeviceIntRect,
+    framebuffer_height: i32,
+) -> Vec<u8> {
+    let (width, height) = (rect.width(), rect.height());
+    if width <= 0 || height <= 0 {
+        return Vec::new();
+    }
+    // gl rows start at the bottom of the framebuffer
+    let y = framebuffer_height - rect.max.y;
+    let pixels = gl.read_pixels(rect.min.x, y, width, height, gl::RGBA, gl::UNSIGNED_BYTE);
+    pixels
+        .chunks_exact(width as usize * 4)
+        .rev()
+        .flatten()
+        .copied()
+        .collect()
+}
+
+// Convert RGBA pixels from premultiplied to straight alpha
+pub(crate) fn unpremultiply(pixels: &mut [u8]) {
+    for pixel in pixels.chunks_exact_mut(4) {
+        let alpha = pixel[3] as u32;
+        if alpha > 0 && alpha < 255 {
+            for c in &mut pixel[..3] {
+                *c = ((*c as u32 * 255 + alpha / 2) / alpha).min(255) as u8;
+            }
+        }
+    }
+}
+
+// Load the gl functions of the current context
+pub(crate) fn load_gl(context: &glutin::Context<PossiblyCurrent>) -> Rc<dyn gl::Gl> {
+    match context.get_api() {
+        glutin::Api::OpenGl => unsafe {
+            gl::GlFns::load_with(|symbol| context.get_proc_address(symbol) as *const _)
+        },
+        glutin::Api::OpenGlEs => unsafe {
+            gl::GlesFns::load_with(|symbol| context.get_proc_address(symbol) as *const _)
+        },
+        glutin::Api::WebGl => unimplemented!(),
+    }
+}
+
+// Create the context the node states are updated with
+pub(crate) fn state_context(
+    stretch: &Rc<RefCell<Taffy>>,
+    stylesheet: &Rc<Stylesheet>,
+    box_sizing: BoxSizing,
+) -> AnyMap {
+    let mut ctx = AnyMap::new();
+    ctx.insert(LayoutContext {
+        stretch: stretch.clone(),
+        stylesheet: stylesheet.clone(),
+        box_sizing,
+    });
+    ctx.insert(stylesheet.clone());
+    ctx
 }
 
 pub enum DirtyNodes {